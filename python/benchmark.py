@@ -106,6 +106,22 @@ def fetch_games(archive_url: str) -> List[dict]:
     resp.raise_for_status()
     return resp.json().get("games", [])
 
+def read_pgn_games(path: str) -> List[dict]:
+    """Games of a PGN file in the shape of the chess.com archive entries."""
+    games = []
+    with open(path) as f:
+        while True:
+            game = chess.pgn.read_game(f)
+            if game is None:
+                break
+            games.append({
+                "url": game.headers.get("Link", game.headers.get("Site", "")),
+                "pgn": str(game),
+                "white": {"username": game.headers.get("White", "")},
+                "black": {"username": game.headers.get("Black", "")},
+            })
+    return games
+
 def analyze_game(game_data: dict, username: str, sf_threads: int, depth: int) -> Optional[GameAnalysis]:
     pgn_str = game_data.get("pgn")
     if not pgn_str:
@@ -179,6 +195,7 @@ def main():
     parser.add_argument('--workers', type=int, default=4, help='Number of parallel workers')
     parser.add_argument('--threads', type=int, default=1, help='Stockfish threads per worker')
     parser.add_argument('--depth', type=int, default=4, help='Stockfish search depth')
+    parser.add_argument('--pgn', help='Analyze games from a PGN file instead of chess.com')
     args = parser.parse_args()
     
     total_cpu = args.workers * args.threads
@@ -193,18 +210,22 @@ def main():
     print(f"Stockfish depth: {args.depth}")
     print()
     
-    print("Fetching archives...")
     fetch_start = time.perf_counter()
-    archives = fetch_archives(args.username)
-    archives = list(reversed(archives))
-    
-    all_games = []
-    for archive_url in archives:
-        if len(all_games) >= args.games:
-            break
-        games = fetch_games(archive_url)
-        all_games.extend(games)
-        print(f"  Fetched {len(games)} games from {archive_url.split('/')[-2]}/{archive_url.split('/')[-1]}")
+    if args.pgn:
+        print(f"Reading {args.pgn}...")
+        all_games = read_pgn_games(args.pgn)
+    else:
+        print("Fetching archives...")
+        archives = fetch_archives(args.username)
+        archives = list(reversed(archives))
+
+        all_games = []
+        for archive_url in archives:
+            if len(all_games) >= args.games:
+                break
+            games = fetch_games(archive_url)
+            all_games.extend(games)
+            print(f"  Fetched {len(games)} games from {archive_url.split('/')[-2]}/{archive_url.split('/')[-1]}")
     
     all_games = all_games[:args.games]
     fetch_time = time.perf_counter() - fetch_start
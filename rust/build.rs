@@ -0,0 +1,15 @@
+use std::process::Command;
+
+// Embeds the git commit the binary was built from, for the run metadata.
+fn main() {
+    let commit = Command::new("git").args(["rev-parse", "--short=12", "HEAD"]).output().ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+    if let Some(commit) = commit {
+        println!("cargo:rustc-env=CHESS_BENCH_GIT_COMMIT={}", commit);
+    }
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+}
@@ -0,0 +1,214 @@
+use crate::analysis::MoveRecord;
+use crate::engine::Wdl;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Expected score for the given side, from a White-relative WDL.
+#[inline]
+pub fn wdl_to_prob(wdl: Wdl, is_white: bool) -> f64 {
+    let w = if is_white { wdl.win } else { wdl.loss };
+    (w as f64 + wdl.draw as f64 * 0.5) / 1000.0
+}
+
+#[inline]
+pub fn calc_accuracy(before: f64, after: f64) -> f64 {
+    if after >= before { 100.0 } else { (100.0 * (1.0 - (before - after) * 2.0)).max(0.0) }
+}
+
+#[inline]
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+}
+
+/// Moves and their mean accuracy in one bucket of a [`BucketSplit`].
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Bucket {
+    pub moves: usize,
+    pub accuracy: f64,
+}
+
+impl Bucket {
+    pub fn add(&mut self, other: Bucket) {
+        let n = self.moves + other.moves;
+        if n > 0 { self.accuracy = (self.accuracy * self.moves as f64 + other.accuracy * other.moves as f64) / n as f64; }
+        self.moves = n;
+    }
+}
+
+/// A player's accuracy with their moves sorted into labeled buckets (by
+/// material, piece, time pressure, ...). A split only says which buckets a
+/// move counts in and what they are called; reading a game's moves, merging
+/// games and the report section are shared.
+pub trait BucketSplit: Clone + Default {
+    /// Heading of the report section.
+    const TITLE: &'static str;
+    /// What the bucketing depends on besides the move, e.g. the time control.
+    type By: Copy;
+
+    /// Keys of the buckets move `r` counts in; none leaves it out.
+    fn keys(r: &MoveRecord, by: Self::By) -> Vec<usize>;
+
+    /// Bucket `key`.
+    fn bucket_mut(&mut self, key: usize) -> &mut Bucket;
+
+    /// Every bucket with its label, by key.
+    fn rows(&self) -> Vec<(String, Bucket)>;
+
+    /// Starts an empty split for one game.
+    fn empty(_by: Self::By) -> Self { Self::default() }
+
+    /// Whether splits `self` and `other` have the same buckets and can be
+    /// merged.
+    fn matches(&self, _other: &Self) -> bool { true }
+
+    /// The player's moves outside the book of one game, or `None` when none
+    /// of them falls in a bucket (e.g. no clock times for time pressure).
+    fn from_records(records: &[MoveRecord], is_white: bool, by: Self::By) -> Option<Self> {
+        let mut split = Self::empty(by);
+        let mut any = false;
+        for r in records.iter().filter(|r| r.is_white == is_white && !r.in_book) {
+            for key in Self::keys(r, by) {
+                split.bucket_mut(key).add(Bucket { moves: 1, accuracy: r.accuracy });
+                any = true;
+            }
+        }
+        any.then_some(split)
+    }
+
+    /// Move-weighted combination of several games. Splits that do not match
+    /// the first one are left out.
+    fn merge<'a>(splits: impl IntoIterator<Item = &'a Self>) -> Option<Self> where Self: 'a {
+        let mut total: Option<Self> = None;
+        for s in splits {
+            let Some(t) = &mut total else {
+                total = Some(s.clone());
+                continue;
+            };
+            if !t.matches(s) { continue; }
+            for (key, (_, b)) in s.rows().into_iter().enumerate() { t.bucket_mut(key).add(b); }
+        }
+        total
+    }
+
+    /// The report section, leaving out empty buckets.
+    fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\n{}", Self::TITLE)?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        for (name, b) in self.rows().into_iter().filter(|(_, b)| b.moves > 0) {
+            writeln!(out, "{:<8} {:>6} moves {:>7.2}%", name, b.moves, b.accuracy)?;
+        }
+        Ok(())
+    }
+}
+
+// Expected-score drops at which a move is downgraded
+const EXCELLENT_DROP: f64 = 0.02;
+const GOOD_DROP: f64 = 0.05;
+const INACCURACY_DROP: f64 = 0.10;
+const MISTAKE_DROP: f64 = 0.20;
+
+/// How a move's accuracy follows from the drop in expected score.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum AccuracyFormula {
+    /// 100 × (1 − 2 × drop): a 50-point drop scores zero.
+    #[default]
+    Linear,
+    /// Lichess' exponential curve on win-percentage points, gentler on
+    /// large drops.
+    Lichess,
+}
+
+/// How a side's move accuracies combine into its accuracy for the game.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Aggregation {
+    /// Plain mean of the moves.
+    #[default]
+    Mean,
+    /// Lichess' method: the mean of a volatility-weighted mean and the
+    /// harmonic mean, so that a few bad moves weigh more.
+    Lichess,
+}
+
+/// The accuracy formula, aggregation and classification thresholds used to
+/// score moves and games.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scoring {
+    pub formula: AccuracyFormula,
+    pub aggregation: Aggregation,
+    pub excellent_drop: f64,
+    pub good_drop: f64,
+    pub inaccuracy_drop: f64,
+    pub mistake_drop: f64,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            formula: AccuracyFormula::Linear,
+            aggregation: Aggregation::Mean,
+            excellent_drop: EXCELLENT_DROP,
+            good_drop: GOOD_DROP,
+            inaccuracy_drop: INACCURACY_DROP,
+            mistake_drop: MISTAKE_DROP,
+        }
+    }
+}
+
+impl Scoring {
+    pub fn accuracy(&self, before: f64, after: f64) -> f64 {
+        match self.formula {
+            AccuracyFormula::Linear => calc_accuracy(before, after),
+            AccuracyFormula::Lichess => {
+                let drop = 100.0 * (before - after).max(0.0);
+                (103.1668100711649 * (-0.04354415386753951 * drop).exp() - 3.166924740191411).clamp(0.0, 100.0)
+            }
+        }
+    }
+
+    pub fn classify(&self, before: f64, after: f64, is_best: bool) -> Classification {
+        let drop = before - after;
+        if is_best { Classification::Best }
+        else if drop < self.excellent_drop { Classification::Excellent }
+        else if drop < self.good_drop { Classification::Good }
+        else if drop < self.inaccuracy_drop { Classification::Inaccuracy }
+        else if drop < self.mistake_drop { Classification::Mistake }
+        else { Classification::Blunder }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Classification {
+    Best,
+    Excellent,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    pub const ALL: [Classification; 6] = [
+        Classification::Best,
+        Classification::Excellent,
+        Classification::Good,
+        Classification::Inaccuracy,
+        Classification::Mistake,
+        Classification::Blunder,
+    ];
+
+    /// Classifies a move from the mover's expected score before and after it.
+    pub fn from_drop(before: f64, after: f64, is_best: bool) -> Self {
+        Scoring::default().classify(before, after, is_best)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Best => "best",
+            Self::Excellent => "excellent",
+            Self::Good => "good",
+            Self::Inaccuracy => "inaccuracy",
+            Self::Mistake => "mistake",
+            Self::Blunder => "blunder",
+        }
+    }
+}
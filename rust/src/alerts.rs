@@ -0,0 +1,82 @@
+//! Alerts on rolling accuracy and blunder rate, for follow and scheduled runs.
+
+use crate::accuracy::mean;
+use crate::report::GameResult;
+use serde::Serialize;
+
+/// Thresholds checked over the last `window` games.
+#[derive(Clone, Debug)]
+pub struct AlertRules {
+    pub below_accuracy: Option<f64>,
+    /// Blunders per 100 moves of the player.
+    pub blunder_rate_above: Option<f64>,
+    pub window: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Alert {
+    pub message: String,
+    pub username: String,
+    pub games: usize,
+    pub rolling_accuracy: f64,
+    pub blunder_rate: f64,
+}
+
+impl AlertRules {
+    pub fn is_empty(&self) -> bool {
+        self.below_accuracy.is_none() && self.blunder_rate_above.is_none()
+    }
+
+    /// Checks the most recent games (oldest first); nothing fires until the
+    /// window is full.
+    pub fn check(&self, recent: &[&GameResult], target: &str) -> Option<Alert> {
+        if self.is_empty() || recent.len() < self.window || self.window == 0 { return None; }
+        let window = &recent[recent.len() - self.window..];
+        let acc: Vec<f64> = window.iter().map(|g| g.accuracy_for(target)).collect();
+        let rolling_accuracy = mean(&acc);
+        let moves: usize = window.iter().map(|g| g.moves_for(target)).sum();
+        let blunders: usize = window.iter().map(|g| g.blunders_for(target)).sum();
+        let blunder_rate = if moves == 0 { 0.0 } else { 100.0 * blunders as f64 / moves as f64 };
+
+        let mut reasons = Vec::new();
+        if let Some(min) = self.below_accuracy.filter(|&m| rolling_accuracy < m) {
+            reasons.push(format!("accuracy {:.1}% is below {:.1}%", rolling_accuracy, min));
+        }
+        if let Some(max) = self.blunder_rate_above.filter(|&m| blunder_rate > m) {
+            reasons.push(format!("blunder rate {:.1}/100 moves is above {:.1}", blunder_rate, max));
+        }
+        if reasons.is_empty() { return None; }
+        Some(Alert {
+            message: format!("{} over the last {} games: {}", target, self.window, reasons.join(", ")),
+            username: target.to_string(),
+            games: self.window,
+            rolling_accuracy,
+            blunder_rate,
+        })
+    }
+}
+
+/// Tracks games as they arrive and raises an alert once each time the
+/// thresholds start being crossed.
+pub struct AlertMonitor {
+    rules: AlertRules,
+    target: String,
+    recent: Vec<GameResult>,
+    firing: bool,
+}
+
+impl AlertMonitor {
+    pub fn new(rules: AlertRules, target: &str) -> Self {
+        Self { rules, target: target.to_lowercase(), recent: Vec::new(), firing: false }
+    }
+
+    pub fn push(&mut self, game: &GameResult) -> Option<Alert> {
+        self.recent.push(game.clone());
+        if self.recent.len() > self.rules.window { self.recent.remove(0); }
+        let refs: Vec<&GameResult> = self.recent.iter().collect();
+        let alert = self.rules.check(&refs, &self.target);
+        let fire = alert.is_some() && !self.firing;
+        self.firing = alert.is_some();
+        alert.filter(|_| fire)
+    }
+}
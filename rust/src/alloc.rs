@@ -0,0 +1,117 @@
+//! Counting global allocator for the `alloc-stats` feature.
+//!
+//! Install it with `#[global_allocator]`; the counters stay at zero otherwise
+//! and [`enabled`] reports `false`.
+
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static BYTES: AtomicU64 = AtomicU64::new(0);
+static CURRENT: AtomicU64 = AtomicU64::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD_ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static THREAD_BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting allocations and live bytes.
+pub struct CountingAlloc;
+
+impl CountingAlloc {
+    fn count(size: usize) {
+        ALLOCATIONS.fetch_add(1, Relaxed);
+        BYTES.fetch_add(size as u64, Relaxed);
+        let now = CURRENT.fetch_add(size as u64, Relaxed) + size as u64;
+        PEAK.fetch_max(now, Relaxed);
+        // Unavailable while the thread is being torn down
+        let _ = THREAD_ALLOCATIONS.try_with(|c| c.set(c.get() + 1));
+        let _ = THREAD_BYTES.try_with(|c| c.set(c.get() + size as u64));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let p = System.alloc(layout);
+        if !p.is_null() { Self::count(layout.size()); }
+        p
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let p = System.alloc_zeroed(layout);
+        if !p.is_null() { Self::count(layout.size()); }
+        p
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size() as u64, Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let p = System.realloc(ptr, layout, new_size);
+        if !p.is_null() {
+            CURRENT.fetch_sub(layout.size() as u64, Relaxed);
+            Self::count(new_size);
+        }
+        p
+    }
+}
+
+/// Allocation counters at one point in time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Snapshot {
+    pub allocations: u64,
+    pub bytes: u64,
+}
+
+impl Snapshot {
+    pub fn since(self, earlier: Snapshot) -> Snapshot {
+        Snapshot { allocations: self.allocations - earlier.allocations, bytes: self.bytes - earlier.bytes }
+    }
+}
+
+/// Whether the counting allocator is installed.
+pub fn enabled() -> bool { ALLOCATIONS.load(Relaxed) > 0 }
+
+/// Process-wide counters.
+pub fn snapshot() -> Snapshot {
+    Snapshot { allocations: ALLOCATIONS.load(Relaxed), bytes: BYTES.load(Relaxed) }
+}
+
+/// Counters of the calling thread only.
+pub fn thread_snapshot() -> Snapshot {
+    Snapshot { allocations: THREAD_ALLOCATIONS.with(Cell::get), bytes: THREAD_BYTES.with(Cell::get) }
+}
+
+/// Starts a new peak measurement from the bytes live now.
+pub fn reset_peak() { PEAK.store(CURRENT.load(Relaxed), Relaxed); }
+
+pub fn peak() -> u64 { PEAK.load(Relaxed) }
+
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct StageAlloc {
+    pub allocations: u64,
+    pub bytes: u64,
+    /// Peak heap in use during the stage; not tracked for parsing, which
+    /// runs interleaved with the engine.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_bytes: Option<u64>,
+}
+
+impl StageAlloc {
+    pub fn new(s: Snapshot, peak_bytes: Option<u64>) -> Self {
+        Self { allocations: s.allocations, bytes: s.bytes, peak_bytes }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+pub struct AllocStats {
+    pub fetch: StageAlloc,
+    pub parse: StageAlloc,
+    /// Analysis excluding parsing.
+    pub analysis: StageAlloc,
+}
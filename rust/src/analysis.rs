@@ -0,0 +1,319 @@
+use crate::accuracy::{mean, wdl_to_prob, Aggregation, Classification, Scoring};
+use crate::endgame::{classify, EndgameType};
+use crate::evalstore::EvalStore;
+use crate::material::material_balance;
+use crate::engine::{EngineError, Eval, Pv, Score, StockfishEngine, Timeout, Wdl};
+use shakmaty::{Chess, Position, fen::Fen, san::San, CastlingMode, EnPassantMode, Color, Role};
+use std::str::FromStr;
+
+/// One scored move of a game.
+#[derive(Clone, Debug)]
+pub struct MoveRecord<'a> {
+    /// 1-based half-move number.
+    pub ply: usize,
+    pub san: &'a str,
+    pub is_white: bool,
+    /// Evaluation of the position after the move.
+    pub eval: Eval,
+    /// Mover's expected score before and after the move.
+    pub prob_before: f64,
+    pub prob_after: f64,
+    pub accuracy: f64,
+    pub classification: Classification,
+    /// Mover's remaining clock after the move, in seconds, when known.
+    pub clock: Option<f64>,
+    /// Still opening theory, or on a config exclusion list; excluded from
+    /// accuracy.
+    pub in_book: bool,
+    /// Played in a time scramble (`--exclude-below-clock`); excluded from accuracy.
+    pub scramble: bool,
+    /// Endgame type of the position after the move, once the game is an endgame.
+    pub endgame: Option<EndgameType>,
+    /// Mover's material advantage before the move, in pawn units.
+    pub material: i32,
+    /// How much more the best move was worth than the second best in the
+    /// position before the move, in the mover's expected score (MultiPV ≥ 2).
+    pub criticality: Option<f64>,
+    /// Centipawns the move lost for the mover, evals capped at ±1000 and mates
+    /// counted as the cap.
+    pub cp_loss: u32,
+    /// Normalized entropy (0 to 1) of the WDL before the move: high when all
+    /// three results are still possible.
+    pub entropy: f64,
+    /// The piece moved (the king when castling).
+    pub piece: Role,
+    pub capture: bool,
+    pub check: bool,
+    pub castle: bool,
+}
+
+/// Where the evals a game was scored from came from.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EvalSource {
+    /// The run's engine, local or on remote workers.
+    #[default]
+    Engine,
+    /// Analysis stored with the game by the server (Lichess).
+    Server,
+    /// A --save-evals file of an earlier run.
+    Saved,
+}
+
+impl EvalSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EvalSource::Engine => "engine",
+            EvalSource::Server => "server",
+            EvalSource::Saved => "saved",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AnalysisError {
+    /// The movetext could not be replayed; retrying will not help.
+    Pgn(String),
+    /// The engine failed or exited; a fresh engine may succeed.
+    Engine(EngineError),
+    /// A position has no saved eval to rescore from (its ply).
+    MissingEval(usize),
+    /// The search of a position (its ply) ran past the timeout.
+    Timeout(usize),
+}
+
+impl std::fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalysisError::Pgn(e) => write!(f, "{}", e),
+            AnalysisError::Engine(e) => write!(f, "engine error: {}", e),
+            AnalysisError::MissingEval(ply) => write!(f, "no saved eval for the position at ply {}", ply),
+            AnalysisError::Timeout(ply) => write!(f, "search of the position at ply {} timed out", ply),
+        }
+    }
+}
+
+impl std::error::Error for AnalysisError {}
+
+impl From<EngineError> for AnalysisError {
+    fn from(e: EngineError) -> Self { AnalysisError::Engine(e) }
+}
+
+/// Replays `moves` from the initial position and scores every move.
+/// `evaluate` is called with each position and its ply (0 = initial position).
+/// A [`AnalysisError::Timeout`] once a move has been scored ends the game
+/// early: the moves scored so far are returned.
+pub fn score_moves<'a, F>(moves: &[&'a str], scoring: &Scoring, mut evaluate: F) -> Result<Vec<MoveRecord<'a>>, AnalysisError>
+where F: FnMut(&Chess, usize) -> Result<Eval, AnalysisError> {
+    let mut pos = Chess::default();
+    let mut records = Vec::with_capacity(moves.len());
+    let mut prev = evaluate(&pos, 0)?;
+
+    for (i, &m) in moves.iter().enumerate() {
+        let is_white = pos.turn() == Color::White;
+        let replay = crate::spans::span("replay");
+        let san = San::from_str(m).map_err(|_| AnalysisError::Pgn(format!("invalid SAN {:?} at ply {}", m, i + 1)))?;
+        let mv = san.to_move(&pos).map_err(|_| AnalysisError::Pgn(format!("illegal move {:?} at ply {}", m, i + 1)))?;
+        let material = material_balance(pos.board(), pos.turn());
+        let is_best = prev.best_move == Some(mv.to_uci(CastlingMode::Standard));
+        let (piece, capture, castle) = (mv.role(), mv.is_capture(), mv.is_castle());
+        pos = pos.play(mv).map_err(|e| AnalysisError::Pgn(e.to_string()))?;
+        drop(replay);
+
+        let eval = match evaluate(&pos, i + 1) {
+            Err(AnalysisError::Timeout(_)) if !records.is_empty() => break,
+            r => r?,
+        };
+        let span = crate::spans::span("accuracy");
+        let prob_before = wdl_to_prob(prev.wdl, is_white);
+        let prob_after = wdl_to_prob(eval.wdl, is_white);
+        let criticality = prev.second.map(|second| {
+            let expected = |s| wdl_to_prob(Wdl::from_score(s), is_white);
+            (expected(prev.score) - expected(second)).max(0.0)
+        });
+        records.push(MoveRecord {
+            ply: i + 1,
+            san: m,
+            is_white,
+            eval,
+            prob_before,
+            prob_after,
+            accuracy: scoring.accuracy(prob_before, prob_after),
+            classification: scoring.classify(prob_before, prob_after, is_best),
+            clock: None,
+            in_book: false,
+            scramble: false,
+            endgame: classify(pos.board()),
+            material,
+            criticality,
+            cp_loss: cp_loss(prev.score, eval.score, is_white),
+            entropy: crate::sharpness::wdl_entropy(prev.wdl),
+            piece,
+            capture,
+            check: pos.is_check(),
+            castle,
+        });
+        drop(span);
+        prev = eval;
+    }
+    Ok(records)
+}
+
+/// Scores every move with the engine, keeping each position's eval in
+/// `store` when given. Positions reached and left only by `excluded` moves
+/// (one flag per move, or none) are not searched; they take the eval before
+/// them.
+pub fn analyze_moves<'a>(engine: &mut StockfishEngine, moves: &[&'a str], excluded: &[bool], store: Option<&EvalStore>,
+                         scoring: &Scoring) -> Result<Vec<MoveRecord<'a>>, AnalysisError> {
+    score_searched(moves, excluded, scoring, |pos, ply| engine_eval(engine, pos, ply, store))
+}
+
+/// Whether the position at `ply` is reached or left by a move not in
+/// `excluded` (one flag per move, or none), so that it needs a search.
+pub fn needs_search(excluded: &[bool], ply: usize) -> bool {
+    excluded.is_empty() || (ply > 0 && !excluded[ply - 1]) || excluded.get(ply).is_some_and(|e| !e)
+}
+
+/// Scores every move, calling `search` only for the positions that
+/// [`needs_search`]; the others take the eval before them.
+pub fn score_searched<'a>(moves: &[&'a str], excluded: &[bool], scoring: &Scoring,
+                          mut search: impl FnMut(&Chess, usize) -> Result<Eval, AnalysisError>) -> Result<Vec<MoveRecord<'a>>, AnalysisError> {
+    let mut last = None;
+    score_moves(moves, scoring, |pos, ply| {
+        let eval = match last {
+            Some(last) if !needs_search(excluded, ply) => last,
+            None if !needs_search(excluded, ply) => initial_eval(),
+            _ => search(pos, ply)?,
+        };
+        last = Some(eval);
+        Ok(eval)
+    })
+}
+
+/// Evaluates the position at `ply` with the engine, keeping the eval in
+/// `store` when given.
+pub fn engine_eval(engine: &mut StockfishEngine, pos: &Chess, ply: usize, store: Option<&EvalStore>) -> Result<Eval, AnalysisError> {
+    let fen = {
+        let _span = crate::spans::span("fen");
+        Fen::from_position(pos, EnPassantMode::Legal).to_string()
+    };
+    let _span = crate::spans::span("engine");
+    let eval = engine.analyze(&fen, pos.turn() == Color::White)
+        .map_err(|e| if e.is::<Timeout>() { AnalysisError::Timeout(ply) } else { AnalysisError::Engine(e) })?;
+    if let Some(store) = store { store.record(pos, engine.info().depth, &eval); }
+    Ok(eval)
+}
+
+/// Scores every move from evaluations already attached to the game (one per
+/// ply, White's point of view), without running an engine.
+pub fn score_with_evals<'a>(moves: &[&'a str], evals: &[Score], scoring: &Scoring) -> Result<Vec<MoveRecord<'a>>, AnalysisError> {
+    if evals.len() < moves.len() {
+        return Err(AnalysisError::Pgn(format!("{} evals for {} moves", evals.len(), moves.len())));
+    }
+    score_moves(moves, scoring, |_, ply| Ok(if ply == 0 { initial_eval() } else { score_eval(evals[ply - 1]) }))
+}
+
+fn score_eval(score: Score) -> Eval {
+    Eval { score, wdl: Wdl::from_score(score), best_move: None, second: None, pv: Pv::default() }
+}
+
+fn initial_eval() -> Eval { score_eval(INITIAL_SCORE) }
+
+/// Scores every move from evals saved by an earlier run, without an engine.
+pub fn rescore_moves<'a>(moves: &[&'a str], store: &EvalStore, scoring: &Scoring) -> Result<Vec<MoveRecord<'a>>, AnalysisError> {
+    score_moves(moves, scoring, |pos, ply| store.get(pos).map(|s| s.eval).ok_or(AnalysisError::MissingEval(ply)))
+}
+
+/// Evaluations beyond this many centipawns count as decided for cp loss.
+const CP_CAP: i32 = 1000;
+
+/// The score in centipawns for the mover, capped at ±1000 with mates at the cap.
+pub fn capped_cp(score: Score, is_white: bool) -> i32 {
+    let cp = match score {
+        Score::Cp(cp) => cp.clamp(-CP_CAP, CP_CAP),
+        Score::Mate(n) if n > 0 => CP_CAP,
+        Score::Mate(n) if n < 0 => -CP_CAP,
+        // Checkmate on the board, which only follows the mover's own move
+        Score::Mate(_) => if is_white { CP_CAP } else { -CP_CAP },
+    };
+    if is_white { cp } else { -cp }
+}
+
+/// Centipawns the mover lost going from `before` to `after`.
+pub fn cp_loss(before: Score, after: Score, is_white: bool) -> u32 {
+    (capped_cp(before, is_white) - capped_cp(after, is_white)).max(0) as u32
+}
+
+/// Evaluation assumed for the initial position when only post-move evals are known.
+const INITIAL_SCORE: Score = Score::Cp(15);
+
+/// Average move accuracy of one side over its non-book, non-scramble moves,
+/// 0.0 when there are none.
+pub fn side_accuracy(records: &[MoveRecord], is_white: bool) -> f64 {
+    let acc: Vec<f64> = records.iter().filter(|r| r.is_white == is_white && !r.in_book && !r.scramble).map(|r| r.accuracy).collect();
+    mean(&acc)
+}
+
+/// One side's accuracy for the game, its moves combined as `scoring` says.
+/// Book and scramble moves are left out as in [`side_accuracy`].
+pub fn game_accuracy(records: &[MoveRecord], is_white: bool, scoring: &Scoring) -> f64 {
+    match scoring.aggregation {
+        Aggregation::Mean => side_accuracy(records, is_white),
+        Aggregation::Lichess => lichess_accuracy(records, is_white),
+    }
+}
+
+/// Lichess' game accuracy: each move weighted by the standard deviation
+/// (0.5 to 12) of White's win percentage over a window of plies around it,
+/// the weighted mean then averaged with the harmonic mean of the moves.
+fn lichess_accuracy(records: &[MoveRecord], is_white: bool) -> f64 {
+    let Some(first) = records.first() else { return 0.0 };
+    let white_pct = |p: f64, white: bool| 100.0 * if white { p } else { 1.0 - p };
+    let mut series = Vec::with_capacity(records.len() + 1);
+    series.push(white_pct(first.prob_before, first.is_white));
+    series.extend(records.iter().map(|r| white_pct(r.prob_after, r.is_white)));
+
+    let size = (records.len() / 10).clamp(2, 8).min(series.len());
+    let head = &series[..size];
+    let windows = std::iter::repeat_n(head, size - 2).chain(series.windows(size));
+    let (mut weighted, mut weights, mut inverse, mut moves) = (0.0, 0.0, 0.0, 0);
+    for (r, window) in records.iter().zip(windows) {
+        if r.is_white != is_white || r.in_book || r.scramble { continue; }
+        let avg = mean(window);
+        let sd = (window.iter().map(|x| (x - avg).powi(2)).sum::<f64>() / window.len() as f64).sqrt();
+        let weight = sd.clamp(0.5, 12.0);
+        weighted += r.accuracy * weight;
+        weights += weight;
+        inverse += 1.0 / r.accuracy.max(1.0);
+        moves += 1;
+    }
+    if moves == 0 { return 0.0; }
+    (weighted / weights + moves as f64 / inverse) / 2.0
+}
+
+/// One side's scramble moves and their average accuracy, out of book.
+pub fn scramble_accuracy(records: &[MoveRecord], is_white: bool) -> Scramble {
+    let acc: Vec<f64> = records.iter().filter(|r| r.is_white == is_white && !r.in_book && r.scramble).map(|r| r.accuracy).collect();
+    Scramble { moves: acc.len(), accuracy: mean(&acc) }
+}
+
+/// Moves played with little time left, kept out of the headline accuracy.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct Scramble {
+    pub moves: usize,
+    pub accuracy: f64,
+}
+
+impl Scramble {
+    /// Move-weighted combination of per-game scrambles.
+    pub fn merge<'a>(items: impl Iterator<Item = &'a Scramble>) -> Option<Self> {
+        let mut seen = false;
+        let (mut moves, mut sum) = (0, 0.0);
+        for s in items {
+            seen = true;
+            moves += s.moves;
+            sum += s.accuracy * s.moves as f64;
+        }
+        seen.then(|| Scramble { moves, accuracy: if moves == 0 { 0.0 } else { sum / moves as f64 } })
+    }
+}
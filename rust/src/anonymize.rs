@@ -0,0 +1,83 @@
+//! Stable pseudonyms for usernames and game URLs, so results can be shared
+//! without the players' identities.
+//!
+//! Pseudonyms are a hash (FNV-1a, then a final mix so similar names differ
+//! in every digit) of the value and an optional key: the same
+//! player gets the same name in every run with the same key, and without the
+//! key a name cannot be checked by hashing guesses.
+
+use crate::report::{GameResult, Summary};
+
+pub struct Anonymizer {
+    key: String,
+}
+
+impl Anonymizer {
+    pub fn new(key: &str) -> Self { Self { key: key.to_string() } }
+
+    fn hash(&self, value: &str) -> u64 {
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        for b in self.key.bytes().chain([0]).chain(value.bytes()) {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x0100_0000_01b3);
+        }
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^ (h >> 33)
+    }
+
+    /// "player-3f2a9c1d", the same for any capitalization of the name.
+    pub fn name(&self, name: &str) -> String {
+        format!("player-{:08x}", self.hash(&name.to_lowercase()) >> 32)
+    }
+
+    /// "game-5e0c91ab02f4".
+    pub fn url(&self, url: &str) -> String {
+        format!("game-{:012x}", self.hash(url) >> 16)
+    }
+
+    pub fn game(&self, g: &mut GameResult) {
+        // chess.com names the winner in the Termination tag
+        if let Some(t) = &mut g.headers.termination {
+            *t = t.split(' ').map(|w| {
+                if w.eq_ignore_ascii_case(&g.white) || w.eq_ignore_ascii_case(&g.black) { self.name(w) } else { w.to_string() }
+            }).collect::<Vec<_>>().join(" ");
+        }
+        g.url = g.url.as_deref().map(|u| self.url(u));
+        g.white = self.name(&g.white);
+        g.black = self.name(&g.black);
+        let h = &mut g.headers;
+        for name in [&mut h.white, &mut h.black].into_iter().flatten() { *name = self.name(name); }
+        h.site = h.site.as_deref().map(|s| self.url(s));
+        h.eco_url = None;
+    }
+
+    /// The summary's player, listings and command line.
+    pub fn summary(&self, s: &mut Summary) {
+        let target = s.username.to_lowercase();
+        s.username = self.name(&s.username);
+        if let Some(run) = &mut s.run {
+            for arg in run.args.iter_mut().filter(|a| a.to_lowercase() == target) { *arg = s.username.clone(); }
+        }
+        if let Some(l) = &mut s.listings {
+            for g in &mut l.worst_games {
+                g.url = g.url.as_deref().map(|u| self.url(u));
+                g.opponent = self.name(&g.opponent);
+            }
+            for b in &mut l.blunders {
+                b.url = b.url.as_deref().map(|u| self.url(u));
+                b.opponent = self.name(&b.opponent);
+            }
+        }
+        if let Some(o) = &mut s.opponents {
+            for r in &mut o.rows { r.opponent = self.name(&r.opponent); }
+        }
+    }
+
+    pub fn results(&self, s: &mut Summary, games: &mut [GameResult]) {
+        self.summary(s);
+        for g in games { self.game(g); }
+    }
+}
@@ -1,3 +1,4 @@
+use chess_bench::pgn::{parse_pgn_moves, MoveTokens};
 use clap::Parser;
 use rayon::prelude::*;
 use serde::Deserialize;
@@ -24,28 +25,6 @@ struct GamesResponse { games: Vec<GameData> }
 #[derive(Deserialize, Clone)]
 struct GameData { pgn: Option<String> }
 
-fn parse_pgn_moves(pgn: &str) -> Vec<String> {
-    let mut moves = Vec::new();
-    let mut in_moves = false;
-    for line in pgn.lines() {
-        let line = line.trim();
-        if line.starts_with('[') { continue; }
-        if !line.is_empty() { in_moves = true; }
-        if in_moves {
-            let mut cleaned = line.to_string();
-            while let Some(s) = cleaned.find('{') {
-                if let Some(e) = cleaned.find('}') {
-                    cleaned = format!("{}{}", &cleaned[..s], &cleaned[e+1..]);
-                } else { break; }
-            }
-            moves.extend(cleaned.split_whitespace()
-                .filter(|t| !t.contains('.') && !["1-0","0-1","1/2-1/2","*"].contains(t))
-                .map(String::from));
-        }
-    }
-    moves
-}
-
 fn fetch_archives(username: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let client = reqwest::blocking::Client::builder().user_agent("ChessBenchmark/1.0").build()?;
     let resp: ArchivesResponse = client.get(format!("https://api.chess.com/pub/player/{}/games/archives", username)).send()?.json()?;
@@ -67,7 +46,7 @@ fn parse_game(pgn: &str) -> (usize, usize) {
     let mut pc = 1;
 
     for m in moves {
-        if let Ok(san) = San::from_str(&m) {
+        if let Ok(san) = San::from_str(m) {
             if let Ok(mv) = san.to_move(&pos) {
                 if let Ok(new_pos) = pos.clone().play(mv) {
                     pos = new_pos;
@@ -112,6 +91,12 @@ fn main() {
     let fetch_time = fetch_start.elapsed();
     println!("Fetched {} games in {:.2}s\n", all_pgns.len(), fetch_time.as_secs_f64());
 
+    // Tokenizer alone, single-threaded: no SAN replay or FEN generation
+    let token_start = Instant::now();
+    let tokens: usize = all_pgns.iter().map(|p| MoveTokens::new(p).count()).sum();
+    let token_time = token_start.elapsed().as_secs_f64();
+    let bytes: usize = all_pgns.iter().map(String::len).sum();
+
     println!("Parsing PGNs...");
     let parse_start = Instant::now();
     let completed = Arc::new(AtomicUsize::new(0));
@@ -120,7 +105,7 @@ fn main() {
     let results: Vec<_> = all_pgns.par_iter().map(|p| {
         let r = parse_game(p);
         let c = completed.fetch_add(1, Ordering::SeqCst) + 1;
-        if c % 100 == 0 || c == total {
+        if c.is_multiple_of(100) || c == total {
             println!("  Parsed {}/{} games ({:.2} games/sec)", c, total, c as f64 / parse_start.elapsed().as_secs_f64());
         }
         r
@@ -134,9 +119,15 @@ fn main() {
     println!("{}", "=".repeat(50));
     println!("Games parsed: {}", parsed);
     println!("Total moves: {}", tm);
+    println!("Total positions: {}", tp);
     println!("\nPerformance");
     println!("{}", "=".repeat(50));
     println!("Parse time: {:.4}s", parse_time.as_secs_f64());
     println!("Games per second: {:.2}", parsed as f64 / parse_time.as_secs_f64());
     println!("Moves per second: {:.2}", tm as f64 / parse_time.as_secs_f64());
+    println!("\nTokenizer (1 thread)");
+    println!("{}", "=".repeat(50));
+    println!("Tokenize time: {:.4}s", token_time);
+    println!("Tokens per second: {:.2}", tokens as f64 / token_time);
+    println!("Throughput: {:.2} MB/s", bytes as f64 / 1e6 / token_time);
 }
@@ -0,0 +1,289 @@
+//! The player's worst games and biggest blunders, listed for the report.
+
+use crate::accuracy::Classification;
+use crate::analysis::MoveRecord;
+use crate::engine::Score;
+use crate::motifs::{find_motifs, value, Motif};
+use crate::report::{GameResult, Side};
+use serde::{Deserialize, Serialize};
+use shakmaty::{fen::Fen, san::San, uci::UciMove, Chess, Color, EnPassantMode, Move, Position, Role};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// A blunder by the player.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Blunder {
+    pub ply: usize,
+    pub san: String,
+    /// Position before the move.
+    pub fen: String,
+    /// Expected score the move threw away.
+    pub swing: f64,
+    pub cp_loss: u32,
+    /// The engine's best move instead, in UCI, when known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best: Option<String>,
+    /// The engine's best reply to the blunder, in UCI.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refutation: Option<String>,
+    #[serde(default)]
+    pub cause: Cause,
+    /// Tactical motifs in the engine's line after the blunder.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub motifs: Vec<Motif>,
+}
+
+/// Best guess at why a move was a blunder, from the position and the
+/// engine's reply alone.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Cause {
+    /// The reply is a rook or queen check on the player's back rank, with
+    /// mate on the board.
+    BackRankMate,
+    /// The reply takes an undefended piece.
+    HangingPiece,
+    /// The player recaptured while the engine preferred a check or a
+    /// capture elsewhere first.
+    MissedInBetween,
+    /// The reply takes a piece worth more than the one taking it.
+    LostMaterial,
+    #[default]
+    Other,
+}
+
+impl Cause {
+    pub const ALL: [Cause; 5] = [Cause::BackRankMate, Cause::HangingPiece, Cause::MissedInBetween, Cause::LostMaterial, Cause::Other];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::BackRankMate => "back-rank mate",
+            Self::HangingPiece => "hanging piece",
+            Self::MissedInBetween => "missed in-between move",
+            Self::LostMaterial => "lost material",
+            Self::Other => "other",
+        }
+    }
+
+    /// `before` is the position the blunder `mv` was played in, `prev` the
+    /// opponent's move that led to it, `after` the eval once it was played.
+    fn classify(before: &Chess, mv: Move, prev: Option<Move>, best: Option<&UciMove>, refutation: Option<&UciMove>, after: Score) -> Self {
+        let us = before.turn();
+        let mut pos = before.clone();
+        pos.play_unchecked(mv);
+        let reply = refutation.and_then(|m| m.to_move(&pos).ok());
+
+        if let Some(reply) = reply {
+            let mated = matches!(after, Score::Mate(n) if (n > 0) != (us == Color::White));
+            let back_rank = us.backrank();
+            if mated && matches!(reply.role(), Role::Rook | Role::Queen) && reply.to().rank() == back_rank
+                && pos.board().king_of(us).is_some_and(|k| k.rank() == back_rank) {
+                let mut next = pos.clone();
+                next.play_unchecked(reply);
+                if next.is_check() { return Self::BackRankMate; }
+            }
+            if reply.is_capture() {
+                let board = pos.board();
+                // Without the capturing piece, so defenders behind it count
+                let occupied = reply.from().map_or(board.occupied(), |sq| board.occupied().without(sq));
+                if reply.capture() != Some(Role::Pawn) && board.attacks_to(reply.to(), us, occupied).is_empty() {
+                    return Self::HangingPiece;
+                }
+            }
+        }
+
+        let recapture = prev.is_some_and(|p| p.is_capture() && p.to() == mv.to()) && mv.is_capture();
+        if recapture {
+            let best = best.and_then(|m| m.to_move(before).ok());
+            if let Some(best) = best.filter(|b| b.to() != mv.to()) {
+                let mut alt = before.clone();
+                alt.play_unchecked(best);
+                if alt.is_check() || best.is_capture() { return Self::MissedInBetween; }
+            }
+        }
+
+        if let Some(reply) = reply {
+            if reply.capture().is_some_and(|c| value(c) > value(reply.role())) { return Self::LostMaterial; }
+        }
+        Self::Other
+    }
+}
+
+/// Non-book blunders by the side to move, in game order.
+pub fn find_blunders(records: &[MoveRecord], is_white: bool) -> Vec<Blunder> {
+    let mut pos = Chess::default();
+    let mut prev = None;
+    let mut out = Vec::new();
+    for (i, r) in records.iter().enumerate() {
+        // Records were scored from these moves, so they replay
+        let Some(mv) = San::from_str(r.san).ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+        if r.is_white == is_white && r.classification == Classification::Blunder && !r.in_book && !r.scramble {
+            let best = i.checked_sub(1).and_then(|p| records[p].eval.best_move.as_ref());
+            let mut after = pos.clone();
+            after.play_unchecked(mv);
+            let mated = matches!(r.eval.score, Score::Mate(n) if (n > 0) != is_white);
+            out.push(Blunder {
+                ply: r.ply,
+                san: r.san.to_string(),
+                fen: Fen::from_position(&pos, EnPassantMode::Legal).to_string(),
+                swing: r.prob_before - r.prob_after,
+                cp_loss: r.cp_loss,
+                best: best.map(|m| m.to_string()),
+                refutation: r.eval.best_move.as_ref().map(|m| m.to_string()),
+                cause: Cause::classify(&pos, mv, prev, best, r.eval.best_move.as_ref(), r.eval.score),
+                motifs: find_motifs(&after, r.eval.pv.moves(), mated),
+            });
+        }
+        pos.play_unchecked(mv);
+        prev = Some(mv);
+    }
+    out
+}
+
+/// Largest expected-score drop of one of the side's non-book moves.
+pub fn max_swing(records: &[MoveRecord], is_white: bool) -> f64 {
+    records.iter().filter(|r| r.is_white == is_white && !r.in_book)
+        .map(|r| (r.prob_before - r.prob_after).max(0.0))
+        .fold(0.0, f64::max)
+}
+
+#[derive(Serialize, Debug)]
+pub struct CauseRow {
+    pub cause: Cause,
+    pub blunders: usize,
+    pub share: f64,
+}
+
+/// The player's blunders counted by cause.
+#[derive(Serialize, Debug)]
+pub struct BlunderCauses {
+    pub blunders: usize,
+    pub rows: Vec<CauseRow>,
+}
+
+impl BlunderCauses {
+    pub fn from_games(games: &[GameResult]) -> Option<Self> {
+        let causes: Vec<Cause> = games.iter().flat_map(|g| g.blunder_moves.iter().map(|b| b.cause)).collect();
+        if causes.is_empty() { return None; }
+        let rows = Cause::ALL.iter().map(|&cause| {
+            let n = causes.iter().filter(|&&c| c == cause).count();
+            CauseRow { cause, blunders: n, share: n as f64 / causes.len() as f64 }
+        }).filter(|r| r.blunders > 0).collect();
+        Some(Self { blunders: causes.len(), rows })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nBlunder Causes")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        for r in &self.rows {
+            writeln!(out, "{:<24} {:>5} {:>6.1}%", r.cause.as_str(), r.blunders, 100.0 * r.share)?;
+        }
+        writeln!(out, "{:<24} {:>5}", "Total", self.blunders)
+    }
+}
+
+/// Order of the worst-games and blunder listings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Lowest accuracy first; for blunders the same as `swing`.
+    Accuracy,
+    /// Most recent first.
+    Date,
+    /// Largest expected-score drop first.
+    Swing,
+}
+
+impl SortKey {
+    pub fn as_str(self) -> &'static str {
+        match self { Self::Accuracy => "accuracy", Self::Date => "date", Self::Swing => "swing" }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct GameEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    pub opponent: String,
+    pub side: Side,
+    pub accuracy: f64,
+    pub blunders: usize,
+    pub swing: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BlunderEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    pub opponent: String,
+    #[serde(flatten)]
+    pub blunder: Blunder,
+}
+
+/// The top entries of the worst-games and blunder listings.
+#[derive(Serialize, Debug)]
+pub struct Listings {
+    pub sort: SortKey,
+    pub worst_games: Vec<GameEntry>,
+    pub blunders: Vec<BlunderEntry>,
+}
+
+impl Listings {
+    pub fn from_games(games: &[GameResult], target: &str, top: usize, sort: SortKey) -> Option<Self> {
+        if games.is_empty() { return None; }
+        let opponent = |g: &GameResult| if g.white == target { g.black.clone() } else { g.white.clone() };
+        let mut ordered: Vec<&GameResult> = games.iter().collect();
+        match sort {
+            SortKey::Accuracy => ordered.sort_by(|a, b| a.accuracy_for(target).total_cmp(&b.accuracy_for(target))),
+            SortKey::Date => ordered.sort_by(|a, b| b.end_time.cmp(&a.end_time).then_with(|| b.headers.date.cmp(&a.headers.date))),
+            SortKey::Swing => ordered.sort_by(|a, b| b.max_swing.unwrap_or(0.0).total_cmp(&a.max_swing.unwrap_or(0.0))),
+        }
+        let worst_games = ordered.iter().take(top).map(|g| GameEntry {
+            url: g.url.clone(),
+            date: g.date(),
+            opponent: opponent(g),
+            side: g.side_of(target),
+            accuracy: g.accuracy_for(target),
+            blunders: g.blunders_for(target),
+            swing: g.max_swing.unwrap_or(0.0),
+        }).collect();
+
+        // `ordered` already has the games most recent first when sorting by date
+        let mut blunders: Vec<BlunderEntry> = ordered.iter().flat_map(|g| g.blunder_moves.iter().map(|b| BlunderEntry {
+            url: g.url.clone(),
+            date: g.date(),
+            opponent: opponent(g),
+            blunder: b.clone(),
+        })).collect();
+        if sort != SortKey::Date { blunders.sort_by(|a, b| b.blunder.swing.total_cmp(&a.blunder.swing)); }
+        blunders.truncate(top);
+        Some(Self { sort, worst_games, blunders })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nWorst Games (by {})", self.sort.as_str())?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<10}  {:<5}  {:<16}  {:>8}  {:>8}  {:>6}  URL", "Date", "Color", "Opponent", "Accuracy", "Blunders", "Swing")?;
+        for g in &self.worst_games {
+            let color = match g.side { Side::White => "White", Side::Black => "Black" };
+            writeln!(out, "{:<10}  {:<5}  {:<16}  {:>7.2}%  {:>8}  {:>5.0}%  {}", g.date.as_deref().unwrap_or("-"), color,
+                g.opponent, g.accuracy, g.blunders, 100.0 * g.swing, g.url.as_deref().unwrap_or("-"))?;
+        }
+        writeln!(out, "\nBiggest Blunders (by {})", if self.sort == SortKey::Date { "date" } else { "swing" })?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        if self.blunders.is_empty() { return writeln!(out, "No blunders."); }
+        writeln!(out, "{:<10}  {:<16}  {:<12}  {:>6}  {:>7}  {:<6}  {:<22}  Motifs", "Date", "Opponent", "Move", "Swing", "cp loss", "Best", "Cause")?;
+        for e in &self.blunders {
+            let b = &e.blunder;
+            let mv = format!("{}{} {}", b.ply.div_ceil(2), if b.ply % 2 == 1 { "." } else { "..." }, b.san);
+            let motifs = if b.motifs.is_empty() { "-".to_string() } else { b.motifs.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", ") };
+            writeln!(out, "{:<10}  {:<16}  {:<12}  {:>5.0}%  {:>7}  {:<6}  {:<22}  {}", e.date.as_deref().unwrap_or("-"), e.opponent, mv,
+                100.0 * b.swing, b.cp_loss, b.best.as_deref().unwrap_or("-"), b.cause.as_str(), motifs)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+//! Calibration of the engine's WDL forecasts against game results.
+
+use crate::accuracy::wdl_to_prob;
+use crate::analysis::MoveRecord;
+use crate::report::GameResult;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Forecast bins of equal width over White's expected score.
+const BINS: usize = 10;
+
+/// White's expected score after `move_number` full moves.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug)]
+pub struct Forecast {
+    pub move_number: usize,
+    pub white_expected: f64,
+}
+
+/// Forecasts at each of `move_numbers` the game lasted to.
+pub fn forecasts(records: &[MoveRecord], move_numbers: &[usize]) -> Vec<Forecast> {
+    move_numbers.iter().filter_map(|&n| {
+        let r = records.get((2 * n).checked_sub(1)?)?;
+        Some(Forecast { move_number: n, white_expected: wdl_to_prob(r.eval.wdl, true) })
+    }).collect()
+}
+
+#[derive(Serialize, Debug)]
+pub struct CalibrationBin {
+    /// Lower edge of White's forecast expected score.
+    pub from: f64,
+    pub games: usize,
+    pub mean_forecast: f64,
+    pub mean_result: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CalibrationCurve {
+    pub move_number: usize,
+    pub games: usize,
+    /// Mean squared error of the forecast against the result.
+    pub brier: f64,
+    pub bins: Vec<CalibrationBin>,
+}
+
+/// Predicted vs actual results at each checkpoint move.
+#[derive(Serialize, Debug)]
+pub struct Calibration {
+    pub curves: Vec<CalibrationCurve>,
+}
+
+impl Calibration {
+    pub fn from_games(games: &[GameResult]) -> Option<Self> {
+        let mut moves: Vec<usize> = games.iter().flat_map(|g| g.forecasts.iter().map(|f| f.move_number)).collect();
+        moves.sort_unstable();
+        moves.dedup();
+        let curves: Vec<CalibrationCurve> = moves.into_iter().filter_map(|n| {
+            let pairs: Vec<(f64, f64)> = games.iter()
+                .filter_map(|g| Some((g.forecasts.iter().find(|f| f.move_number == n)?.white_expected, g.score_for(&g.white)?)))
+                .collect();
+            if pairs.is_empty() { return None; }
+            let bins = (0..BINS).filter_map(|b| {
+                let from = b as f64 / BINS as f64;
+                let in_bin: Vec<&(f64, f64)> = pairs.iter()
+                    .filter(|(p, _)| ((p * BINS as f64) as usize).min(BINS - 1) == b)
+                    .collect();
+                (!in_bin.is_empty()).then(|| CalibrationBin {
+                    from,
+                    games: in_bin.len(),
+                    mean_forecast: in_bin.iter().map(|(p, _)| p).sum::<f64>() / in_bin.len() as f64,
+                    mean_result: in_bin.iter().map(|(_, r)| r).sum::<f64>() / in_bin.len() as f64,
+                })
+            }).collect();
+            Some(CalibrationCurve {
+                move_number: n,
+                games: pairs.len(),
+                brier: pairs.iter().map(|(p, r)| (p - r).powi(2)).sum::<f64>() / pairs.len() as f64,
+                bins,
+            })
+        }).collect();
+        (!curves.is_empty()).then_some(Self { curves })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nWDL Calibration (White's expected score)")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        for c in &self.curves {
+            writeln!(out, "Move {}: {} games, Brier score {:.3}", c.move_number, c.games, c.brier)?;
+            writeln!(out, "  {:<10} {:>6} {:>9} {:>8}", "Forecast", "Games", "Predicted", "Actual")?;
+            for b in &c.bins {
+                writeln!(out, "  {:<10} {:>6} {:>9.2} {:>8.2}",
+                    format!("{:.1}-{:.1}", b.from, b.from + 1.0 / BINS as f64), b.games, b.mean_forecast, b.mean_result)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+//! chess.com public API: monthly archives and the games they contain.
+
+use crate::engine::Score;
+use crate::http::{HttpClient, HttpError};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct ArchivesResponse { archives: Vec<String> }
+
+#[derive(Deserialize)]
+struct GamesResponse { games: Vec<GameData> }
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct GameData {
+    pub url: Option<String>,
+    pub pgn: Option<String>,
+    pub white: Option<PlayerData>,
+    pub black: Option<PlayerData>,
+    /// Unix time the game ended.
+    pub end_time: Option<i64>,
+    /// "bullet", "blitz", "rapid", "daily", ...
+    pub time_class: Option<String>,
+    /// Per-ply evaluations supplied by the source, White's point of view.
+    #[serde(skip)]
+    pub evals: Option<Vec<Score>>,
+    /// Mover's remaining clock after each ply, in seconds.
+    #[serde(skip)]
+    pub clocks: Option<Vec<f64>>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerData { pub username: Option<String> }
+
+/// Monthly archive URLs for `username`, oldest first.
+pub fn fetch_archives(client: &HttpClient, username: &str) -> Result<Vec<String>, HttpError> {
+    let url = format!("https://api.chess.com/pub/player/{}/games/archives", username);
+    let resp: ArchivesResponse = serde_json::from_str(&client.get_text(&url, false)?)?;
+    Ok(resp.archives)
+}
+
+/// Games of one monthly archive. Months before the current one are final,
+/// so their cached copy is used without revalidation.
+pub fn fetch_games(client: &HttpClient, url: &str) -> Result<Vec<GameData>, HttpError> {
+    let immutable = archive_month(url).is_some_and(|m| m < crate::date::current_month());
+    let text = client.get_text(url, immutable)?;
+    let _span = crate::spans::span("json");
+    let resp: GamesResponse = serde_json::from_str(&text)?;
+    Ok(resp.games)
+}
+
+/// Titles chess.com lists players for.
+pub const TITLES: [&str; 10] = ["GM", "WGM", "IM", "WIM", "FM", "WFM", "NM", "WNM", "CM", "WCM"];
+
+#[derive(Deserialize)]
+struct TitledResponse { players: Vec<String> }
+
+/// Usernames (lowercase) of the chess.com players holding `title`.
+pub fn fetch_titled(client: &HttpClient, title: &str) -> Result<Vec<String>, HttpError> {
+    let url = format!("https://api.chess.com/pub/titled/{}", title);
+    let resp: TitledResponse = serde_json::from_str(&client.get_text(&url, false)?)?;
+    Ok(resp.players.into_iter().map(|p| p.to_lowercase()).collect())
+}
+
+#[derive(Deserialize)]
+struct ClubMember { username: String }
+
+#[derive(Deserialize)]
+struct ClubMembersResponse {
+    #[serde(default)]
+    weekly: Vec<ClubMember>,
+    #[serde(default)]
+    monthly: Vec<ClubMember>,
+    #[serde(default)]
+    all_time: Vec<ClubMember>,
+}
+
+/// Usernames (lowercase) of a club's members, most recently active first.
+/// `club` is the ID in the club's URL, e.g. "chess-com-developer-community".
+pub fn fetch_club_members(client: &HttpClient, club: &str) -> Result<Vec<String>, HttpError> {
+    let url = format!("https://api.chess.com/pub/club/{}/members", club);
+    let resp: ClubMembersResponse = serde_json::from_str(&client.get_text(&url, false)?)?;
+    Ok(resp.weekly.into_iter().chain(resp.monthly).chain(resp.all_time).map(|m| m.username.to_lowercase()).collect())
+}
+
+#[derive(Deserialize)]
+struct TournamentResponse {
+    name: String,
+    #[serde(default)]
+    rounds: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RoundResponse {
+    #[serde(default)]
+    groups: Vec<String>,
+}
+
+/// A finished chess.com tournament (Swiss events such as Titled Tuesday, or
+/// arenas) with its games.
+pub struct Tournament {
+    pub name: String,
+    /// Games of each round, in round order.
+    pub rounds: Vec<Vec<GameData>>,
+}
+
+/// ID of a tournament from its page URL
+/// ("https://www.chess.com/tournament/live/late-titled-tuesday-blitz-january-30-2024-4696001"),
+/// or the ID itself.
+pub fn tournament_id(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next()?;
+    path.trim_end_matches('/').rsplit('/').next().filter(|id| !id.is_empty())
+}
+
+/// The tournament `id` and the games of every group of every round.
+pub fn fetch_tournament(client: &HttpClient, id: &str) -> Result<Tournament, HttpError> {
+    let url = format!("https://api.chess.com/pub/tournament/{}", id);
+    let resp: TournamentResponse = serde_json::from_str(&client.get_text(&url, false)?)?;
+    let mut rounds = Vec::with_capacity(resp.rounds.len());
+    for round_url in &resp.rounds {
+        let round: RoundResponse = serde_json::from_str(&client.get_text(round_url, false)?)?;
+        let mut games = Vec::new();
+        for group_url in &round.groups {
+            games.extend(serde_json::from_str::<GamesResponse>(&client.get_text(group_url, false)?)?.games);
+        }
+        crate::progress!("  Fetched {} games from round {}", games.len(), rounds.len() + 1);
+        rounds.push(games);
+    }
+    Ok(Tournament { name: resp.name, rounds })
+}
+
+pub struct RecentGames {
+    /// Newest games first, at most the requested number.
+    pub games: Vec<GameData>,
+    /// Archive URLs that could not be loaded.
+    pub missing: Vec<String>,
+}
+
+/// Walks the archives newest-first until `max` games are collected.
+pub fn fetch_recent_games(client: &HttpClient, username: &str, max: usize) -> Result<RecentGames, HttpError> {
+    let mut archives = fetch_archives(client, username)?;
+    archives.reverse();
+
+    let mut games = Vec::new();
+    let mut missing = Vec::new();
+    for url in &archives {
+        if games.len() >= max { break; }
+        match fetch_games(client, url) {
+            Ok(month) => {
+                let (y, m) = archive_month(url).unwrap_or_default();
+                crate::progress!("  Fetched {} games from {}/{:02}", month.len(), y, m);
+                games.extend(month);
+            }
+            Err(e) => {
+                crate::verbose!("  Failed to fetch {}: {}", url, e);
+                missing.push(url.clone());
+            }
+        }
+    }
+    games.truncate(max);
+    Ok(RecentGames { games, missing })
+}
+
+/// Archive URL of one month of `username`'s games.
+pub fn month_url(username: &str, (year, month): (i32, u32)) -> String {
+    format!("https://api.chess.com/pub/player/{}/games/{}/{:02}", username.to_lowercase(), year, month)
+}
+
+/// (year, month) of an archive URL ending in `/YYYY/MM`.
+pub fn archive_month(url: &str) -> Option<(i32, u32)> {
+    let mut parts = url.trim_end_matches('/').rsplit('/');
+    let month = parts.next()?.parse().ok()?;
+    let year = parts.next()?.parse().ok()?;
+    Some((year, month))
+}
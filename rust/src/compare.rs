@@ -0,0 +1,121 @@
+//! Two saved runs (`--format json`) side by side, with t tests over their
+//! per-game values: paired over the games both runs analyzed, as when
+//! comparing engines or settings, else Welch's test, as for two players.
+
+use crate::cploss::GameCpLoss;
+use crate::stats::{paired, welch, TTest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+#[derive(Deserialize)]
+struct SavedRun {
+    summary: SavedSummary,
+    games: Vec<SavedGame>,
+}
+
+#[derive(Deserialize)]
+struct SavedSummary {
+    username: String,
+}
+
+#[derive(Deserialize)]
+struct SavedGame {
+    url: Option<String>,
+    white: String,
+    black: String,
+    white_accuracy: f64,
+    black_accuracy: f64,
+    moves: usize,
+    #[serde(default)]
+    cp_loss: Option<GameCpLoss>,
+}
+
+/// The player's values in one game.
+#[derive(Debug)]
+struct GameValue {
+    /// URL, else the players and length.
+    key: String,
+    accuracy: f64,
+    acpl: Option<f64>,
+}
+
+/// The player's per-game values of one run.
+#[derive(Debug)]
+pub struct RunValues {
+    pub username: String,
+    games: Vec<GameValue>,
+}
+
+impl RunValues {
+    pub fn from_json(text: &str) -> Result<Self, serde_json::Error> {
+        let run: SavedRun = serde_json::from_str(text)?;
+        let target = run.summary.username.to_lowercase();
+        let games = run.games.into_iter().map(|g| {
+            let key = g.url.clone().unwrap_or_else(|| format!("{}|{}|{}", g.white, g.black, g.moves));
+            let accuracy = if g.white == target { g.white_accuracy } else { g.black_accuracy };
+            let acpl = g.cp_loss.filter(|c| !c.player.is_empty())
+                .map(|c| c.player.iter().map(|&l| l as f64).sum::<f64>() / c.player.len() as f64);
+            GameValue { key, accuracy, acpl }
+        }).collect();
+        Ok(Self { username: run.summary.username, games })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RunComparison {
+    pub a: String,
+    pub b: String,
+    pub games_a: usize,
+    pub games_b: usize,
+    /// Games found in both runs.
+    pub matched: usize,
+    pub accuracy: Option<TTest>,
+    /// Average centipawn loss, when both runs used --cp-loss.
+    pub acpl: Option<TTest>,
+}
+
+impl RunComparison {
+    pub fn new(a: &RunValues, b: &RunValues) -> Self {
+        let in_b: HashMap<&str, &GameValue> = b.games.iter().map(|g| (g.key.as_str(), g)).collect();
+        let pairs: Vec<(&GameValue, &GameValue)> = a.games.iter()
+            .filter_map(|g| Some((g, *in_b.get(g.key.as_str())?)))
+            .collect();
+        let (accuracy, acpl) = if pairs.len() >= 2 {
+            let acc_a: Vec<f64> = pairs.iter().map(|p| p.0.accuracy).collect();
+            let acc_b: Vec<f64> = pairs.iter().map(|p| p.1.accuracy).collect();
+            let (cp_a, cp_b): (Vec<f64>, Vec<f64>) = pairs.iter().filter_map(|p| Some((p.0.acpl?, p.1.acpl?))).unzip();
+            (paired(&acc_a, &acc_b), paired(&cp_a, &cp_b))
+        } else {
+            let acc = |r: &RunValues| r.games.iter().map(|g| g.accuracy).collect::<Vec<_>>();
+            let cp = |r: &RunValues| r.games.iter().filter_map(|g| g.acpl).collect::<Vec<_>>();
+            (welch(&acc(a), &acc(b)), welch(&cp(a), &cp(b)))
+        };
+        Self {
+            a: a.username.clone(),
+            b: b.username.clone(),
+            games_a: a.games.len(),
+            games_b: b.games.len(),
+            matched: pairs.len(),
+            accuracy,
+            acpl,
+        }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nRun Comparison")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "A: {} ({} games)", self.a, self.games_a)?;
+        writeln!(out, "B: {} ({} games)", self.b, self.games_b)?;
+        writeln!(out, "Games in both: {}", self.matched)?;
+        writeln!(out, "{:<18} {:>8} {:>8}  A - B", "", "A", "B")?;
+        for (name, test, prec) in [("Accuracy (%)", self.accuracy, 2), ("Avg cp loss", self.acpl, 1)] {
+            match test {
+                Some(t) => writeln!(out, "{:<18} {:>8.prec$} {:>8.prec$}  {}", name, t.mean_a, t.mean_b, t.describe(prec), prec = prec)?,
+                None if name.starts_with("Avg") => writeln!(out, "{:<18} (needs --cp-loss in both runs)", name)?,
+                None => writeln!(out, "{:<18} (too few games to test)", name)?,
+            }
+        }
+        Ok(())
+    }
+}
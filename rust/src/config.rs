@@ -0,0 +1,40 @@
+//! Optional JSON configuration file. Command-line flags take precedence.
+
+use crate::exclusions::Exclusions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Full User-Agent string sent to chess.com.
+    pub user_agent: Option<String>,
+    /// Contact address appended to the default User-Agent.
+    pub email: Option<String>,
+    /// Proxy URL for all HTTP requests.
+    pub proxy: Option<String>,
+    /// Opening lines and move sequences left out of scoring.
+    pub exclude: Exclusions,
+    /// Requests per second by host, "*" for every other host.
+    pub rate_limits: BTreeMap<String, f64>,
+}
+
+impl Config {
+    /// Loads `path`, or the default location when `path` is `None`.
+    /// A missing default file yields the empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let (path, explicit) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match default_config_path() { Some(p) => (p, false), None => return Ok(Self::default()) },
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e).into()),
+            Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e).into()),
+        }
+    }
+}
+
+/// `config.json` in the config directory, see [`crate::dirs`].
+pub fn default_config_path() -> Option<PathBuf> { crate::dirs::config_dir().map(|d| d.join("config.json")) }
@@ -0,0 +1,98 @@
+//! Distribution of per-move centipawn loss, beyond the average.
+
+use crate::analysis::MoveRecord;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// Upper bounds of the histogram buckets, in centipawns; the last bucket is open.
+pub const BUCKETS: [u32; 6] = [10, 25, 50, 100, 200, 300];
+
+/// Centipawn losses of one side's non-book moves, in move order.
+pub fn side_losses(records: &[MoveRecord], is_white: bool) -> Vec<u32> {
+    records.iter().filter(|r| r.is_white == is_white && !r.in_book).map(|r| r.cp_loss).collect()
+}
+
+/// The player's and their opponents' cp losses in one game.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GameCpLoss {
+    pub player: Vec<u32>,
+    pub opponent: Vec<u32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CpLossStats {
+    pub moves: usize,
+    pub mean: f64,
+    pub p50: u32,
+    pub p90: u32,
+    pub p99: u32,
+    /// Share of moves losing more than 100 and 300 centipawns.
+    pub over_100: f64,
+    pub over_300: f64,
+    /// Moves per bucket of `BUCKETS`, plus the open-ended last one.
+    pub histogram: Vec<usize>,
+}
+
+impl CpLossStats {
+    pub fn from_losses(mut losses: Vec<u32>) -> Option<Self> {
+        if losses.is_empty() { return None; }
+        losses.sort_unstable();
+        let n = losses.len();
+        // Nearest-rank percentile
+        let pct = |p: usize| losses[(p * n).div_ceil(100).clamp(1, n) - 1];
+        let share = |cp: u32| losses.iter().filter(|&&l| l > cp).count() as f64 / n as f64;
+        let mut histogram = vec![0; BUCKETS.len() + 1];
+        for &l in &losses {
+            histogram[BUCKETS.iter().position(|&b| l < b).unwrap_or(BUCKETS.len())] += 1;
+        }
+        Some(Self {
+            moves: n,
+            mean: losses.iter().map(|&l| l as f64).sum::<f64>() / n as f64,
+            p50: pct(50),
+            p90: pct(90),
+            p99: pct(99),
+            over_100: share(100),
+            over_300: share(300),
+            histogram,
+        })
+    }
+}
+
+/// Cp-loss distribution of the player and, for comparison, their opponents.
+#[derive(Serialize, Debug)]
+pub struct CpLossReport {
+    pub player: CpLossStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opponents: Option<CpLossStats>,
+}
+
+impl CpLossReport {
+    pub fn from_games<'a>(games: impl Iterator<Item = &'a GameCpLoss>) -> Option<Self> {
+        let (mut player, mut opponents) = (Vec::new(), Vec::new());
+        for g in games {
+            player.extend(&g.player);
+            opponents.extend(&g.opponent);
+        }
+        Some(Self { player: CpLossStats::from_losses(player)?, opponents: CpLossStats::from_losses(opponents) })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nCentipawn Loss")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<10} {:>6} {:>6} {:>5} {:>5} {:>5} {:>7} {:>7}", "", "Moves", "Mean", "p50", "p90", "p99", ">100cp", ">300cp")?;
+        let rows = [("Player", Some(&self.player)), ("Opponents", self.opponents.as_ref())];
+        for (name, s) in rows.iter().filter_map(|(n, s)| Some((n, (*s)?))) {
+            writeln!(out, "{:<10} {:>6} {:>6.1} {:>5} {:>5} {:>5} {:>6.1}% {:>6.1}%", name, s.moves, s.mean,
+                s.p50, s.p90, s.p99, 100.0 * s.over_100, 100.0 * s.over_300)?;
+        }
+        let labels: Vec<String> = BUCKETS.iter().enumerate()
+            .map(|(i, b)| format!("{}-{}", if i == 0 { 0 } else { BUCKETS[i - 1] }, b))
+            .chain(std::iter::once(format!("{}+", BUCKETS[BUCKETS.len() - 1])))
+            .collect();
+        for (label, n) in labels.iter().zip(&self.player.histogram) {
+            let share = *n as f64 / self.player.moves as f64;
+            writeln!(out, "{:>8}cp {:>6} {:>5.1}% {}", label, n, 100.0 * share, "#".repeat((share * 40.0).round() as usize))?;
+        }
+        Ok(())
+    }
+}
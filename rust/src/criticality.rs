@@ -0,0 +1,33 @@
+//! Accuracy on critical positions, where only one move keeps the evaluation,
+//! against easy ones with several good moves. Needs MultiPV ≥ 2.
+
+use crate::accuracy::{Bucket, BucketSplit};
+use crate::analysis::MoveRecord;
+use serde::Serialize;
+
+/// A player's accuracy on critical and easy positions.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct CriticalitySplit {
+    /// Positions where the best move beat the second best by at least the gap.
+    pub critical: Bucket,
+    pub easy: Bucket,
+}
+
+impl BucketSplit for CriticalitySplit {
+    const TITLE: &'static str = "Accuracy by Criticality";
+    /// The gap in expected score from which a position is critical. Moves
+    /// without a second-best evaluation are left out.
+    type By = f64;
+
+    fn keys(r: &MoveRecord, gap: f64) -> Vec<usize> {
+        r.criticality.map(|c| if c >= gap { 0 } else { 1 }).into_iter().collect()
+    }
+
+    fn bucket_mut(&mut self, key: usize) -> &mut Bucket {
+        if key == 0 { &mut self.critical } else { &mut self.easy }
+    }
+
+    fn rows(&self) -> Vec<(String, Bucket)> {
+        vec![("Critical".into(), self.critical), ("Easy".into(), self.easy)]
+    }
+}
@@ -0,0 +1,83 @@
+//! Minimal UTC calendar helpers, enough for archive months and game timestamps.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// (year, month, day) for a count of days since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = (yoe + era * 400 + i64::from(m <= 2)) as i32;
+    (y, m, d)
+}
+
+pub fn now_unix() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Current UTC (year, month).
+pub fn current_month() -> (i32, u32) {
+    let (y, m, _) = civil_from_days(now_unix().div_euclid(86_400));
+    (y, m)
+}
+
+/// Days since 1970-01-01 for a (year, month, day), the inverse of [`civil_from_days`].
+pub fn days_from_civil(y: i32, m: u32, d: u32) -> i64 {
+    let y = i64::from(y) - i64::from(m <= 2);
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = (i64::from(m) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Unix time of a PGN date ("2024.01.15") and time ("18:02:11") pair.
+pub fn parse_pgn_datetime(date: &str, time: &str) -> Option<i64> {
+    let mut d = date.split('.').map(|p| p.parse::<u32>().ok());
+    let (y, m, day) = (d.next()??, d.next()??, d.next()??);
+    let mut t = time.split(':').map(|p| p.parse::<i64>().ok());
+    let (h, min, s) = (t.next()??, t.next()??, t.next().flatten().unwrap_or(0));
+    if !(1..=12).contains(&m) || !(1..=31).contains(&day) { return None; }
+    Some(days_from_civil(y as i32, m, day) * 86_400 + h * 3600 + min * 60 + s)
+}
+
+/// Seconds east of UTC for "UTC", "Z" or a fixed offset like "+05:30" / "-0800".
+pub fn parse_utc_offset(s: &str) -> Option<i64> {
+    if matches!(s, "UTC" | "utc" | "Z" | "GMT") { return Some(0); }
+    let sign = match s.as_bytes().first()? { b'+' => 1, b'-' => -1, _ => return None };
+    let digits: String = s[1..].chars().filter(|c| *c != ':').collect();
+    let (h, m) = match digits.len() {
+        1 | 2 => (digits.parse::<i64>().ok()?, 0),
+        4 => (digits[..2].parse::<i64>().ok()?, digits[2..].parse::<i64>().ok()?),
+        _ => return None,
+    };
+    (h <= 14 && m < 60).then_some(sign * (h * 3600 + m * 60))
+}
+
+/// Hour of day (0-23) and weekday (0 = Monday) of a Unix time shifted by `offset` seconds.
+pub fn hour_and_weekday(unix: i64, offset: i64) -> (u32, u32) {
+    let t = unix + offset;
+    // 1970-01-01 was a Thursday
+    ((t.rem_euclid(86_400) / 3600) as u32, (t.div_euclid(86_400) + 3).rem_euclid(7) as u32)
+}
+
+/// "2024-01-15 18:02 UTC".
+pub fn fmt_unix(t: i64) -> String {
+    let (y, m, d) = civil_from_days(t.div_euclid(86_400));
+    let secs = t.rem_euclid(86_400);
+    format!("{}-{:02}-{:02} {:02}:{:02} UTC", y, m, d, secs / 3600, secs % 3600 / 60)
+}
+
+/// "20240115-1802", for file names.
+pub fn snapshot_stamp(t: i64) -> String {
+    let (y, m, d) = civil_from_days(t.div_euclid(86_400));
+    let secs = t.rem_euclid(86_400);
+    format!("{}{:02}{:02}-{:02}{:02}", y, m, d, secs / 3600, secs % 3600 / 60)
+}
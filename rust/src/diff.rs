@@ -0,0 +1,118 @@
+//! Game-by-game differences between two saved runs of the same games, e.g.
+//! before and after a depth or engine change.
+
+use crate::merge::{game_key, SavedResults};
+use crate::report::GameResult;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// A game whose accuracy or blunder count changed.
+#[derive(Serialize, Debug)]
+pub struct GameChange {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    pub opponent: String,
+    pub accuracy_old: f64,
+    pub accuracy_new: f64,
+    pub blunders_old: usize,
+    pub blunders_new: usize,
+    /// New minus old accuracy.
+    pub change: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RunDiff {
+    pub username: String,
+    pub old_games: usize,
+    pub new_games: usize,
+    /// Games in both runs.
+    pub matched: usize,
+    /// Accuracy change, in points, at or above which a game is listed.
+    pub min_change: f64,
+    /// Mean accuracy over the matched games in each run.
+    pub accuracy_old: f64,
+    pub accuracy_new: f64,
+    /// Mean absolute per-game accuracy change.
+    pub mean_abs_change: f64,
+    pub blunders_old: usize,
+    pub blunders_new: usize,
+    /// Matched games whose blunder count changed.
+    pub reclassified: usize,
+    /// Listed games, largest change first.
+    pub changes: Vec<GameChange>,
+    pub only_old: Vec<String>,
+    pub only_new: Vec<String>,
+}
+
+impl RunDiff {
+    pub fn new(old: &SavedResults, new: &SavedResults, min_change: f64) -> Self {
+        let target = old.username().to_lowercase();
+        let in_new: HashMap<String, &GameResult> = new.games().iter().map(|g| (game_key(g), g)).collect();
+        let in_old: HashMap<String, &GameResult> = old.games().iter().map(|g| (game_key(g), g)).collect();
+        let pairs: Vec<(String, &GameResult, &GameResult)> = old.games().iter()
+            .filter_map(|g| { let key = game_key(g); let n = *in_new.get(&key)?; Some((key, g, n)) })
+            .collect();
+
+        let mut changes: Vec<GameChange> = pairs.iter().map(|(key, o, n)| GameChange {
+            key: key.clone(),
+            date: o.date(),
+            opponent: if o.white == target { o.black.clone() } else { o.white.clone() },
+            accuracy_old: o.accuracy_for(&target),
+            accuracy_new: n.accuracy_for(&target),
+            blunders_old: o.blunders_for(&target),
+            blunders_new: n.blunders_for(&target),
+            change: n.accuracy_for(&target) - o.accuracy_for(&target),
+        }).collect();
+        let n = changes.len().max(1) as f64;
+        let mean = |f: fn(&GameChange) -> f64| changes.iter().map(f).sum::<f64>() / n;
+        let (accuracy_old, accuracy_new) = (mean(|c| c.accuracy_old), mean(|c| c.accuracy_new));
+        let mean_abs_change = mean(|c| c.change.abs());
+        let reclassified = changes.iter().filter(|c| c.blunders_old != c.blunders_new).count();
+        let blunders_old = changes.iter().map(|c| c.blunders_old).sum();
+        let blunders_new = changes.iter().map(|c| c.blunders_new).sum();
+        changes.retain(|c| c.change.abs() >= min_change || c.blunders_old != c.blunders_new);
+        changes.sort_by(|a, b| b.change.abs().total_cmp(&a.change.abs()));
+
+        let missing = |from: &SavedResults, other: &HashMap<String, &GameResult>| from.games().iter()
+            .map(game_key).filter(|k| !other.contains_key(k)).collect();
+        Self {
+            username: old.username().to_string(),
+            old_games: old.games().len(),
+            new_games: new.games().len(),
+            matched: pairs.len(),
+            min_change,
+            accuracy_old,
+            accuracy_new,
+            mean_abs_change,
+            blunders_old,
+            blunders_new,
+            reclassified,
+            changes,
+            only_old: missing(old, &in_new),
+            only_new: missing(new, &in_old),
+        }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nRun Diff")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "Player: {}", self.username)?;
+        writeln!(out, "Games: {} old, {} new, {} in both ({} only old, {} only new)",
+            self.old_games, self.new_games, self.matched, self.only_old.len(), self.only_new.len())?;
+        writeln!(out, "Accuracy: {:.2}% -> {:.2}% ({:+.2}), mean change per game {:.2}",
+            self.accuracy_old, self.accuracy_new, self.accuracy_new - self.accuracy_old, self.mean_abs_change)?;
+        writeln!(out, "Blunders: {} -> {} ({:+}), {} games with a different count",
+            self.blunders_old, self.blunders_new, self.blunders_new as i64 - self.blunders_old as i64, self.reclassified)?;
+        if self.changes.is_empty() {
+            return writeln!(out, "No game changed by {:.1} points or more.", self.min_change);
+        }
+        writeln!(out, "\n{:<10}  {:<16}  {:>8}  {:>8}  {:>7}  {:>8}  Game", "Date", "Opponent", "Old", "New", "Change", "Blunders")?;
+        for c in &self.changes {
+            writeln!(out, "{:<10}  {:<16}  {:>7.2}%  {:>7.2}%  {:>+7.2}  {:>8}  {}", c.date.as_deref().unwrap_or("-"), c.opponent,
+                c.accuracy_old, c.accuracy_new, c.change, format!("{} -> {}", c.blunders_old, c.blunders_new), c.key)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! Where chess-bench keeps its files: a `chess-bench` directory in each of
+//! the platform's config, cache and data directories.
+//!
+//! | | Linux and other Unix | macOS | Windows |
+//! |---|---|---|---|
+//! | config | `~/.config` | `~/Library/Application Support` | `%APPDATA%\chess-bench\config` |
+//! | cache | `~/.cache` | `~/Library/Caches` | `%LOCALAPPDATA%\chess-bench\cache` |
+//! | data | `~/.local/share` | `~/Library/Application Support` | `%APPDATA%\chess-bench\data` |
+//!
+//! `$XDG_CONFIG_HOME`, `$XDG_CACHE_HOME` and `$XDG_DATA_HOME` take
+//! precedence on every platform when set.
+//!
+//! - config: `config.json`
+//! - cache, safe to delete: `http/` API responses and `evals.bin`, the
+//!   default `--save-evals` file
+//! - data, kept: `history/` snapshots of `--schedule` runs
+//!
+//! Earlier versions used the Linux locations everywhere; [`migrate`] moves
+//! them to the platform's.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const APP: &str = "chess-bench";
+
+#[derive(Clone, Copy)]
+enum Kind { Config, Cache, Data }
+
+impl Kind {
+    const ALL: [Kind; 3] = [Kind::Config, Kind::Cache, Kind::Data];
+
+    /// The XDG variable and its default under the home directory.
+    fn xdg(self) -> (&'static str, &'static str) {
+        match self {
+            Kind::Config => ("XDG_CONFIG_HOME", ".config"),
+            Kind::Cache => ("XDG_CACHE_HOME", ".cache"),
+            Kind::Data => ("XDG_DATA_HOME", ".local/share"),
+        }
+    }
+}
+
+fn env_path(var: &str) -> Option<PathBuf> { std::env::var_os(var).filter(|v| !v.is_empty()).map(PathBuf::from) }
+
+fn home() -> Option<PathBuf> { env_path("HOME").or_else(|| if cfg!(windows) { env_path("USERPROFILE") } else { None }) }
+
+/// The XDG location, also where earlier versions kept everything.
+fn xdg_dir(kind: Kind) -> Option<PathBuf> {
+    let (var, home_default) = kind.xdg();
+    env_path(var).or_else(|| home().map(|h| h.join(home_default))).map(|d| d.join(APP))
+}
+
+fn dir(kind: Kind) -> Option<PathBuf> {
+    if let Some(d) = env_path(kind.xdg().0) { return Some(d.join(APP)); }
+    if cfg!(target_os = "macos") {
+        let library = home()?.join("Library");
+        Some(match kind { Kind::Cache => library.join("Caches"), _ => library.join("Application Support") }.join(APP))
+    } else if cfg!(windows) {
+        Some(match kind {
+            Kind::Config => env_path("APPDATA")?.join(APP).join("config"),
+            Kind::Cache => env_path("LOCALAPPDATA")?.join(APP).join("cache"),
+            Kind::Data => env_path("APPDATA")?.join(APP).join("data"),
+        })
+    } else {
+        xdg_dir(kind)
+    }
+}
+
+/// Where earlier versions kept `kind`, when that is not where it goes now.
+fn old_dir(kind: Kind) -> Option<PathBuf> {
+    let old = xdg_dir(kind)?;
+    (Some(&old) != dir(kind).as_ref()).then_some(old)
+}
+
+pub fn config_dir() -> Option<PathBuf> { dir(Kind::Config) }
+
+pub fn cache_dir() -> Option<PathBuf> { dir(Kind::Cache) }
+
+pub fn data_dir() -> Option<PathBuf> { dir(Kind::Data) }
+
+/// The cache directory of earlier versions, when something is left in it.
+pub fn old_cache_dir() -> Option<PathBuf> { old_dir(Kind::Cache).filter(|d| d.exists()) }
+
+/// Moves the files of earlier versions to the platform's locations and
+/// returns the directories moved from. Entries already present at the new
+/// location are left behind.
+pub fn migrate() -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moved = Vec::new();
+    for kind in Kind::ALL {
+        let (Some(old), Some(new)) = (old_dir(kind), dir(kind)) else { continue };
+        if old.is_dir() && move_into(&old, &new)? { moved.push((old, new)); }
+    }
+    Ok(moved)
+}
+
+/// Moves each entry of `from` into `to` unless `to` has one of that name,
+/// then removes `from` if it is empty. Whether anything moved.
+fn move_into(from: &Path, to: &Path) -> io::Result<bool> {
+    fs::create_dir_all(to)?;
+    let mut any = false;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if target.exists() { continue; }
+        fs::rename(entry.path(), target)?;
+        any = true;
+    }
+    // Left alone when something stayed behind
+    let _ = fs::remove_dir(from);
+    Ok(any)
+}
+
+/// Responses cached at the top of the cache directory by versions before
+/// `http/`, dropped by `cache clear --http`.
+pub fn old_responses() -> io::Result<Vec<PathBuf>> {
+    let Some(dir) = cache_dir() else { return Ok(Vec::new()) };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if path.is_file() && (name.ends_with(".body") || name.ends_with(".meta.json")) { files.push(path); }
+    }
+    Ok(files)
+}
+
+/// Cached chess.com and Lichess responses.
+pub fn http_cache_dir() -> Option<PathBuf> { cache_dir().map(|d| d.join("http")) }
+
+/// Engine evals kept by `--save-evals` without a file.
+pub fn eval_cache_path() -> Option<PathBuf> { cache_dir().map(|d| d.join("evals.bin")) }
+
+/// Dated JSON snapshots of scheduled runs.
+pub fn history_dir() -> Option<PathBuf> { data_dir().map(|d| d.join("history")) }
+
+/// Files and their total size under a path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: Usage) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+    }
+}
+
+/// What `path` holds: the file itself, or every file below the directory.
+/// A path that does not exist holds nothing.
+pub fn usage(path: &Path) -> io::Result<Usage> {
+    let meta = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Usage::default()),
+        Err(e) => return Err(e),
+    };
+    if !meta.is_dir() { return Ok(Usage { files: 1, bytes: meta.len() }); }
+    let mut total = Usage::default();
+    for entry in fs::read_dir(path)? { total.add(usage(&entry?.path())?); }
+    Ok(total)
+}
+
+/// Deletes the file at `path`, or everything inside the directory (keeping
+/// the directory), and returns what was freed.
+pub fn clear(path: &Path) -> io::Result<Usage> {
+    let freed = usage(path)?;
+    match fs::symlink_metadata(path) {
+        Ok(m) if m.is_dir() => {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() { fs::remove_dir_all(entry.path())?; } else { fs::remove_file(entry.path())?; }
+            }
+        }
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    Ok(freed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_keeps_what_is_already_there() {
+        let root = std::env::temp_dir().join(format!("chess-bench-dirs-{}", std::process::id()));
+        let (old, new) = (root.join("old"), root.join("new"));
+        fs::create_dir_all(old.join("http")).unwrap();
+        fs::write(old.join("http/a.body"), "a").unwrap();
+        fs::write(old.join("evals.bin"), "old").unwrap();
+        fs::create_dir_all(&new).unwrap();
+        fs::write(new.join("evals.bin"), "new").unwrap();
+
+        assert!(move_into(&old, &new).unwrap());
+        assert_eq!(fs::read_to_string(new.join("http/a.body")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(new.join("evals.bin")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(old.join("evals.bin")).unwrap(), "old");
+
+        fs::remove_file(old.join("evals.bin")).unwrap();
+        assert!(!move_into(&old, &new).unwrap());
+        assert!(!old.exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+//! Spreading the engine work of a run over several machines.
+//!
+//! The coordinator (a normal run with `--serve-jobs`) keeps fetching,
+//! scoring and reporting, but hands each game's positions to remote
+//! `benchmark worker` processes through a small HTTP job API:
+//!
+//! - `GET /job` leases the next job (200 with a [`Job`]), or answers 204
+//!   when none is waiting and 410 once the run is over.
+//! - `POST /result/<id>` returns a [`JobResult`].
+//!
+//! A job not returned within the lease goes back to the queue, so a worker
+//! that dies only delays its games.
+
+use crate::engine::{Eval, EngineInfo, Pv, Score, StockfishEngine, Timeout, Wdl};
+use serde::{Deserialize, Serialize};
+use shakmaty::fen::Fen;
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::{Chess, EnPassantMode, Position};
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Engine settings of the run, applied by every worker so that remote evals
+/// match local ones. Threads stay each worker's own.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JobSettings {
+    pub depth: u32,
+    pub multipv: u32,
+    pub hash: Option<u32>,
+    pub clear_hash: bool,
+}
+
+/// The positions of one game, as FEN, initial position first.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Job {
+    pub id: u64,
+    pub settings: JobSettings,
+    pub positions: Vec<String>,
+}
+
+/// An eval as sent over the wire.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WireEval {
+    pub score: Score,
+    pub wdl: Wdl,
+    pub best_move: Option<String>,
+    pub second: Option<Score>,
+    pub pv: Vec<String>,
+}
+
+impl From<&Eval> for WireEval {
+    fn from(e: &Eval) -> Self {
+        Self {
+            score: e.score,
+            wdl: e.wdl,
+            best_move: e.best_move.as_ref().map(UciMove::to_string),
+            second: e.second,
+            pv: e.pv.moves().map(UciMove::to_string).collect(),
+        }
+    }
+}
+
+impl WireEval {
+    pub fn eval(&self) -> Eval {
+        Eval {
+            score: self.score,
+            wdl: self.wdl,
+            best_move: self.best_move.as_deref().and_then(|m| UciMove::from_str(m).ok()),
+            second: self.second,
+            pv: Pv::from_moves(self.pv.iter().map_while(|m| UciMove::from_str(m).ok())),
+        }
+    }
+}
+
+/// Evals of a job's positions, in order. On an error or timeout `evals`
+/// holds the positions analyzed before it.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct JobResult {
+    pub evals: Vec<WireEval>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<EngineInfo>,
+}
+
+/// FENs of the initial position and of the position after each move, up to
+/// the first unplayable one.
+pub fn game_fens(moves: &[&str]) -> Vec<String> {
+    let mut pos = Chess::default();
+    let mut fens = vec![Fen::from_position(&pos, EnPassantMode::Legal).to_string()];
+    for m in moves {
+        let Some(mv) = San::from_str(m).ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+        pos.play_unchecked(mv);
+        fens.push(Fen::from_position(&pos, EnPassantMode::Legal).to_string());
+    }
+    fens
+}
+
+/// Runs a job on `engine`, stopping at the first failed search.
+pub fn run_job(engine: &mut StockfishEngine, job: &Job) -> JobResult {
+    let mut result = JobResult { engine: Some(engine.info().clone()), ..Default::default() };
+    for fen in &job.positions {
+        let white_to_move = fen.split_whitespace().nth(1) != Some("b");
+        match engine.analyze(fen, white_to_move) {
+            Ok(eval) => result.evals.push(WireEval::from(&eval)),
+            Err(e) => {
+                result.timed_out = e.is::<Timeout>();
+                result.error = Some(e.to_string());
+                break;
+            }
+        }
+    }
+    result
+}
+
+struct Pending {
+    positions: Vec<String>,
+    leased: Option<Instant>,
+    reply: Sender<JobResult>,
+}
+
+#[derive(Default)]
+struct Queue {
+    next_id: u64,
+    waiting: VecDeque<u64>,
+    jobs: HashMap<u64, Pending>,
+    closed: bool,
+}
+
+/// The coordinator's side: queues the games of the run's workers and
+/// serves them to remote workers from a background thread.
+pub struct Coordinator {
+    settings: JobSettings,
+    lease: Duration,
+    queue: Mutex<Queue>,
+}
+
+/// Largest request body accepted, well above any game's evals.
+const MAX_BODY: usize = 16 << 20;
+
+/// How long a connection may stall while sending its request or taking
+/// the response before it is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl Coordinator {
+    /// Binds `addr` and serves jobs from a background thread.
+    pub fn serve(addr: &str, settings: JobSettings, lease: Duration) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(addr)?;
+        let coordinator = Arc::new(Self { settings, lease, queue: Mutex::new(Queue::default()) });
+        let shared = Arc::clone(&coordinator);
+        std::thread::spawn(move || {
+            // One thread per connection, so a slow worker does not hold up the others
+            for stream in listener.incoming().flatten() {
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || {
+                    let served = stream.set_read_timeout(Some(REQUEST_TIMEOUT))
+                        .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)))
+                        .and_then(|_| shared.respond(stream));
+                    if let Err(e) = served { crate::verbose!("  Job request failed: {}", e); }
+                });
+            }
+        });
+        Ok(coordinator)
+    }
+
+    /// Queues `positions` and waits until a worker returns their evals.
+    pub fn analyze(&self, positions: Vec<String>) -> JobResult {
+        let (reply, result) = mpsc::channel();
+        {
+            let mut q = self.queue.lock().unwrap();
+            let id = q.next_id;
+            q.next_id += 1;
+            q.jobs.insert(id, Pending { positions, leased: None, reply });
+            q.waiting.push_back(id);
+        }
+        result.recv().unwrap_or_else(|_| JobResult { error: Some("coordinator stopped".into()), ..Default::default() })
+    }
+
+    /// Tells workers asking for jobs that the run is over.
+    pub fn close(&self) { self.queue.lock().unwrap().closed = true; }
+
+    /// Leases the next waiting job, first putting back jobs whose lease ran out.
+    fn lease(&self) -> Option<Job> {
+        let mut q = self.queue.lock().unwrap();
+        let expired: Vec<u64> = q.jobs.iter()
+            .filter(|(_, p)| p.leased.is_some_and(|t| t.elapsed() > self.lease))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in expired {
+            crate::verbose!("  Job {} not returned in time; queued again", id);
+            if let Some(p) = q.jobs.get_mut(&id) { p.leased = None; }
+            q.waiting.push_front(id);
+        }
+        // Ids of jobs returned by a worker whose lease had run out are stale
+        while let Some(id) = q.waiting.pop_front() {
+            if let Some(p) = q.jobs.get_mut(&id) {
+                p.leased = Some(Instant::now());
+                return Some(Job { id, settings: self.settings, positions: p.positions.clone() });
+            }
+        }
+        None
+    }
+
+    /// Hands `result` to the game waiting for it. A job already returned
+    /// by another worker (after its lease ran out) is ignored.
+    fn complete(&self, id: u64, result: JobResult) {
+        let pending = self.queue.lock().unwrap().jobs.remove(&id);
+        if let Some(p) = pending { let _ = p.reply.send(result); }
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(&stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() { break; }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") { length = value.trim().parse().unwrap_or(0); }
+            }
+        }
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or("/"));
+        let (status, body) = match (method, path) {
+            ("GET", "/job") => match self.lease() {
+                Some(job) => ("200 OK", serde_json::to_string(&job)?),
+                None if self.queue.lock().unwrap().closed => ("410 Gone", String::new()),
+                None => ("204 No Content", String::new()),
+            },
+            ("POST", p) if p.starts_with("/result/") && length <= MAX_BODY => {
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body)?;
+                match (p["/result/".len()..].parse::<u64>(), serde_json::from_slice::<JobResult>(&body)) {
+                    (Ok(id), Ok(result)) => {
+                        self.complete(id, result);
+                        ("200 OK", String::new())
+                    }
+                    _ => ("400 Bad Request", "bad job result\n".to_string()),
+                }
+            }
+            _ => ("404 Not Found", "not found\n".to_string()),
+        };
+        write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status, body.len(), body)
+    }
+}
@@ -0,0 +1,155 @@
+//! Endgame detection and classification from the material on the board.
+
+use crate::accuracy::{mean, wdl_to_prob};
+use crate::analysis::MoveRecord;
+use crate::report::GameResult;
+use serde::Serialize;
+use shakmaty::{Bitboard, Board, Color};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Pieces other than kings and pawns at or below which the game is an
+/// endgame (the same cut-off as the Lichess game divider).
+const ENDGAME_PIECES: usize = 6;
+
+/// Expected score at endgame entry from which a position counts as winning
+/// (and at or below one minus it, as losing).
+const WINNING: f64 = 0.7;
+
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum EndgameType {
+    Pawn,
+    Minor,
+    OppositeBishops,
+    Rook,
+    RookMinor,
+    Queen,
+    Mixed,
+}
+
+impl EndgameType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pawn => "pawn",
+            Self::Minor => "minor piece",
+            Self::OppositeBishops => "opposite bishops",
+            Self::Rook => "rook",
+            Self::RookMinor => "rook + minor",
+            Self::Queen => "queen",
+            Self::Mixed => "mixed",
+        }
+    }
+}
+
+/// The endgame type of a position, or `None` while it is not yet an endgame.
+pub fn classify(board: &Board) -> Option<EndgameType> {
+    let m = board.material();
+    let count = |f: fn(&shakmaty::ByRole<u8>) -> u8| (f(&m.white) + f(&m.black)) as usize;
+    let (minors, rooks, queens) = (count(|r| r.knight + r.bishop), count(|r| r.rook), count(|r| r.queen));
+    if minors + rooks + queens > ENDGAME_PIECES { return None; }
+
+    Some(match (minors, rooks, queens) {
+        (0, 0, 0) => EndgameType::Pawn,
+        (_, 0, 0) if opposite_bishops(board) => EndgameType::OppositeBishops,
+        (_, 0, 0) => EndgameType::Minor,
+        (0, _, 0) => EndgameType::Rook,
+        (_, _, 0) => EndgameType::RookMinor,
+        (0, 0, _) => EndgameType::Queen,
+        _ => EndgameType::Mixed,
+    })
+}
+
+/// One bishop each, no knights, on squares of different colors.
+fn opposite_bishops(board: &Board) -> bool {
+    let m = board.material();
+    if m.white.bishop != 1 || m.black.bishop != 1 || m.white.knight + m.black.knight != 0 { return false; }
+    let on_dark = |c: Color| (board.bishops() & board.by_color(c) & Bitboard::DARK_SQUARES).any();
+    on_dark(Color::White) != on_dark(Color::Black)
+}
+
+/// How the player entered the endgame of one game.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug)]
+pub struct EndgameEntry {
+    pub kind: EndgameType,
+    /// Ply of the move that reached the endgame.
+    pub ply: usize,
+    /// Player's expected score right after entering.
+    pub expected: f64,
+    /// Player's accuracy over the endgame moves, if they made any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accuracy: Option<f64>,
+}
+
+impl EndgameEntry {
+    pub fn from_records(records: &[MoveRecord], is_white: bool) -> Option<Self> {
+        let entry = records.iter().position(|r| r.endgame.is_some())?;
+        let rest: Vec<f64> = records[entry + 1..].iter()
+            .filter(|r| r.is_white == is_white && !r.in_book)
+            .map(|r| r.accuracy)
+            .collect();
+        let r = &records[entry];
+        Some(Self {
+            kind: r.endgame?,
+            ply: r.ply,
+            expected: wdl_to_prob(r.eval.wdl, is_white),
+            accuracy: (!rest.is_empty()).then(|| mean(&rest)),
+        })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct EndgameTypeStats {
+    pub kind: EndgameType,
+    pub games: usize,
+    pub avg_accuracy: f64,
+    /// Games entered winning, and how many of them were won.
+    pub winning: usize,
+    pub converted: usize,
+    /// Games entered losing, and how many of them were drawn or won.
+    pub losing: usize,
+    pub held: usize,
+}
+
+/// The player's endgames grouped by type.
+#[derive(Serialize, Debug)]
+pub struct EndgameStats {
+    pub by_type: Vec<EndgameTypeStats>,
+}
+
+impl EndgameStats {
+    pub fn from_games(games: &[GameResult], target: &str) -> Option<Self> {
+        let mut groups: BTreeMap<EndgameType, Vec<(&GameResult, EndgameEntry)>> = BTreeMap::new();
+        for g in games {
+            if let Some(e) = g.endgame { groups.entry(e.kind).or_default().push((g, e)); }
+        }
+        if groups.is_empty() { return None; }
+
+        let by_type = groups.into_iter().map(|(kind, gs)| {
+            let acc: Vec<f64> = gs.iter().filter_map(|(_, e)| e.accuracy).collect();
+            let winning: Vec<_> = gs.iter().filter(|(_, e)| e.expected >= WINNING).collect();
+            let losing: Vec<_> = gs.iter().filter(|(_, e)| e.expected <= 1.0 - WINNING).collect();
+            EndgameTypeStats {
+                kind,
+                games: gs.len(),
+                avg_accuracy: mean(&acc),
+                winning: winning.len(),
+                converted: winning.iter().filter(|(g, _)| g.score_for(target) == Some(1.0)).count(),
+                losing: losing.len(),
+                held: losing.iter().filter(|(g, _)| g.score_for(target).is_some_and(|s| s >= 0.5)).count(),
+            }
+        }).collect();
+        Some(Self { by_type })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nEndgames")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<18} {:>5} {:>9} {:>11} {:>9}", "Type", "Games", "Accuracy", "Converted", "Held")?;
+        for t in &self.by_type {
+            writeln!(out, "{:<18} {:>5} {:>8.1}% {:>11} {:>9}", t.kind.as_str(), t.games, t.avg_accuracy,
+                format!("{}/{}", t.converted, t.winning), format!("{}/{}", t.held, t.losing))?;
+        }
+        Ok(())
+    }
+}
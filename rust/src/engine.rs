@@ -0,0 +1,628 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::fen::Fen;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio, ChildStdin, ChildStdout};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+pub const STOCKFISH_PATH: &str = "/opt/homebrew/bin/stockfish";
+
+/// The engine binary: `$STOCKFISH_PATH` when set, else [`STOCKFISH_PATH`].
+pub fn stockfish_path() -> PathBuf {
+    std::env::var_os("STOCKFISH_PATH").filter(|p| !p.is_empty()).map_or_else(|| PathBuf::from(STOCKFISH_PATH), PathBuf::from)
+}
+
+pub type EngineError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A search ran past its timeout or the game deadline.
+#[derive(Debug)]
+pub struct Timeout {
+    /// The engine ignored `stop` and was killed.
+    pub killed: bool,
+}
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.killed { write!(f, "search timed out and the engine ignored stop; killed it") } else { write!(f, "search timed out") }
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+/// How long a timed-out engine gets to answer `stop` before it is killed.
+const STOP_GRACE: Duration = Duration::from_secs(2);
+
+/// Engine score, always from White's point of view.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Score {
+    Cp(i32),
+    Mate(i32),
+}
+
+/// Win/draw/loss per mille, always from White's point of view.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Wdl { pub win: i32, pub draw: i32, pub loss: i32 }
+
+impl Default for Wdl {
+    fn default() -> Self { Self { win: 333, draw: 334, loss: 333 } }
+}
+
+impl Wdl {
+    /// Approximates a WDL from a score with the Lichess win-chance model
+    /// (no draw share), for evaluations that come without one.
+    pub fn from_score(score: Score) -> Self {
+        let win = match score {
+            Score::Mate(n) if n > 0 => 1000,
+            Score::Mate(n) if n < 0 => 0,
+            Score::Mate(_) => 500,
+            Score::Cp(cp) => (1000.0 / (1.0 + (-0.00368208 * cp as f64).exp())).round() as i32,
+        };
+        Self { win, draw: 0, loss: 1000 - win }
+    }
+}
+
+impl Score {
+    /// The same score from the other side's point of view.
+    pub fn flipped(self) -> Self {
+        match self { Score::Cp(v) => Score::Cp(-v), Score::Mate(v) => Score::Mate(-v) }
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Cp(cp) => write!(f, "{:+.2}", *cp as f64 / 100.0),
+            Score::Mate(n) => write!(f, "#{}", n),
+        }
+    }
+}
+
+/// Moves of a principal variation kept per position.
+pub const PV_LEN: usize = 8;
+
+/// The start of the engine's principal variation, held inline so that
+/// evals stay `Copy`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pv {
+    moves: [Option<UciMove>; PV_LEN],
+}
+
+impl Pv {
+    /// Reads the moves after " pv " in an info line, up to `PV_LEN`.
+    fn parse(line: &str) -> Self {
+        let mut pv = Self::default();
+        for (slot, m) in pv.moves.iter_mut().zip(line.split_whitespace()) {
+            match UciMove::from_str(m) {
+                Ok(m) => *slot = Some(m),
+                Err(_) => break,
+            }
+        }
+        pv
+    }
+
+    /// The first `PV_LEN` of `moves`.
+    pub fn from_moves(moves: impl IntoIterator<Item = UciMove>) -> Self {
+        let mut pv = Self::default();
+        for (slot, m) in pv.moves.iter_mut().zip(moves) { *slot = Some(m); }
+        pv
+    }
+
+    pub fn moves(&self) -> impl Iterator<Item = &UciMove> {
+        self.moves.iter().map_while(Option::as_ref)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Eval {
+    pub score: Score,
+    pub wdl: Wdl,
+    pub best_move: Option<UciMove>,
+    /// Score of the second-best move, when searching with MultiPV ≥ 2 and the
+    /// position has more than one legal move.
+    pub second: Option<Score>,
+    /// The engine's line from this position, starting with `best_move`.
+    pub pv: Pv,
+}
+
+/// Engine settings bundled for runs that have to be comparable across
+/// machines or between each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum EnginePreset {
+    /// One thread, a fixed depth and the hash cleared before every position:
+    /// the same evals on any machine, at the cost of speed.
+    Reproducible,
+    /// Shallow and single-threaded, for quick looks at many games.
+    Fast,
+    /// Four threads and a deep search with a large hash; results vary
+    /// slightly between runs because of multi-threaded search.
+    Deep,
+}
+
+/// What a preset sets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PresetSettings {
+    pub threads: usize,
+    pub depth: u32,
+    pub hash_mb: u32,
+    /// Send `ucinewgame` before each position so no search depends on the
+    /// ones before it.
+    pub clear_hash: bool,
+}
+
+impl EnginePreset {
+    pub fn settings(self) -> PresetSettings {
+        match self {
+            Self::Reproducible => PresetSettings { threads: 1, depth: 14, hash_mb: 16, clear_hash: true },
+            Self::Fast => PresetSettings { threads: 1, depth: 8, hash_mb: 16, clear_hash: false },
+            Self::Deep => PresetSettings { threads: 4, depth: 22, hash_mb: 256, clear_hash: false },
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self { Self::Reproducible => "reproducible", Self::Fast => "fast", Self::Deep => "deep" }
+    }
+}
+
+/// Largest automatic hash per search thread, in MB; a bigger table no
+/// longer helps the depths a benchmark searches to.
+const AUTO_HASH_PER_THREAD_MB: u64 = 256;
+
+/// Hash per engine, in MB, for `engines` engines of `threads` threads on a
+/// machine with `total_mb` of memory: a quarter of the memory shared between
+/// the engines, at most [`AUTO_HASH_PER_THREAD_MB`] per thread and at least
+/// Stockfish's default of 16, rounded down to a power of two.
+pub fn auto_hash_mb(total_mb: u64, engines: usize, threads: usize) -> u32 {
+    let share = total_mb / 4 / engines.max(1) as u64;
+    let mb = share.min(AUTO_HASH_PER_THREAD_MB * threads.max(1) as u64).max(16);
+    1 << mb.ilog2()
+}
+
+/// Who the engine is and how it was set up, from the UCI handshake and the
+/// options this crate sent.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EngineInfo {
+    /// `id name`, e.g. "Stockfish 16.1".
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Network file, the EvalFile option's default unless set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nnue: Option<String>,
+    pub depth: u32,
+    /// Whether the hash is cleared before every position.
+    pub clear_hash: bool,
+    /// Every option set with `setoption`, with its value.
+    pub options: BTreeMap<String, String>,
+}
+
+impl EngineInfo {
+    /// "Stockfish 16.1, nn-b1a57edbea57.nnue, depth 14, Hash=16 Threads=1".
+    pub fn describe(&self) -> String {
+        let mut parts = vec![self.name.clone()];
+        parts.extend(self.nnue.clone());
+        parts.push(format!("depth {}", self.depth));
+        let options: Vec<String> = self.options.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        if !options.is_empty() { parts.push(options.join(" ")); }
+        if self.clear_hash { parts.push("hash cleared per position".into()); }
+        parts.join(", ")
+    }
+}
+
+/// Where engine output is read from: straight from the pipe, or, once a
+/// timeout is set, from a thread reading the pipe so that a wait can give up.
+enum Output {
+    Pipe(BufReader<ChildStdout>),
+    Thread(Receiver<String>),
+}
+
+/// The outcome of waiting for one line of output.
+enum Line {
+    Read,
+    Closed,
+    TimedOut,
+}
+
+pub struct StockfishEngine {
+    child: Child,
+    stdin: ChildStdin,
+    output: Output,
+    /// Longest a single search may take.
+    timeout: Option<Duration>,
+    /// When the current game must be done.
+    deadline: Option<Instant>,
+    depth: u32,
+    clear_hash: bool,
+    info: EngineInfo,
+    line_buf: String,
+    transcript: Option<Transcript>,
+}
+
+/// Every line sent to and read from one engine, with the seconds since it
+/// started: `  12.345 > go depth 12` and `  12.412 < bestmove e2e4`.
+struct Transcript {
+    out: LineWriter<File>,
+    start: Instant,
+}
+
+impl Transcript {
+    fn create(path: &Path, binary: &Path) -> std::io::Result<Self> {
+        let mut out = LineWriter::new(File::create(path)?);
+        writeln!(out, "# {} started {}", binary.display(), crate::date::fmt_unix(crate::date::now_unix()))?;
+        Ok(Self { out, start: Instant::now() })
+    }
+
+    /// Written line by line, so a hung or crashed run keeps everything up
+    /// to its last line. Write errors are ignored: the transcript must not
+    /// stop the analysis.
+    fn line(&mut self, dir: char, line: &str) {
+        let _ = writeln!(self.out, "{:>9.3} {} {}", self.start.elapsed().as_secs_f64(), dir, line);
+    }
+}
+
+impl StockfishEngine {
+    pub fn new(threads: usize, depth: u32) -> Result<Self, EngineError> {
+        Self::with_path(&stockfish_path(), threads, depth)
+    }
+
+    /// Starts the engine binary at `path` instead of [`stockfish_path`].
+    pub fn with_path(path: &Path, threads: usize, depth: u32) -> Result<Self, EngineError> {
+        Self::recorded(path, threads, depth, None)
+    }
+
+    /// Like [`Self::with_path`], writing the UCI exchange to `transcript`
+    /// from the handshake on.
+    pub fn recorded(path: &Path, threads: usize, depth: u32, transcript: Option<&Path>) -> Result<Self, EngineError> {
+        let transcript = transcript.map(|t| Transcript::create(t, path)
+            .map_err(|e| format!("cannot create {}: {}", t.display(), e))).transpose()?;
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        // Use smaller buffer for lower latency (like Python's bufsize=1)
+        let reader = BufReader::with_capacity(256, stdout);
+
+        let mut engine = Self {
+            child,
+            stdin,
+            output: Output::Pipe(reader),
+            timeout: None,
+            deadline: None,
+            depth,
+            clear_hash: false,
+            info: EngineInfo { depth, ..EngineInfo::default() },
+            line_buf: String::with_capacity(512),
+            transcript,
+        };
+
+        engine.send("uci")?;
+        engine.read_id()?;
+        engine.set_option("Threads", threads)?;
+        engine.set_option("UCI_ShowWDL", true)?;
+        Ok(engine)
+    }
+
+    /// Reads the handshake up to `uciok`, keeping the `id` lines and the
+    /// default network file.
+    fn read_id(&mut self) -> Result<(), EngineError> {
+        loop {
+            if let Line::Closed = self.next_line(None)? {
+                return Err("engine exited while waiting for uciok".into());
+            }
+            let line = self.line_buf.trim_end();
+            crate::trace!("  < {}", line);
+            if let Some(t) = &mut self.transcript { t.line('<', line); }
+            if line == "uciok" { return Ok(()); }
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.info.name = name.to_string();
+                self.info.version = name.split_whitespace().skip(1).find(|w| w.starts_with(|c: char| c.is_ascii_digit())).map(str::to_string);
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                self.info.author = Some(author.to_string());
+            } else if let Some(rest) = line.strip_prefix("option name EvalFile ") {
+                self.info.nnue = rest.split_once(" default ").map(|(_, f)| f.trim().to_string()).filter(|f| !f.is_empty() && f != "<empty>");
+            }
+        }
+    }
+
+    /// Identity and applied options of this engine.
+    pub fn info(&self) -> &EngineInfo { &self.info }
+
+    /// Limits every later search (and `isready` wait) to `timeout`. A search
+    /// that runs over is sent `stop`; an engine that then stays silent for
+    /// a few more seconds is killed. Either way the search fails with
+    /// [`Timeout`].
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+        if timeout.is_some() { self.read_in_thread(); }
+    }
+
+    /// Makes searches fail with [`Timeout`] once `deadline` has passed, as
+    /// [`Self::set_timeout`] does.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+        if deadline.is_some() { self.read_in_thread(); }
+    }
+
+    /// Moves reading the pipe to a thread, so that waits can time out.
+    fn read_in_thread(&mut self) {
+        if let Output::Thread(_) = self.output { return; }
+        let (tx, rx) = mpsc::channel();
+        let Output::Pipe(mut reader) = std::mem::replace(&mut self.output, Output::Thread(rx)) else { unreachable!() };
+        std::thread::spawn(move || {
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+                if tx.send(std::mem::take(&mut line)).is_err() { break; }
+            }
+        });
+    }
+
+    /// Reads the next line of output into `line_buf`, giving up at `deadline`
+    /// (only once output is read in a thread).
+    fn next_line(&mut self, deadline: Option<Instant>) -> std::io::Result<Line> {
+        self.line_buf.clear();
+        match &mut self.output {
+            Output::Pipe(reader) => Ok(if reader.read_line(&mut self.line_buf)? == 0 { Line::Closed } else { Line::Read }),
+            Output::Thread(rx) => {
+                let line = match deadline {
+                    Some(d) => rx.recv_timeout(d.saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                Ok(match line {
+                    Ok(line) => { self.line_buf = line; Line::Read }
+                    Err(RecvTimeoutError::Timeout) => Line::TimedOut,
+                    Err(RecvTimeoutError::Disconnected) => Line::Closed,
+                })
+            }
+        }
+    }
+
+    /// The earlier of the search timeout from now and the game deadline.
+    fn search_deadline(&self) -> Option<Instant> {
+        let timeout = self.timeout.map(|t| Instant::now() + t);
+        match (timeout, self.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    #[inline]
+    fn send(&mut self, cmd: &str) -> Result<(), std::io::Error> {
+        crate::trace!("  > {}", cmd);
+        if let Some(t) = &mut self.transcript { t.line('>', cmd); }
+        writeln!(self.stdin, "{}", cmd)?;
+        self.stdin.flush()
+    }
+
+    /// Reads engine output until a line containing `token`, collecting the last
+    /// reported score and WDL (side-to-move relative, as UCI sends them).
+    fn wait_for(&mut self, token: &str) -> Result<Eval, EngineError> {
+        let mut eval = Eval { score: Score::Cp(0), wdl: Wdl::default(), best_move: None, second: None, pv: Pv::default() };
+        let mut deadline = self.search_deadline();
+        let mut stopped = false;
+
+        loop {
+            match self.next_line(deadline)? {
+                Line::Read => {}
+                Line::Closed => return Err(format!("engine exited while waiting for {}", token).into()),
+                Line::TimedOut if !stopped && token == "bestmove" => {
+                    crate::verbose!("  Search timed out; sending stop");
+                    self.send("stop")?;
+                    stopped = true;
+                    deadline = Some(Instant::now() + STOP_GRACE);
+                    continue;
+                }
+                Line::TimedOut => {
+                    let _ = self.child.kill();
+                    let _ = self.child.wait();
+                    return Err(Box::new(Timeout { killed: true }));
+                }
+            }
+            crate::trace!("  < {}", self.line_buf.trim_end());
+            if let Some(t) = &mut self.transcript { t.line('<', self.line_buf.trim_end()); }
+
+            // With MultiPV only the first line sets the eval; the second gives the runner-up
+            let pv = self.line_buf.find(" multipv ")
+                .and_then(|p| self.line_buf[p + 9..].split_whitespace().next()?.parse::<u32>().ok())
+                .unwrap_or(1);
+            if pv == 2 {
+                if let Some(score_pos) = self.line_buf.find(" score ") {
+                    let mut parts = self.line_buf[score_pos + 7..].split_whitespace();
+                    match (parts.next(), parts.next().and_then(|v| v.parse().ok())) {
+                        (Some("cp"), Some(v)) => eval.second = Some(Score::Cp(v)),
+                        (Some("mate"), Some(v)) => eval.second = Some(Score::Mate(v)),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if pv > 2 { continue; }
+
+            // Work on &str slices of the line buffer to avoid allocations
+            if let Some(wdl_pos) = self.line_buf.find(" wdl ") {
+                let mut parts = self.line_buf[wdl_pos + 5..].split_whitespace();
+                if let (Some(w), Some(d), Some(l)) = (parts.next(), parts.next(), parts.next()) {
+                    eval.wdl = Wdl {
+                        win: w.parse().unwrap_or(333),
+                        draw: d.parse().unwrap_or(334),
+                        loss: l.parse().unwrap_or(333),
+                    };
+                }
+            }
+            if let Some(score_pos) = self.line_buf.find(" score ") {
+                let mut parts = self.line_buf[score_pos + 7..].split_whitespace();
+                match (parts.next(), parts.next().and_then(|v| v.parse().ok())) {
+                    (Some("cp"), Some(v)) => eval.score = Score::Cp(v),
+                    (Some("mate"), Some(v)) => eval.score = Score::Mate(v),
+                    _ => {}
+                }
+            }
+            if let Some(pv_pos) = self.line_buf.find(" pv ") {
+                eval.pv = Pv::parse(&self.line_buf[pv_pos + 4..]);
+            }
+
+            if self.line_buf.contains(token) {
+                // The engine is still usable, but the eval is not from a full search
+                if stopped { return Err(Box::new(Timeout { killed: false })); }
+                if let Some(rest) = self.line_buf.strip_prefix("bestmove ") {
+                    eval.best_move = rest.split_whitespace().next().and_then(|m| UciMove::from_str(m).ok());
+                    // Terminal positions get no search, hence no wdl line
+                    if eval.best_move.is_none() {
+                        eval.wdl = match eval.score {
+                            Score::Mate(0) => Wdl { win: 0, draw: 0, loss: 1000 },
+                            _ => Wdl { win: 0, draw: 1000, loss: 0 },
+                        };
+                    }
+                }
+                return Ok(eval);
+            }
+        }
+    }
+
+    /// Analyzes a position and returns its evaluation from White's point of view.
+    #[inline]
+    pub fn analyze(&mut self, fen: &str, white_to_move: bool) -> Result<Eval, EngineError> {
+        if self.clear_hash {
+            self.send("ucinewgame")?;
+            self.send("isready")?;
+            self.wait_for("readyok")?;
+        }
+        self.send(&format!("position fen {}", fen))?;
+        self.send(&format!("go depth {}", self.depth))?;
+        let mut eval = self.wait_for("bestmove")?;
+        if !white_to_move {
+            eval.score = eval.score.flipped();
+            eval.second = eval.second.map(Score::flipped);
+            eval.wdl = Wdl { win: eval.wdl.loss, draw: eval.wdl.draw, loss: eval.wdl.win };
+        }
+        Ok(eval)
+    }
+
+    /// Sets a UCI option, e.g. `set_option("MultiPV", 2)`.
+    pub fn set_option(&mut self, name: &str, value: impl std::fmt::Display) -> Result<(), EngineError> {
+        self.send(&format!("setoption name {} value {}", name, value))?;
+        self.send("isready")?;
+        self.wait_for("readyok")?;
+        if name == "EvalFile" { self.info.nnue = Some(value.to_string()); }
+        self.info.options.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    /// Clears the hash before every position, making each search independent
+    /// of the positions analyzed before it.
+    pub fn set_clear_hash(&mut self, on: bool) {
+        self.clear_hash = on;
+        self.info.clear_hash = on;
+    }
+
+    pub fn quit(&mut self) {
+        let _ = self.send("quit");
+    }
+
+    /// Searches the first two [`REFERENCE_POSITIONS`]: the initial position,
+    /// which must come out roughly level with a legal best move, and a back
+    /// rank mate in one, which must be found. Catches a wrong binary, a
+    /// broken network file or bad options before any game is lost to them.
+    pub fn self_check(&mut self) -> Result<(), EngineError> {
+        for p in &REFERENCE_POSITIONS[..2] {
+            let eval = self.analyze(p.fen, p.white_to_move())?;
+            if let Some(problem) = p.problem(&eval) { return Err(format!("{}: {}", p.name, problem).into()); }
+        }
+        Ok(())
+    }
+}
+
+/// What the eval of a reference position must be, from White's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// Within ±[`CHECK_MAX_LEVEL_CP`].
+    Level,
+    /// At least [`CHECK_MIN_WINNING_CP`] for White, or a mate by White.
+    WhiteWinning,
+    /// At least [`CHECK_MIN_WINNING_CP`] for Black, or a mate by Black.
+    BlackWinning,
+    /// Mate in exactly this many moves, negative when Black mates.
+    Mate(i32),
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expected::Level => write!(f, "within ±{:.2}", CHECK_MAX_LEVEL_CP as f64 / 100.0),
+            Expected::WhiteWinning => write!(f, "≥ +{:.2}", CHECK_MIN_WINNING_CP as f64 / 100.0),
+            Expected::BlackWinning => write!(f, "≤ -{:.2}", CHECK_MIN_WINNING_CP as f64 / 100.0),
+            Expected::Mate(n) => write!(f, "#{}", n),
+        }
+    }
+}
+
+/// A position whose answer is known, for checking an engine.
+#[derive(Clone, Copy, Debug)]
+pub struct ReferencePosition {
+    pub name: &'static str,
+    pub fen: &'static str,
+    /// Best moves in UCI notation, any of which is right; any legal move
+    /// when empty.
+    pub best: &'static [&'static str],
+    pub expected: Expected,
+}
+
+impl ReferencePosition {
+    pub fn white_to_move(&self) -> bool { self.fen.split(' ').nth(1) != Some("b") }
+
+    /// What is wrong with `eval` as the engine's answer, if anything.
+    pub fn problem(&self, eval: &Eval) -> Option<String> {
+        let ok = match (self.expected, eval.score) {
+            (Expected::Level, Score::Cp(cp)) => cp.abs() <= CHECK_MAX_LEVEL_CP,
+            (Expected::WhiteWinning, Score::Cp(cp)) => cp >= CHECK_MIN_WINNING_CP,
+            (Expected::BlackWinning, Score::Cp(cp)) => cp <= -CHECK_MIN_WINNING_CP,
+            (Expected::WhiteWinning, Score::Mate(n)) => n > 0,
+            (Expected::BlackWinning, Score::Mate(n)) => n < 0,
+            (Expected::Mate(want), Score::Mate(n)) => n == want,
+            _ => false,
+        };
+        if !ok { return Some(format!("scored {}, expected {}", eval.score, self.expected)); }
+        let Some(mv) = &eval.best_move else { return Some("no best move".into()) };
+        let found = mv.to_string();
+        if !self.best.is_empty() {
+            if !self.best.contains(&found.as_str()) { return Some(format!("best move {}, expected {}", found, self.best.join(" or "))); }
+            return None;
+        }
+        let pos: Option<Chess> = self.fen.parse::<Fen>().ok().and_then(|f| f.into_position(CastlingMode::Standard).ok());
+        match pos {
+            Some(pos) if mv.to_move(&pos).is_ok() => None,
+            _ => Some(format!("illegal best move {}", found)),
+        }
+    }
+}
+
+/// Largest score, in centipawns, of a position that [`Expected::Level`] accepts.
+pub const CHECK_MAX_LEVEL_CP: i32 = 150;
+
+/// Smallest advantage, in centipawns, that [`Expected::WhiteWinning`] and
+/// [`Expected::BlackWinning`] accept.
+pub const CHECK_MIN_WINNING_CP: i32 = 200;
+
+/// Positions with clear answers, which any engine at a shallow depth gets
+/// right: a level start, mates in one for either side, material left
+/// hanging and a promotion. [`StockfishEngine::self_check`] searches the
+/// first two.
+pub const REFERENCE_POSITIONS: [ReferencePosition; 7] = [
+    ReferencePosition { name: "Initial position", fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", best: &[], expected: Expected::Level },
+    ReferencePosition { name: "Back rank mate (White)", fen: "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", best: &["a1a8"], expected: Expected::Mate(1) },
+    ReferencePosition { name: "Back rank mate (Black)", fen: "r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", best: &["a8a1"], expected: Expected::Mate(-1) },
+    ReferencePosition { name: "Hanging queen (White takes)", fen: "4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", best: &["d2d5"], expected: Expected::WhiteWinning },
+    ReferencePosition { name: "Hanging queen (Black takes)", fen: "4k3/3r4/8/8/3Q4/8/8/4K3 b - - 0 1", best: &["d7d4"], expected: Expected::BlackWinning },
+    ReferencePosition { name: "Queen up, Black to move", fen: "4k3/8/8/8/8/8/8/3QK3 b - - 0 1", best: &[], expected: Expected::WhiteWinning },
+    ReferencePosition { name: "Promotion", fen: "8/P7/8/8/8/8/8/k6K w - - 0 1", best: &["a7a8q"], expected: Expected::WhiteWinning },
+];
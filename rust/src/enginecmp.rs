@@ -0,0 +1,134 @@
+//! Two engine binaries on the same positions: how far their evals and best
+//! moves diverge and which one is faster.
+
+use crate::analysis::capped_cp;
+use crate::engine::{EngineInfo, Eval};
+use crate::pgn::parse_pgn_moves;
+use crate::stats::{paired, TTest};
+use serde::Serialize;
+use shakmaty::{fen::Fen, san::San, Chess, Color, EnPassantMode, Position};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Eval differences above this many centipawns count as divergent.
+pub const DIVERGENCE_CP: i32 = 50;
+
+/// A position to analyze.
+#[derive(Clone, Debug)]
+pub struct TestPosition {
+    pub fen: String,
+    pub white_to_move: bool,
+}
+
+/// Distinct positions reached in the games, in order, at most `limit`.
+/// Games stop contributing at their first unplayable move.
+pub fn game_positions(games: &[&str], limit: usize) -> Vec<TestPosition> {
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for pgn in games {
+        let mut pos = Chess::default();
+        for m in parse_pgn_moves(pgn) {
+            let Some(mv) = San::from_str(m).ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+            pos.play_unchecked(mv);
+            if pos.is_game_over() { break; }
+            let fen = Fen::from_position(&pos, EnPassantMode::Legal).to_string();
+            if seen.insert(fen.clone()) {
+                out.push(TestPosition { fen, white_to_move: pos.turn() == Color::White });
+                if out.len() >= limit { return out; }
+            }
+        }
+    }
+    out
+}
+
+/// One engine's evals of the position set, in order.
+#[derive(Debug)]
+pub struct EngineRun {
+    pub info: EngineInfo,
+    pub evals: Vec<Eval>,
+    pub seconds: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EngineSide {
+    pub engine: EngineInfo,
+    pub seconds: f64,
+    pub positions_per_second: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EngineComparison {
+    pub positions: usize,
+    /// Absolute eval difference, in centipawns capped at ±1000.
+    pub mean_cp_diff: f64,
+    pub p90_cp_diff: f64,
+    pub max_cp_diff: i32,
+    /// Share of positions whose evals differ by more than [`DIVERGENCE_CP`].
+    pub divergent: f64,
+    /// Share of positions where the engines favour different sides, each by
+    /// more than [`DIVERGENCE_CP`].
+    pub sign_flips: f64,
+    /// Share of positions with a different best move.
+    pub bestmove_disagreement: f64,
+    /// Paired test of A's evals against B's (White's view, capped cp): a
+    /// significant difference means one engine systematically scores higher.
+    pub eval_bias: Option<TTest>,
+    pub a: EngineSide,
+    pub b: EngineSide,
+}
+
+impl EngineComparison {
+    pub fn new(a: EngineRun, b: EngineRun) -> Self {
+        let n = a.evals.len().min(b.evals.len());
+        let mut diffs: Vec<i32> = (0..n)
+            .map(|i| (capped_cp(a.evals[i].score, true) - capped_cp(b.evals[i].score, true)).abs())
+            .collect();
+        diffs.sort_unstable();
+        let share = |count: usize| if n == 0 { 0.0 } else { count as f64 / n as f64 };
+        let flips = (0..n).filter(|&i| {
+            let (x, y) = (capped_cp(a.evals[i].score, true), capped_cp(b.evals[i].score, true));
+            (x > DIVERGENCE_CP && y < -DIVERGENCE_CP) || (x < -DIVERGENCE_CP && y > DIVERGENCE_CP)
+        }).count();
+        let cp = |run: &EngineRun| run.evals[..n].iter().map(|e| capped_cp(e.score, true) as f64).collect::<Vec<_>>();
+        let eval_bias = paired(&cp(&a), &cp(&b));
+        let disagree = (0..n).filter(|&i| a.evals[i].best_move != b.evals[i].best_move).count();
+        let side = |run: EngineRun| EngineSide {
+            positions_per_second: if run.seconds > 0.0 { run.evals.len() as f64 / run.seconds } else { 0.0 },
+            seconds: run.seconds,
+            engine: run.info,
+        };
+        Self {
+            positions: n,
+            mean_cp_diff: if n == 0 { 0.0 } else { diffs.iter().map(|&d| d as f64).sum::<f64>() / n as f64 },
+            p90_cp_diff: diffs.get((n * 9 / 10).min(n.saturating_sub(1))).map_or(0.0, |&d| d as f64),
+            max_cp_diff: diffs.last().copied().unwrap_or(0),
+            divergent: share(diffs.iter().filter(|&&d| d > DIVERGENCE_CP).count()),
+            sign_flips: share(flips),
+            bestmove_disagreement: share(disagree),
+            eval_bias,
+            a: side(a),
+            b: side(b),
+        }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nEngine Comparison")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "A: {}", self.a.engine.describe())?;
+        writeln!(out, "B: {}", self.b.engine.describe())?;
+        writeln!(out, "Positions: {}", self.positions)?;
+        writeln!(out, "Eval difference: mean {:.1}cp, p90 {:.0}cp, max {}cp", self.mean_cp_diff, self.p90_cp_diff, self.max_cp_diff)?;
+        writeln!(out, "Divergent (>{}cp): {:.1}%", DIVERGENCE_CP, 100.0 * self.divergent)?;
+        writeln!(out, "Opposite verdicts: {:.1}%", 100.0 * self.sign_flips)?;
+        writeln!(out, "Best move disagreement: {:.1}%", 100.0 * self.bestmove_disagreement)?;
+        if let Some(t) = &self.eval_bias { writeln!(out, "Eval bias A - B (cp): {}", t.describe(1))?; }
+        writeln!(out, "\n{:<4} {:>10} {:>14}", "", "Time (s)", "Positions/s")?;
+        writeln!(out, "{:<4} {:>10.2} {:>14.1}", "A", self.a.seconds, self.a.positions_per_second)?;
+        writeln!(out, "{:<4} {:>10.2} {:>14.1}", "B", self.b.seconds, self.b.positions_per_second)?;
+        if self.b.positions_per_second > 0.0 {
+            writeln!(out, "A/B throughput: {:.2}x", self.a.positions_per_second / self.b.positions_per_second)?;
+        }
+        Ok(())
+    }
+}
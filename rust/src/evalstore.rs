@@ -0,0 +1,211 @@
+//! Engine evaluations saved per position, so a run's engine work can be
+//! reused by later runs and by `rescore`.
+//!
+//! The file is a little-endian binary: the magic `CBEV`, a version byte and
+//! a record count, then one record per position:
+//!
+//! ```text
+//! zobrist u64 | depth u8 | score tag u8, i32 | wdl 3 × u16 |
+//! second tag u8, i32 | best u16 | pv length u8 | pv u16 × length
+//! ```
+//!
+//! Scores are from White's point of view (tag 0 = cp, 1 = mate, 2 = none for
+//! the second score); moves are packed as from | to << 6 | promotion << 12,
+//! with 0 for none.
+
+use crate::engine::{Eval, Pv, Score, Wdl};
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, EnPassantMode, Role, Square};
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+const MAGIC: &[u8; 4] = b"CBEV";
+const VERSION: u8 = 1;
+
+/// An evaluation and the depth it was searched to.
+#[derive(Clone, Copy, Debug)]
+pub struct StoredEval {
+    pub depth: u8,
+    pub eval: Eval,
+}
+
+/// Evaluations keyed by Zobrist hash, shared by the analysis workers.
+#[derive(Default)]
+pub struct EvalStore {
+    evals: Mutex<HashMap<u64, StoredEval>>,
+}
+
+impl EvalStore {
+    /// The evaluations in `path`, or an empty store if it does not exist yet.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        match Self::load(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            r => r,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn key(pos: &Chess) -> u64 {
+        pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0
+    }
+
+    /// Keeps `eval` unless the position is already stored at a greater depth.
+    pub fn record(&self, pos: &Chess, depth: u32, eval: &Eval) {
+        let depth = depth.min(u8::MAX as u32) as u8;
+        let mut evals = self.evals.lock().unwrap();
+        let slot = evals.entry(Self::key(pos)).or_insert(StoredEval { depth, eval: *eval });
+        if depth >= slot.depth { *slot = StoredEval { depth, eval: *eval }; }
+    }
+
+    pub fn get(&self, pos: &Chess) -> Option<StoredEval> {
+        self.evals.lock().unwrap().get(&Self::key(pos)).copied()
+    }
+
+    pub fn len(&self) -> usize { self.evals.lock().unwrap().len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn read(r: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC { return Err(invalid("not an evals file")); }
+        if read_u8(r)? != VERSION { return Err(invalid("unsupported evals file version")); }
+        let count = u32::from_le_bytes(read_array(r)?) as usize;
+        let mut evals = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let key = u64::from_le_bytes(read_array(r)?);
+            let depth = read_u8(r)?;
+            let score = read_score(r)?.ok_or_else(|| invalid("missing score"))?;
+            let wdl = Wdl {
+                win: u16::from_le_bytes(read_array(r)?) as i32,
+                draw: u16::from_le_bytes(read_array(r)?) as i32,
+                loss: u16::from_le_bytes(read_array(r)?) as i32,
+            };
+            let second = read_score(r)?;
+            let best_move = unpack_move(u16::from_le_bytes(read_array(r)?));
+            let mut pv = Vec::new();
+            for _ in 0..read_u8(r)? { pv.extend(unpack_move(u16::from_le_bytes(read_array(r)?))); }
+            let eval = Eval { score, wdl, best_move, second, pv: Pv::from_moves(pv) };
+            evals.insert(key, StoredEval { depth, eval });
+        }
+        Ok(Self { evals: Mutex::new(evals) })
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        let evals = self.evals.lock().unwrap();
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&(evals.len() as u32).to_le_bytes())?;
+        // By key, so the same evals always make the same file
+        let mut keys: Vec<&u64> = evals.keys().collect();
+        keys.sort_unstable();
+        for key in keys {
+            let s = &evals[key];
+            let e = &s.eval;
+            w.write_all(&key.to_le_bytes())?;
+            w.write_all(&[s.depth])?;
+            write_score(w, Some(e.score))?;
+            for v in [e.wdl.win, e.wdl.draw, e.wdl.loss] { w.write_all(&(v.clamp(0, 1000) as u16).to_le_bytes())?; }
+            write_score(w, e.second)?;
+            w.write_all(&e.best_move.as_ref().map_or(0, pack_move).to_le_bytes())?;
+            let pv: Vec<u16> = e.pv.moves().map(pack_move).filter(|&m| m != 0).collect();
+            w.write_all(&[pv.len() as u8])?;
+            for m in pv { w.write_all(&m.to_le_bytes())?; }
+        }
+        Ok(())
+    }
+
+    /// Writes the store to `path`, replacing the file. The store is written
+    /// next to it first, so a failed or interrupted save keeps the old one.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        let written = std::fs::File::create(&tmp).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            self.write(&mut out)?;
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()
+        });
+        match written {
+            Ok(()) => std::fs::rename(&tmp, path),
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, msg) }
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> { Ok(read_array::<1>(r)?[0]) }
+
+fn read_score(r: &mut impl Read) -> io::Result<Option<Score>> {
+    let tag = read_u8(r)?;
+    let v = i32::from_le_bytes(read_array(r)?);
+    Ok(match tag {
+        0 => Some(Score::Cp(v)),
+        1 => Some(Score::Mate(v)),
+        _ => None,
+    })
+}
+
+fn write_score(w: &mut impl Write, score: Option<Score>) -> io::Result<()> {
+    let (tag, v) = match score {
+        Some(Score::Cp(v)) => (0, v),
+        Some(Score::Mate(v)) => (1, v),
+        None => (2, 0),
+    };
+    w.write_all(&[tag])?;
+    w.write_all(&v.to_le_bytes())
+}
+
+fn pack_move(m: &UciMove) -> u16 {
+    match *m {
+        UciMove::Normal { from, to, promotion } => {
+            u16::from(from) | u16::from(to) << 6 | promotion.map_or(0, |r| r as u16) << 12
+        }
+        _ => 0,
+    }
+}
+
+fn unpack_move(v: u16) -> Option<UciMove> {
+    if v == 0 { return None; }
+    let square = |i: u16| Square::new((i & 63) as u32);
+    let promotion = match v >> 12 {
+        0 => None,
+        r => Some(Role::try_from(r as u8).ok()?),
+    };
+    Some(UciMove::Normal { from: square(v), to: square(v >> 6), promotion })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_replaces_the_file_without_leaving_a_temporary() {
+        let dir = std::env::temp_dir().join(format!("chess-bench-evalstore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("evals.bin");
+        std::fs::write(&path, b"old").unwrap();
+        let store = EvalStore::default();
+        let eval = Eval { score: Score::Cp(35), wdl: Wdl { win: 100, draw: 850, loss: 50 }, best_move: None, second: None, pv: Pv::default() };
+        store.record(&Chess::default(), 20, &eval);
+        store.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+        let loaded = EvalStore::load(&path).unwrap();
+        assert_eq!(loaded.get(&Chess::default()).unwrap().eval.score, Score::Cp(35));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
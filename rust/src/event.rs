@@ -0,0 +1,90 @@
+//! Standings of a tournament: every player's points, accuracy and blunders,
+//! with their score in each round.
+
+use crate::report::{csv_field, GameResult};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+#[derive(Serialize, Debug)]
+pub struct EventPlayer {
+    pub rank: usize,
+    pub username: String,
+    /// Points from the analyzed games (byes and unfinished games not counted).
+    pub points: f64,
+    pub games: usize,
+    pub accuracy: f64,
+    pub blunders: usize,
+    /// Points scored in each round; `None` where the player had no game.
+    pub rounds: Vec<Option<f64>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EventStandings {
+    pub name: String,
+    pub games: usize,
+    /// Ranked by points, then accuracy.
+    pub players: Vec<EventPlayer>,
+}
+
+impl EventStandings {
+    /// Standings over `games`, each with its round number (from 1) out of `rounds`.
+    pub fn new(name: &str, rounds: usize, games: &[(usize, GameResult)]) -> Self {
+        #[derive(Default)]
+        struct Acc { points: f64, accuracy: Vec<f64>, blunders: usize, rounds: Vec<Option<f64>> }
+        let mut by_player: BTreeMap<&str, Acc> = BTreeMap::new();
+        for (round, g) in games {
+            for name in [g.white.as_str(), g.black.as_str()] {
+                let p = by_player.entry(name).or_insert_with(|| Acc { rounds: vec![None; rounds], ..Default::default() });
+                let score = g.score_for(name).unwrap_or(0.0);
+                p.points += score;
+                p.accuracy.push(g.accuracy_for(name));
+                p.blunders += g.blunders_for(name);
+                if let Some(r) = p.rounds.get_mut(round - 1) { *r = Some(r.unwrap_or(0.0) + score); }
+            }
+        }
+        let mut players: Vec<EventPlayer> = by_player.into_iter().map(|(name, p)| EventPlayer {
+            rank: 0,
+            username: name.to_string(),
+            points: p.points,
+            games: p.accuracy.len(),
+            accuracy: crate::accuracy::mean(&p.accuracy),
+            blunders: p.blunders,
+            rounds: p.rounds,
+        }).collect();
+        players.sort_by(|a, b| b.points.total_cmp(&a.points).then_with(|| b.accuracy.total_cmp(&a.accuracy)));
+        for (i, p) in players.iter_mut().enumerate() { p.rank = i + 1; }
+        Self { name: name.to_string(), games: games.len(), players }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let width = self.players.iter().map(|p| p.username.len()).max().unwrap_or(0).clamp(8, 24);
+        writeln!(out, "\n{}", self.name)?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{} games, {} players", self.games, self.players.len())?;
+        let rounds = self.players.first().map_or(0, |p| p.rounds.len());
+        write!(out, "{:>4}  {:<w$}  {:>6}  {:>5}  {:>8}  {:>8}", "Rank", "Player", "Points", "Games", "Accuracy", "Blunders", w = width)?;
+        for r in 1..=rounds { write!(out, " {:>4}", format!("R{}", r))?; }
+        writeln!(out)?;
+        for p in &self.players {
+            let name: String = p.username.chars().take(width).collect();
+            write!(out, "{:>4}  {:<w$}  {:>6}  {:>5}  {:>7.2}%  {:>8}", p.rank, name, p.points, p.games, p.accuracy, p.blunders, w = width)?;
+            for r in &p.rounds { write!(out, " {:>4}", r.map_or("-".to_string(), |s| s.to_string()))?; }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        let rounds = self.players.first().map_or(0, |p| p.rounds.len());
+        write!(out, "rank,username,points,games,accuracy,blunders")?;
+        for r in 1..=rounds { write!(out, ",round_{}", r)?; }
+        writeln!(out)?;
+        for p in &self.players {
+            write!(out, "{},{},{},{},{:.2},{}", p.rank, csv_field(&p.username), p.points, p.games, p.accuracy, p.blunders)?;
+            for r in &p.rounds { write!(out, ",{}", r.map_or(String::new(), |s| s.to_string()))?; }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+}
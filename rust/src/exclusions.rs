@@ -0,0 +1,45 @@
+//! Moves left out of scoring by the config file's `exclude` lists: opening
+//! lines, and move sequences wherever they occur (known premoves in bullet,
+//! forced repetitions). Excluded moves count as book moves, and positions
+//! only reached and left by them are not searched.
+
+use crate::pgn::MoveTokens;
+use serde::Deserialize;
+
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Exclusions {
+    /// Lines from the initial position ("1. e4 e5 2. Nf3"); a game that
+    /// plays a whole line has those moves excluded.
+    pub openings: Vec<String>,
+    /// Move sequences ("Nf3 Nf6 Ng1 Ng8") excluded wherever a game plays them
+    /// in a row.
+    pub sequences: Vec<String>,
+}
+
+/// SAN without check and mate marks, so "Nf3+" matches "Nf3". Annotation
+/// marks are already gone from the tokens.
+fn bare(san: &str) -> &str { san.trim_end_matches(['+', '#']) }
+
+fn tokens(line: &str) -> Vec<&str> { MoveTokens::new(line).map(bare).collect() }
+
+impl Exclusions {
+    pub fn is_empty(&self) -> bool { self.openings.is_empty() && self.sequences.is_empty() }
+
+    /// Which of `moves` are excluded; empty when nothing is.
+    pub fn mark(&self, moves: &[&str]) -> Vec<bool> {
+        if self.is_empty() { return Vec::new(); }
+        let moves: Vec<&str> = moves.iter().map(|m| bare(m)).collect();
+        let mut excluded = vec![false; moves.len()];
+        for line in self.openings.iter().map(|l| tokens(l)) {
+            if !line.is_empty() && moves.starts_with(&line) { excluded[..line.len()].fill(true); }
+        }
+        for seq in self.sequences.iter().map(|s| tokens(s)) {
+            if seq.is_empty() { continue; }
+            for start in 0..moves.len().saturating_sub(seq.len() - 1) {
+                if moves[start..].starts_with(&seq) { excluded[start..start + seq.len()].fill(true); }
+            }
+        }
+        if excluded.contains(&true) { excluded } else { Vec::new() }
+    }
+}
@@ -0,0 +1,153 @@
+//! Reference move databases (Lichess opening explorer or a local PGN
+//! collection), used to tell theory from over-the-board play.
+
+use crate::http::{HttpClient, HttpError};
+use crate::pgn::{parse_pgn_moves, split_games};
+use serde::Deserialize;
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{Chess, Color, Position, fen::Fen, san::San, CastlingMode, EnPassantMode, Move};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// A database answering "which moves were played here, and how often".
+pub trait MoveReference: Sync {
+    /// Games per move from `pos`, as (uci, count).
+    fn moves(&self, pos: &Chess) -> Result<Vec<(String, u64)>, HttpError>;
+}
+
+/// How often the player's moves matched the reference.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchCount {
+    pub matched: usize,
+    pub positions: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExplorerDb {
+    Masters,
+    Lichess,
+}
+
+#[derive(Deserialize)]
+struct ExplorerResponse { moves: Vec<ExplorerMove> }
+
+#[derive(Deserialize)]
+struct ExplorerMove { uci: String, white: u64, draws: u64, black: u64 }
+
+pub struct Explorer<'a> {
+    client: &'a HttpClient,
+    db: ExplorerDb,
+    token: Option<&'a str>,
+}
+
+impl<'a> Explorer<'a> {
+    pub fn new(client: &'a HttpClient, db: ExplorerDb, token: Option<&'a str>) -> Self {
+        Self { client, db, token }
+    }
+
+    /// Games in the database per move from `fen`, as (uci, count), most played first.
+    /// Responses are cached indefinitely; the reference databases change slowly.
+    pub fn moves_at(&self, fen: &str) -> Result<Vec<(String, u64)>, HttpError> {
+        let base = match self.db {
+            ExplorerDb::Masters => "https://explorer.lichess.ovh/masters",
+            ExplorerDb::Lichess => "https://explorer.lichess.ovh/lichess",
+        };
+        let url = reqwest::Url::parse_with_params(base, &[("fen", fen), ("moves", "30"), ("topGames", "0"), ("recentGames", "0")])?;
+        let resp: ExplorerResponse = serde_json::from_str(&self.client.get_text_auth(url.as_str(), true, self.token)?)?;
+        Ok(resp.moves.into_iter().map(|m| (m.uci, m.white + m.draws + m.black)).collect())
+    }
+
+}
+
+impl MoveReference for Explorer<'_> {
+    fn moves(&self, pos: &Chess) -> Result<Vec<(String, u64)>, HttpError> {
+        self.moves_at(&Fen::from_position(pos, EnPassantMode::Legal).to_string())
+    }
+}
+
+/// Move counts built from a local PGN collection, keyed by position hash.
+pub struct LocalReference {
+    positions: HashMap<u64, HashMap<String, u64>>,
+}
+
+impl LocalReference {
+    pub fn from_pgn(text: &str) -> Self {
+        let mut positions: HashMap<u64, HashMap<String, u64>> = HashMap::new();
+        for game in split_games(text) {
+            for_each_move(&parse_pgn_moves(game), |pos, mv| {
+                let uci = mv.to_uci(CastlingMode::Standard).to_string();
+                *positions.entry(position_key(pos)).or_default().entry(uci).or_default() += 1;
+                true
+            });
+        }
+        Self { positions }
+    }
+
+    pub fn len(&self) -> usize { self.positions.len() }
+
+    pub fn is_empty(&self) -> bool { self.positions.is_empty() }
+}
+
+impl MoveReference for LocalReference {
+    fn moves(&self, pos: &Chess) -> Result<Vec<(String, u64)>, HttpError> {
+        Ok(self.positions.get(&position_key(pos))
+            .map(|m| m.iter().map(|(u, &n)| (u.clone(), n)).collect())
+            .unwrap_or_default())
+    }
+}
+
+fn position_key(pos: &Chess) -> u64 {
+    let h: Zobrist64 = pos.zobrist_hash(EnPassantMode::Legal);
+    h.0
+}
+
+/// Replays SAN `moves`, calling `f` with each position and the move played
+/// from it until `f` returns false or a move fails to parse.
+fn for_each_move(moves: &[&str], mut f: impl FnMut(&Chess, &Move) -> bool) {
+    let mut pos = Chess::default();
+    for m in moves {
+        let Some(mv) = San::from_str(m).ok().and_then(|s| s.to_move(&pos).ok()) else { return };
+        if !f(&pos, &mv) { return; }
+        pos.play_unchecked(mv);
+    }
+}
+
+/// Number of leading plies of `moves` that were each played in at least
+/// `min_games` reference games: the part of the game that was still theory.
+pub fn book_depth(reference: &dyn MoveReference, moves: &[&str], min_games: u64) -> Result<usize, HttpError> {
+    let mut depth = 0;
+    let mut err = None;
+    for_each_move(moves, |pos, mv| {
+        let uci = mv.to_uci(CastlingMode::Standard).to_string();
+        match reference.moves(pos) {
+            Ok(known) if known.iter().any(|(u, n)| *u == uci && *n >= min_games) => { depth += 1; true }
+            Ok(_) => false,
+            Err(e) => { err = Some(e); false }
+        }
+    });
+    err.map_or(Ok(depth), Err)
+}
+
+/// Over the first `plies` plies, how many of `color`'s moves appear in the
+/// reference. Stops once the game leaves the reference entirely.
+pub fn match_rate(reference: &dyn MoveReference, moves: &[&str], color: Color, plies: usize) -> Result<MatchCount, HttpError> {
+    let mut count = MatchCount::default();
+    let mut err = None;
+    let mut ply = 0;
+    for_each_move(moves, |pos, mv| {
+        ply += 1;
+        if ply > plies { return false; }
+        if pos.turn() != color { return true; }
+        match reference.moves(pos) {
+            Ok(known) if known.is_empty() => false,
+            Ok(known) => {
+                let uci = mv.to_uci(CastlingMode::Standard).to_string();
+                count.positions += 1;
+                if known.iter().any(|(u, _)| *u == uci) { count.matched += 1; }
+                true
+            }
+            Err(e) => { err = Some(e); false }
+        }
+    });
+    err.map_or(Ok(count), Err)
+}
@@ -0,0 +1,106 @@
+//! Which games of a run are analyzed, decided from the headers before the
+//! engine starts.
+
+use crate::pgn::GameHeaders;
+use crate::report::Side;
+use std::collections::HashMap;
+
+/// A game's result from the target player's point of view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Outcome {
+    /// From a PGN result tag; `None` for unfinished or unknown results.
+    pub fn of(result: &str, side: Side) -> Option<Self> {
+        Some(match (result, side) {
+            ("1-0", Side::White) | ("0-1", Side::Black) => Self::Win,
+            ("0-1", Side::White) | ("1-0", Side::Black) => Self::Loss,
+            ("1/2-1/2", _) => Self::Draw,
+            _ => return None,
+        })
+    }
+}
+
+/// An ECO code or inclusive range of codes, e.g. "B20-B99".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EcoRange {
+    pub from: String,
+    pub to: String,
+}
+
+impl EcoRange {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (from, to) = s.split_once('-').unwrap_or((s, s));
+        let code = |c: &str| {
+            let c = c.trim().to_ascii_uppercase();
+            let valid = c.len() == 3 && matches!(c.as_bytes()[0], b'A'..=b'E') && c[1..].bytes().all(|b| b.is_ascii_digit());
+            valid.then_some(c).ok_or_else(|| format!("expected an ECO code like B20 or a range like B20-B99, got {:?}", s))
+        };
+        let (from, to) = (code(from)?, code(to)?);
+        if from > to { return Err(format!("empty ECO range {:?}", s)); }
+        Ok(Self { from, to })
+    }
+
+    pub fn contains(&self, eco: &str) -> bool {
+        let eco = eco.to_ascii_uppercase();
+        self.from <= eco && eco <= self.to
+    }
+}
+
+/// Criteria a game must meet; the default keeps every game.
+#[derive(Clone, Debug, Default)]
+pub struct GameFilter {
+    /// Only games with one of these results for the player.
+    pub results: Vec<Outcome>,
+    /// Only games whose opening name contains this, ignoring case.
+    pub opening: Option<String>,
+    /// Only games with an ECO code in one of these ranges.
+    pub eco: Vec<EcoRange>,
+    /// Only games against these players (lowercase usernames).
+    pub opponents: Vec<String>,
+    /// No games against these players (lowercase usernames).
+    pub excluded_opponents: Vec<String>,
+    /// Only games against players with one of these titles ("GM", "IM", ...).
+    pub vs_titles: Vec<String>,
+    /// Titles of chess.com players by lowercase username, for games whose
+    /// PGN has no title tags.
+    pub titled_players: HashMap<String, String>,
+    /// Only games of at least / at most this many full moves.
+    pub min_moves: Option<usize>,
+    pub max_moves: Option<usize>,
+}
+
+impl GameFilter {
+    /// Why the game of `side`, `plies` half-moves long, is left out, if it is.
+    pub fn rejects(&self, headers: &GameHeaders, side: Side, plies: usize) -> Option<&'static str> {
+        let moves = plies.div_ceil(2);
+        if self.min_moves.is_some_and(|m| moves < m) { return Some("too short"); }
+        if self.max_moves.is_some_and(|m| moves > m) { return Some("too long"); }
+        let opponent = match side { Side::White => &headers.black, Side::Black => &headers.white };
+        let opponent = opponent.as_deref().unwrap_or_default().to_lowercase();
+        if !self.opponents.is_empty() && !self.opponents.contains(&opponent) { return Some("opponent filter"); }
+        if self.excluded_opponents.contains(&opponent) { return Some("excluded opponent"); }
+        if !self.vs_titles.is_empty() {
+            let tag = match side { Side::White => &headers.black_title, Side::Black => &headers.white_title };
+            let title = tag.as_ref().or_else(|| self.titled_players.get(&opponent));
+            if !title.is_some_and(|t| self.vs_titles.iter().any(|w| w.eq_ignore_ascii_case(t))) { return Some("untitled opponent"); }
+        }
+        if !self.results.is_empty() {
+            let outcome = headers.result.as_deref().and_then(|r| Outcome::of(r, side));
+            if !outcome.is_some_and(|o| self.results.contains(&o)) { return Some("result filter"); }
+        }
+        if let Some(wanted) = &self.opening {
+            let name = headers.opening_name().unwrap_or_default().to_lowercase();
+            if !name.contains(&wanted.to_lowercase()) { return Some("opening filter"); }
+        }
+        if !self.eco.is_empty() {
+            let eco = headers.eco.as_deref().unwrap_or_default();
+            if !self.eco.iter().any(|r| r.contains(eco)) { return Some("ECO filter"); }
+        }
+        None
+    }
+}
@@ -0,0 +1,131 @@
+//! The ply-by-ply evaluation series of a game, for eval graphs.
+
+use crate::accuracy::{wdl_to_prob, Classification};
+use crate::analysis::MoveRecord;
+use crate::engine::Score;
+use serde::{Deserialize, Serialize};
+
+/// The evaluation after one move, from White's point of view.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EvalPoint {
+    pub ply: usize,
+    pub san: String,
+    /// Centipawns, when the engine did not see a mate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cp: Option<i32>,
+    /// Moves to mate, negative when Black mates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mate: Option<i32>,
+    /// White's expected score from the WDL, 0.0 to 1.0.
+    pub white_expected: f64,
+    /// "inaccuracy", "mistake" or "blunder" for such moves out of book.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One point per scored move.
+pub fn eval_series(records: &[MoveRecord]) -> Vec<EvalPoint> {
+    records.iter().map(|r| {
+        let (cp, mate) = match r.eval.score {
+            Score::Cp(cp) => (Some(cp), None),
+            Score::Mate(n) => (None, Some(n)),
+        };
+        let error = match r.classification {
+            Classification::Inaccuracy | Classification::Mistake | Classification::Blunder if !r.in_book =>
+                Some(r.classification.as_str().to_string()),
+            _ => None,
+        };
+        EvalPoint {
+            ply: r.ply,
+            san: r.san.to_string(),
+            cp,
+            mate,
+            white_expected: wdl_to_prob(r.eval.wdl, true),
+            error,
+        }
+    }).collect()
+}
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 160.0;
+
+/// An SVG eval graph: White's expected score over the game, the area above
+/// the middle line shaded for White and below it for Black, with mistakes
+/// and blunders marked.
+pub fn svg(points: &[EvalPoint]) -> String {
+    use std::fmt::Write;
+
+    let x = |ply: usize| WIDTH * ply as f64 / points.len().max(1) as f64;
+    let y = |p: f64| HEIGHT * (1.0 - p);
+    let mid = HEIGHT / 2.0;
+    let mut path = format!("M0,{:.1}", mid);
+    for p in points { let _ = write!(path, " L{:.1},{:.1}", x(p.ply), y(p.white_expected)); }
+    let _ = write!(path, " L{:.1},{:.1} Z", x(points.len()), mid);
+
+    let mut out = format!(concat!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {w} {h}" width="{w}" height="{h}">"#,
+        r#"<defs><clipPath id="white-adv"><rect width="{w}" height="{m}"/></clipPath>"#,
+        r#"<clipPath id="black-adv"><rect y="{m}" width="{w}" height="{m}"/></clipPath></defs>"#,
+        r##"<rect width="{w}" height="{h}" fill="#ddd"/>"##,
+        r##"<path d="{d}" fill="#fff" clip-path="url(#white-adv)"/>"##,
+        r##"<path d="{d}" fill="#333" clip-path="url(#black-adv)"/>"##,
+        r##"<line x1="0" y1="{m}" x2="{w}" y2="{m}" stroke="#999" stroke-width="0.5"/>"##),
+        w = WIDTH, h = HEIGHT, m = mid, d = path);
+    for p in points {
+        let color = match p.error.as_deref() {
+            Some("blunder") => "#d00",
+            Some("mistake") => "#e80",
+            _ => continue,
+        };
+        let _ = write!(out, r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"><title>{}{} {}</title></circle>"#,
+            x(p.ply), y(p.white_expected), color, p.ply.div_ceil(2), if p.ply % 2 == 1 { "." } else { "..." }, p.san);
+    }
+    out.push_str("</svg>");
+    out
+}
+
+/// Rows of the terminal graph on each side of the middle line.
+const TERM_ROWS: usize = 4;
+
+/// A block-character eval graph at most `width` columns wide: White's
+/// advantage grows up from the middle line, Black's down, in half-row steps.
+/// A `^` under the graph marks columns with a blunder.
+pub fn terminal(points: &[EvalPoint], width: usize) -> String {
+    if points.is_empty() || width == 0 { return String::new(); }
+    let cols = points.len().min(width);
+    let columns: Vec<(f64, bool)> = (0..cols).map(|c| {
+        let chunk = &points[c * points.len() / cols..(c + 1) * points.len() / cols];
+        let mean = chunk.iter().map(|p| p.white_expected).sum::<f64>() / chunk.len() as f64;
+        (2.0 * mean - 1.0, chunk.iter().any(|p| p.error.as_deref() == Some("blunder")))
+    }).collect();
+
+    let steps = 2 * TERM_ROWS;
+    let mut out = String::new();
+    // Rows from the top; row r (0-based from the middle line outwards) is full
+    // once a column needs more than 2r half-steps, half-filled at exactly 2r + 1
+    for row in (0..TERM_ROWS).rev() {
+        out.push_str("W │");
+        for &(v, _) in &columns {
+            let h = if v > 0.0 { (v * steps as f64).round() as usize } else { 0 };
+            out.push(if h > 2 * row + 1 { '█' } else if h == 2 * row + 1 { '▄' } else { ' ' });
+        }
+        out.push('\n');
+    }
+    out.push_str("  ├");
+    out.push_str(&"─".repeat(cols));
+    out.push('\n');
+    for row in 0..TERM_ROWS {
+        out.push_str("B │");
+        for &(v, _) in &columns {
+            let h = if v < 0.0 { (-v * steps as f64).round() as usize } else { 0 };
+            out.push(if h > 2 * row + 1 { '█' } else if h == 2 * row + 1 { '▀' } else { ' ' });
+        }
+        out.push('\n');
+    }
+    if columns.iter().any(|&(_, b)| b) {
+        out.push_str("   ");
+        out.extend(columns.iter().map(|&(_, b)| if b { '^' } else { ' ' }));
+        out.push('\n');
+    }
+    out
+}
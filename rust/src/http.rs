@@ -0,0 +1,331 @@
+//! Blocking HTTP client with an on-disk response cache.
+//!
+//! Cached bodies are stored next to their `ETag`/`Last-Modified` validators so
+//! that stale entries are revalidated with a conditional request instead of
+//! being downloaded again. Requests that do reach the network wait their
+//! turn under per-host [`RateLimits`].
+
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub type HttpError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Returned in offline mode for a URL that has no cached copy.
+#[derive(Debug)]
+pub struct NotCached(pub String);
+
+impl std::fmt::Display for NotCached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not in the cache (offline mode)", self.0)
+    }
+}
+
+impl std::error::Error for NotCached {}
+
+/// Returned when replaying for a URL that was not recorded.
+#[derive(Debug)]
+pub struct NotRecorded(pub String);
+
+impl std::fmt::Display for NotRecorded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} was not recorded (replay mode)", self.0)
+    }
+}
+
+impl std::error::Error for NotRecorded {}
+
+/// Default User-Agent, optionally extended with a contact address as chess.com requests.
+pub fn default_user_agent(email: Option<&str>) -> String {
+    let base = format!("chess-bench/{} (+https://github.com/Bot-Rakshit/chess-bench", env!("CARGO_PKG_VERSION"));
+    match email {
+        Some(e) => format!("{}; {})", base, e),
+        None => format!("{})", base),
+    }
+}
+
+/// Requests per second to the APIs this crate uses, within what chess.com
+/// and Lichess ask of API consumers.
+pub const DEFAULT_RATE_LIMITS: [(&str, f64); 3] = [("api.chess.com", 4.0), ("lichess.org", 2.0), ("explorer.lichess.ovh", 2.0)];
+
+/// Requests per second allowed per host. A limit applies to the host and
+/// its subdomains, the most specific one winning; 0 means unlimited.
+#[derive(Clone, Debug)]
+pub struct RateLimits {
+    hosts: HashMap<String, f64>,
+    /// Limit of the hosts not listed.
+    other: Option<f64>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self { hosts: DEFAULT_RATE_LIMITS.iter().map(|&(h, r)| (h.to_string(), r)).collect(), other: None }
+    }
+}
+
+/// Token bucket of one limit: a listed host with its subdomains, or all the
+/// hosts not listed. Shared by every client and thread of the process so
+/// that concurrent fetches add up to the limit, not exceed it.
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+fn buckets() -> &'static Mutex<HashMap<String, Bucket>> {
+    static BUCKETS: OnceLock<Mutex<HashMap<String, Bucket>>> = OnceLock::new();
+    BUCKETS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+impl RateLimits {
+    /// Sets the limit of `host`, or of every host not listed when `None`.
+    pub fn set(&mut self, host: Option<&str>, rate: f64) {
+        match host {
+            Some(h) => { self.hosts.insert(h.to_ascii_lowercase(), rate); }
+            None => self.other = Some(rate),
+        }
+    }
+
+    /// The limit of `host`, if any.
+    pub fn rate(&self, host: &str) -> Option<f64> { self.rule(host).map(|r| r.1) }
+
+    /// The limit applying to `host` and the bucket it draws from: the
+    /// matched host, or `*` for the limit of the hosts not listed.
+    fn rule(&self, host: &str) -> Option<(&str, f64)> {
+        let host = host.to_ascii_lowercase();
+        self.hosts.iter()
+            .filter(|(h, _)| host == **h || host.ends_with(&format!(".{}", h)))
+            .max_by_key(|(h, _)| h.len())
+            .map_or(self.other.map(|r| ("*", r)), |(h, &r)| Some((h.as_str(), r)))
+            .filter(|r| r.1 > 0.0)
+    }
+
+    /// Waits until a request to `url` is within its limit. Bursts of up to
+    /// one second's worth of requests go through at once.
+    pub fn wait(&self, url: &str) {
+        let Some(host) = reqwest::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_ascii_lowercase)) else { return };
+        let Some((rule, rate)) = self.rule(&host) else { return };
+        let burst = rate.max(1.0);
+        let delay = {
+            let mut buckets = buckets().lock().unwrap();
+            let now = Instant::now();
+            let b = buckets.entry(rule.to_string()).or_insert(Bucket { tokens: burst, updated: now });
+            b.tokens = (b.tokens + now.duration_since(b.updated).as_secs_f64() * rate).min(burst) - 1.0;
+            b.updated = now;
+            // A negative balance is the queue of requests already waiting
+            Duration::from_secs_f64((-b.tokens).max(0.0) / rate)
+        };
+        if !delay.is_zero() {
+            crate::verbose!("  rate limit: waiting {}ms for {}", delay.as_millis(), host);
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+pub struct HttpOptions {
+    pub user_agent: String,
+    pub cache_dir: Option<PathBuf>,
+    /// Proxy for all requests. When unset, `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY`
+    /// from the environment apply.
+    pub proxy: Option<String>,
+    /// Serve only cached responses and never touch the network.
+    pub offline: bool,
+    /// Save every response body to this directory for later replay.
+    pub record: Option<PathBuf>,
+    /// Serve responses only from a directory written by `record`.
+    pub replay: Option<PathBuf>,
+    pub rate_limits: RateLimits,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            user_agent: default_user_agent(None),
+            cache_dir: None,
+            proxy: None,
+            offline: false,
+            record: None,
+            replay: None,
+            rate_limits: RateLimits::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheMeta {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+pub struct HttpClient {
+    client: reqwest::blocking::Client,
+    cache_dir: Option<PathBuf>,
+    offline: bool,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    rate_limits: RateLimits,
+}
+
+impl HttpClient {
+    pub fn new(opts: HttpOptions) -> Result<Self, HttpError> {
+        let mut builder = reqwest::blocking::Client::builder().user_agent(opts.user_agent);
+        if let Some(proxy) = &opts.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("invalid proxy {:?}: {}", proxy, e))?);
+        }
+        let client = builder.build()?;
+        if let Some(dir) = &opts.cache_dir { fs::create_dir_all(dir)?; }
+        if let Some(dir) = &opts.record { fs::create_dir_all(dir)?; }
+        if opts.offline && opts.cache_dir.is_none() { return Err("offline mode needs the cache".into()); }
+        if let Some(dir) = opts.replay.as_ref().filter(|d| !d.is_dir()) {
+            return Err(format!("replay directory {} does not exist", dir.display()).into());
+        }
+        Ok(Self {
+            client,
+            cache_dir: opts.cache_dir,
+            offline: opts.offline,
+            record: opts.record,
+            replay: opts.replay,
+            rate_limits: opts.rate_limits,
+        })
+    }
+
+    /// The underlying client for an uncached request to `url`, once the
+    /// rate limit allows it; unavailable offline and when replaying.
+    pub fn online(&self, url: &str) -> Result<&reqwest::blocking::Client, HttpError> {
+        if self.offline { return Err("this source needs the network (offline mode)".into()); }
+        if self.replay.is_some() { return Err("this request cannot be replayed (replay mode)".into()); }
+        self.rate_limits.wait(url);
+        Ok(&self.client)
+    }
+
+    /// The recorded body of `url` when replaying, for callers that do not go
+    /// through [`get_text`](Self::get_text) (e.g. streamed responses).
+    pub fn replayed(&self, url: &str) -> Option<Result<String, HttpError>> {
+        let dir = self.replay.as_ref()?;
+        Some(fs::read_to_string(cache_paths(dir, url).0).map_err(|_| NotRecorded(url.to_string()).into()))
+    }
+
+    pub fn is_recording(&self) -> bool { self.record.is_some() }
+
+    /// Saves `body` as the recorded response of `url` when recording.
+    pub fn record(&self, url: &str, body: &str) {
+        let Some(dir) = &self.record else { return };
+        if let Err(e) = fs::write(cache_paths(dir, url).0, body) {
+            crate::verbose!("  Failed to record {}: {}", url, e);
+        }
+    }
+
+    /// Fetches `url` as text. With `immutable`, a cached copy is returned
+    /// without contacting the server; otherwise it is revalidated.
+    pub fn get_text(&self, url: &str, immutable: bool) -> Result<String, HttpError> {
+        self.get_text_auth(url, immutable, None)
+    }
+
+    /// Like [`get_text`](Self::get_text), sending `bearer` as an OAuth token.
+    pub fn get_text_auth(&self, url: &str, immutable: bool, bearer: Option<&str>) -> Result<String, HttpError> {
+        let _span = crate::spans::span("http");
+        if let Some(body) = self.replayed(url) { return body; }
+        let body = self.fetch_text(url, immutable, bearer)?;
+        self.record(url, &body);
+        Ok(body)
+    }
+
+    fn fetch_text(&self, url: &str, immutable: bool, bearer: Option<&str>) -> Result<String, HttpError> {
+        let request = |url: &str| {
+            let req = self.client.get(url);
+            match bearer { Some(t) => req.bearer_auth(t), None => req }
+        };
+        let Some(dir) = &self.cache_dir else {
+            if self.offline { return Err(NotCached(url.to_string()).into()); }
+            self.rate_limits.wait(url);
+            return Ok(request(url).send()?.error_for_status()?.text()?);
+        };
+        let (body_path, meta_path) = cache_paths(dir, url);
+        let cached = fs::read_to_string(&body_path).ok();
+        let meta: CacheMeta = fs::read(&meta_path).ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+
+        if let (Some(body), true) = (&cached, immutable || self.offline) {
+            crate::verbose!("  cache hit {}", url);
+            return Ok(body.clone());
+        }
+        if self.offline { return Err(NotCached(url.to_string()).into()); }
+
+        let mut req = request(url);
+        if cached.is_some() {
+            if let Some(etag) = &meta.etag { req = req.header(IF_NONE_MATCH, etag); }
+            if let Some(lm) = &meta.last_modified { req = req.header(IF_MODIFIED_SINCE, lm); }
+        }
+        self.rate_limits.wait(url);
+        let resp = req.send()?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            if let Some(body) = cached {
+                crate::verbose!("  not modified {}", url);
+                return Ok(body);
+            }
+        }
+        let resp = resp.error_for_status()?;
+        let header = |name| resp.headers().get(name).and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok()).map(String::from);
+        let meta = CacheMeta { etag: header(ETAG), last_modified: header(LAST_MODIFIED) };
+        let body = resp.text()?;
+
+        // A failed cache write only costs a refetch next time
+        if let Err(e) = fs::write(&body_path, &body)
+            .and_then(|_| fs::write(&meta_path, serde_json::to_vec(&meta).unwrap_or_default())) {
+            crate::verbose!("  Failed to cache {}: {}", url, e);
+        }
+        Ok(body)
+    }
+}
+
+/// Characters of the URL kept at the start of a cache file name, to tell
+/// the files apart by eye.
+const CACHE_NAME_PREFIX: usize = 64;
+
+/// Body and metadata files of `url`: a readable start of the URL and a hash
+/// of all of it, so that long URLs (explorer FENs) stay within file name
+/// limits and URLs that read alike do not share a file.
+fn cache_paths(dir: &Path, url: &str) -> (PathBuf, PathBuf) {
+    let readable: String = url.trim_start_matches("https://").trim_start_matches("http://")
+        .chars().take(CACHE_NAME_PREFIX).map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect();
+    // FNV-1a, stable across builds unlike the std hasher
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+    let key = format!("{}-{:016x}", readable, hash);
+    (dir.join(format!("{}.body", key)), dir.join(format!("{}.meta.json", key)))
+}
+
+/// Default cache location: `http/` in the cache directory, see [`crate::dirs`].
+pub fn default_cache_dir() -> Option<PathBuf> { crate::dirs::http_cache_dir() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subdomains_and_unlisted_hosts_share_their_rule() {
+        let mut limits = RateLimits::default();
+        limits.set(None, 1.0);
+        assert_eq!(limits.rule("api.chess.com"), Some(("api.chess.com", 4.0)));
+        assert_eq!(limits.rule("explorer.lichess.org"), Some(("lichess.org", 2.0)));
+        assert_eq!(limits.rule("example.com"), Some(("*", 1.0)));
+        assert_eq!(limits.rule("example.org"), Some(("*", 1.0)));
+        limits.set(Some("lichess.org"), 0.0);
+        assert_eq!(limits.rule("lichess.org"), None);
+    }
+
+    #[test]
+    fn cache_names_are_short_and_distinct() {
+        let dir = Path::new("cache");
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".repeat(8);
+        let (body, meta) = cache_paths(dir, &format!("https://explorer.lichess.ovh/masters?fen={}", fen));
+        assert!(meta.file_name().unwrap().len() < 100);
+        assert_eq!(body.file_name().unwrap().to_str().unwrap()[..22], *"explorer_lichess_ovh_m");
+        // Read alike once sanitized
+        assert_ne!(cache_paths(dir, "https://a.b/c?d").0, cache_paths(dir, "https://a.b/c&d").0);
+    }
+}
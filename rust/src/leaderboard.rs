@@ -0,0 +1,87 @@
+//! Several players benchmarked on the same settings, ranked by accuracy.
+
+use crate::report::{csv_field, GameResult, Summary};
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Serialize, Debug)]
+pub struct LeaderboardRow {
+    pub rank: usize,
+    pub username: String,
+    pub games: usize,
+    /// Moves played by the player.
+    pub moves: usize,
+    pub accuracy: f64,
+    /// 95% bootstrap confidence interval of the accuracy, over games.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accuracy_ci: Option<(f64, f64)>,
+    /// Average centipawn loss over the player's non-book moves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acpl: Option<f64>,
+    /// Blunders per 100 moves.
+    pub blunder_rate: f64,
+}
+
+impl LeaderboardRow {
+    /// The row of one player's run; `rank` is set by [`Leaderboard::new`].
+    pub fn new(summary: &Summary, games: &[GameResult]) -> Self {
+        let target = summary.username.to_lowercase();
+        let moves: usize = games.iter().map(|g| g.moves_for(&target)).sum();
+        let blunders: usize = games.iter().map(|g| g.blunders_for(&target)).sum();
+        let losses: Vec<u32> = games.iter().filter_map(|g| g.cp_loss.as_ref()).flat_map(|c| c.player.iter().copied()).collect();
+        Self {
+            rank: 0,
+            username: summary.username.clone(),
+            games: summary.games_analyzed,
+            moves,
+            accuracy: summary.average_accuracy,
+            accuracy_ci: summary.accuracy_ci,
+            acpl: (!losses.is_empty()).then(|| losses.iter().map(|&l| l as f64).sum::<f64>() / losses.len() as f64),
+            blunder_rate: if moves == 0 { 0.0 } else { 100.0 * blunders as f64 / moves as f64 },
+        }
+    }
+}
+
+/// Players ranked by average accuracy, best first.
+#[derive(Serialize, Debug)]
+pub struct Leaderboard {
+    /// The club or event the players were taken from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub rows: Vec<LeaderboardRow>,
+}
+
+impl Leaderboard {
+    pub fn new(mut rows: Vec<LeaderboardRow>) -> Self {
+        rows.sort_by(|a, b| b.accuracy.total_cmp(&a.accuracy).then_with(|| a.username.cmp(&b.username)));
+        for (i, r) in rows.iter_mut().enumerate() { r.rank = i + 1; }
+        Self { name: None, rows }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let width = self.rows.iter().map(|r| r.username.len()).max().unwrap_or(0).clamp(8, 24);
+        match &self.name {
+            Some(name) => writeln!(out, "\nLeaderboard: {}", name)?,
+            None => writeln!(out, "\nLeaderboard")?,
+        }
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:>4}  {:<w$}  {:>5}  {:>8}  {:>13}  {:>6}  {:>9}", "Rank", "Player", "Games", "Accuracy", "95% CI", "ACPL", "Blunders%", w = width)?;
+        for r in &self.rows {
+            let ci = r.accuracy_ci.map_or("-".to_string(), |(lo, hi)| format!("{:.1}-{:.1}", lo, hi));
+            let acpl = r.acpl.map_or("-".to_string(), |a| format!("{:.1}", a));
+            let name: String = r.username.chars().take(width).collect();
+            writeln!(out, "{:>4}  {:<w$}  {:>5}  {:>7.2}%  {:>13}  {:>6}  {:>9.2}", r.rank, name, r.games, r.accuracy, ci, acpl, r.blunder_rate, w = width)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_csv(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "rank,username,games,moves,accuracy,accuracy_ci_low,accuracy_ci_high,acpl,blunder_rate")?;
+        for r in &self.rows {
+            let opt = |v: Option<f64>| v.map_or(String::new(), |v| format!("{:.2}", v));
+            writeln!(out, "{},{},{},{},{:.2},{},{},{},{:.2}", r.rank, csv_field(&r.username), r.games, r.moves, r.accuracy,
+                opt(r.accuracy_ci.map(|c| c.0)), opt(r.accuracy_ci.map(|c| c.1)), opt(r.acpl), r.blunder_rate)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+pub mod accuracy;
+pub mod alerts;
+pub mod alloc;
+pub mod analysis;
+pub mod anonymize;
+pub mod blunders;
+pub mod calibration;
+pub mod chesscom;
+pub mod compare;
+pub mod config;
+pub mod cploss;
+pub mod criticality;
+pub mod date;
+pub mod diff;
+pub mod dirs;
+pub mod distributed;
+pub mod endgame;
+pub mod engine;
+pub mod event;
+pub mod enginecmp;
+pub mod evalstore;
+pub mod exclusions;
+pub mod explorer;
+pub mod filter;
+pub mod graph;
+pub mod http;
+pub mod leaderboard;
+pub mod lichess;
+pub mod log;
+pub mod material;
+pub mod merge;
+pub mod meta;
+pub mod motifs;
+pub mod movenumber;
+pub mod openings;
+pub mod opponents;
+pub mod pgn;
+pub mod pieces;
+pub mod polyglot;
+pub mod positions;
+pub mod postgres;
+pub mod pressure;
+pub mod progress;
+pub mod rating;
+pub mod report;
+pub mod s3;
+pub mod schedule;
+pub mod sessions;
+pub mod sharpness;
+pub mod spans;
+pub mod split;
+pub mod stability;
+pub mod stats;
+pub mod termination;
+pub mod timeofday;
+pub mod tree;
+pub mod validate;
+pub mod variations;
+pub mod web;
@@ -0,0 +1,120 @@
+//! Lichess game export API.
+
+use crate::chesscom::{GameData, PlayerData};
+use crate::engine::Score;
+use crate::http::{HttpClient, HttpError};
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Cursor};
+
+#[derive(Deserialize)]
+struct ExportedGame {
+    id: String,
+    #[serde(default)]
+    players: Players,
+    pgn: Option<String>,
+    /// Server analysis, one entry per ply, when the game was analyzed.
+    analysis: Option<Vec<PlyEval>>,
+    /// Remaining clock after each ply, in centiseconds.
+    clocks: Option<Vec<u32>>,
+    /// Milliseconds since the epoch.
+    #[serde(rename = "lastMoveAt")]
+    last_move_at: Option<i64>,
+    speed: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PlyEval {
+    eval: Option<i32>,
+    mate: Option<i32>,
+}
+
+#[derive(Deserialize, Default)]
+struct Players {
+    #[serde(default)]
+    white: Player,
+    #[serde(default)]
+    black: Player,
+}
+
+#[derive(Deserialize, Default)]
+struct Player { user: Option<User> }
+
+#[derive(Deserialize)]
+struct User { name: String }
+
+impl From<ExportedGame> for GameData {
+    fn from(g: ExportedGame) -> Self {
+        let evals = g.analysis.and_then(|a| a.iter().map(|e| match (e.eval, e.mate) {
+            (_, Some(m)) => Some(Score::Mate(m)),
+            (Some(cp), None) => Some(Score::Cp(cp)),
+            (None, None) => None,
+        }).collect());
+        GameData {
+            url: Some(format!("https://lichess.org/{}", g.id)),
+            pgn: g.pgn,
+            white: Some(PlayerData { username: g.players.white.user.map(|u| u.name) }),
+            black: Some(PlayerData { username: g.players.black.user.map(|u| u.name) }),
+            end_time: g.last_move_at.map(|ms| ms / 1000),
+            time_class: g.speed,
+            evals,
+            clocks: g.clocks.map(|c| c.iter().map(|&cs| cs as f64 / 100.0).collect()),
+        }
+    }
+}
+
+/// Most recent games of `username`, newest first, with server evals and
+/// clocks where available. The NDJSON export is consumed as it streams in.
+/// With a personal API token the export runs at the higher authenticated rate.
+pub fn fetch_user_games(client: &HttpClient, username: &str, max: usize, token: Option<&str>) -> Result<Vec<GameData>, HttpError> {
+    let url = format!("https://lichess.org/api/games/user/{}?max={}&pgnInJson=true&evals=true&clocks=true", username, max);
+    let reader: Box<dyn BufRead> = match client.replayed(&url) {
+        Some(body) => Box::new(Cursor::new(body?)),
+        None => {
+            let mut req = client.online(&url)?.get(&url).header(ACCEPT, "application/x-ndjson");
+            if let Some(t) = token { req = req.header(AUTHORIZATION, format!("Bearer {}", t)); }
+            Box::new(BufReader::new(req.send()?.error_for_status()?))
+        }
+    };
+
+    let mut games = Vec::new();
+    let mut analyzed = 0;
+    let mut recorded = String::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        if client.is_recording() { recorded.push_str(&line); recorded.push('\n'); }
+        let g: GameData = {
+            let _span = crate::spans::span("json");
+            serde_json::from_str::<ExportedGame>(&line)?.into()
+        };
+        if g.evals.is_some() { analyzed += 1; }
+        games.push(g);
+        if games.len().is_multiple_of(100) { crate::progress!("  Fetched {} games from lichess", games.len()); }
+        if games.len() >= max { break; }
+    }
+    client.record(&url, &recorded);
+    crate::progress!("  Fetched {} games from lichess ({} with server analysis)", games.len(), analyzed);
+    Ok(games)
+}
+
+#[derive(Deserialize)]
+struct CreatedStudy { id: String }
+
+/// Creates a private study named `name` with one chapter per PGN and
+/// returns its URL. Needs a token with the `study:write` scope.
+pub fn publish_study(client: &HttpClient, token: &str, name: &str, chapters: &[(String, String)]) -> Result<String, HttpError> {
+    let create = "https://lichess.org/api/study";
+    let study: CreatedStudy = client.online(create)?.post(create)
+        .bearer_auth(token)
+        .form(&[("name", name), ("visibility", "private")])
+        .send()?.error_for_status()?.json()?;
+    for (chapter, pgn) in chapters {
+        let import = format!("https://lichess.org/api/study/{}/import-pgn", study.id);
+        client.online(&import)?.post(&import)
+            .bearer_auth(token)
+            .form(&[("name", chapter.as_str()), ("pgn", pgn.as_str())])
+            .send()?.error_for_status()?;
+    }
+    Ok(format!("https://lichess.org/study/{}", study.id))
+}
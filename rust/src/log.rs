@@ -0,0 +1,38 @@
+//! Process-wide verbosity for the human-readable log on stderr.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Final summary only.
+pub const QUIET: u8 = 0;
+/// Stage and progress lines (default).
+pub const NORMAL: u8 = 1;
+/// Per-game results and skip reasons.
+pub const VERBOSE: u8 = 2;
+/// Every UCI command sent and line received.
+pub const TRACE: u8 = 3;
+
+static LEVEL: AtomicU8 = AtomicU8::new(NORMAL);
+
+pub fn set_level(level: u8) {
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn enabled(level: u8) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level
+}
+
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => { if $crate::log::enabled($crate::log::NORMAL) { eprintln!($($arg)*) } };
+}
+
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => { if $crate::log::enabled($crate::log::VERBOSE) { eprintln!($($arg)*) } };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => { if $crate::log::enabled($crate::log::TRACE) { eprintln!($($arg)*) } };
+}
@@ -1,296 +1,2779 @@
-use clap::Parser;
+use chess_bench::accuracy::{wdl_to_prob, Classification};
+use chess_bench::{log, progress, verbose};
+use chess_bench::alerts::{Alert, AlertMonitor, AlertRules};
+use chess_bench::accuracy::{AccuracyFormula, Aggregation, BucketSplit, Scoring};
+use chess_bench::anonymize::Anonymizer;
+use chess_bench::analysis::{analyze_moves, engine_eval, needs_search, rescore_moves, game_accuracy, scramble_accuracy, score_searched, score_with_evals, AnalysisError, EvalSource, side_accuracy, MoveRecord};
+use chess_bench::alloc::{self, AllocStats, StageAlloc};
+use chess_bench::calibration::forecasts;
+use chess_bench::chesscom::{fetch_club_members, fetch_titled, fetch_tournament, tournament_id, TITLES, fetch_games, fetch_recent_games, month_url, GameData, PlayerData, RecentGames};
+use chess_bench::date::{current_month, fmt_unix, now_unix, snapshot_stamp};
+use chess_bench::schedule::Schedule;
+use chess_bench::lichess;
+use chess_bench::leaderboard::{Leaderboard, LeaderboardRow};
+use chess_bench::openings::Novelty;
+use chess_bench::opponents::OpponentStats;
+use chess_bench::endgame::EndgameEntry;
+use chess_bench::event::EventStandings;
+use chess_bench::diff::RunDiff;
+use chess_bench::dirs;
+use chess_bench::distributed::{game_fens, run_job, Coordinator, Job, JobResult, JobSettings};
+use chess_bench::merge::{Merged, SavedResults};
+use chess_bench::meta::{total_memory_mb, RunMetadata};
+use chess_bench::evalstore::EvalStore;
+use chess_bench::exclusions::Exclusions;
+use chess_bench::engine::{auto_hash_mb, Eval, EngineInfo, EnginePreset, StockfishEngine, stockfish_path, REFERENCE_POSITIONS};
+use chess_bench::enginecmp::{game_positions, EngineComparison, EngineRun, TestPosition};
+use chess_bench::material::MaterialSplit;
+use chess_bench::movenumber::MoveNumberSplit;
+use chess_bench::pieces::PieceSplit;
+use chess_bench::polyglot::BookBuilder;
+use chess_bench::positions::{write_positions, PositionFilter, PositionTally};
+use chess_bench::pressure::PressureSplit;
+use chess_bench::filter::{EcoRange, GameFilter, Outcome};
+use chess_bench::graph::{self, eval_series};
+use chess_bench::explorer::{book_depth, match_rate, Explorer, ExplorerDb, LocalReference, MoveReference};
+use shakmaty::Color;
+use chess_bench::compare::{RunComparison, RunValues};
+use chess_bench::blunders::{find_blunders, max_swing, BlunderCauses, Listings, SortKey};
+use chess_bench::config::{default_config_path, Config};
+use chess_bench::cploss::{side_losses, GameCpLoss};
+use chess_bench::criticality::CriticalitySplit;
+use chess_bench::http::{default_cache_dir, default_user_agent, HttpClient, HttpError, HttpOptions, RateLimits};
+use chess_bench::sessions::SessionStats;
+use chess_bench::sharpness::{weighted_accuracy, SharpAccuracy, SharpnessScheme};
+use chess_bench::spans::{self, StageProfile};
+use chess_bench::tree::TreeBuilder;
+use chess_bench::split::{split_pgn, SplitBy};
+use chess_bench::stability::EvalStability;
+use chess_bench::postgres;
+use chess_bench::s3::{S3Config, S3Location};
+use chess_bench::validate;
+use chess_bench::variations::{score_variation, KnownEvals, SideLines};
+use chess_bench::termination::Termination;
+use chess_bench::timeofday::TimeOfDay;
+use chess_bench::date::parse_utc_offset;
+use chess_bench::rating::rating_series;
+use chess_bench::web::Dashboard;
+use chess_bench::report::{self, Format, GameResult, Resourcefulness, Side, Summary};
+use chess_bench::progress::{Progress, Workers};
+use chess_bench::pgn::{aborted_reason, annotate, end_time, move_clocks, parse_time_control, parse_pgn_moves, parse_variations, GameHeaders, split_games, tag_value, time_class, Variation};
+use clap::{Args as ClapArgs, CommandFactory, Parser, Subcommand, ValueEnum};
 use rayon::prelude::*;
-use serde::Deserialize;
-use shakmaty::{Chess, Position, fen::Fen, san::San, EnPassantMode, Color};
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Command, Stdio, ChildStdin, ChildStdout};
-use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-const STOCKFISH_PATH: &str = "/opt/homebrew/bin/stockfish";
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOC: alloc::CountingAlloc = alloc::CountingAlloc;
 
 #[derive(Parser)]
+#[command(about = "Benchmark a chess player's accuracy: fetch their games, analyze them with Stockfish and report")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    /// JSON config file (default: config.json in the config directory, see `cache stats`)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Print only the final summary
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Log per-game results (-v) and every engine command (-vv)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Print every subcommand and flag as JSON, for wrappers and GUIs, and exit
+    #[arg(long)]
+    dump_cli_json: bool,
+    #[command(subcommand)]
+    command: Option<Cmd>,
+    #[command(flatten)]
+    run: Args,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Print a move-by-move table for a single game
+    AnalyzePgn(AnalyzePgnArgs),
+    /// Keep polling a player's games and analyze each one shortly after it ends
+    Follow(Box<FollowArgs>),
+    /// Run this and the Python implementation on the same games and compare them
+    CompareImpl(CompareImplArgs),
+    /// Analyze the same positions with two engine binaries and compare evals,
+    /// best moves and speed
+    CompareEngines(CompareEnginesArgs),
+    /// Compare two saved JSON results and test whether their accuracy and
+    /// cp loss differ significantly
+    CompareRuns(CompareRunsArgs),
+    /// List the games whose accuracy or blunder count changed between two
+    /// saved runs, with the overall shift
+    Diff(DiffArgs),
+    /// Combine saved results of the same player into one file, keeping each
+    /// game once, from the deepest analysis
+    Merge(MergeArgs),
+    /// Recompute accuracy, classifications and every report from a
+    /// --save-evals file, without the engine
+    Rescore(Box<RescoreArgs>),
+    /// Analyze a few games and report the time spent in each stage of the
+    /// pipeline (HTTP, JSON, PGN, replay, FEN, engine, accuracy) per game
+    Profile(Box<ProfileArgs>),
+    /// Benchmark the members of a chess.com club and rank them on a leaderboard
+    Club(Box<ClubArgs>),
+    /// Analyze every game of a chess.com tournament (Titled Tuesday, arenas)
+    /// and print the standings with each player's accuracy and round results
+    Event(Box<EventArgs>),
+    /// Split a large PGN into one file per player, month or event
+    Split(SplitArgs),
+    /// Write the FEN of every position reached in a PGN, one per line
+    Positions(PositionsArgs),
+    /// Download a player's games and write them as one PGN, oldest first
+    Fetch(FetchArgs),
+    /// Opening books built from a player's games
+    #[command(subcommand)]
+    Book(BookCmd),
+    /// Replay every game of a PGN and report illegal or ambiguous moves,
+    /// missing tags and broken comments by line
+    Validate(ValidateArgs),
+    /// Analyze games handed out by a run started with --serve-jobs on
+    /// another machine
+    Worker(WorkerArgs),
+    /// Show or free the disk used by cached responses and evals
+    #[command(subcommand)]
+    Cache(CacheCmd),
+    /// Checks of the engine binary and options
+    #[command(subcommand)]
+    Engine(EngineCmd),
+    /// Print a shell completion script, e.g. `benchmark completions zsh > _benchmark`
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(ClapArgs)]
+struct RescoreArgs {
+    /// File written by --save-evals
+    #[arg(long, value_name = "FILE")]
+    evals: PathBuf,
+    #[command(flatten)]
+    run: Args,
+}
+
+#[derive(ClapArgs)]
+#[command(mut_arg("games", |a| a.default_value("20")))]
+#[command(mut_arg("workers", |a| a.default_value("1")))]
+struct ProfileArgs {
+    #[command(flatten)]
+    run: Args,
+}
+
+#[derive(ClapArgs)]
+#[command(mut_arg("username", |a| a.help("Club ID, as in the club's URL").value_name("CLUB_ID").default_value(None).required(true)))]
+#[command(mut_arg("games", |a| a.help("Recent games per member").default_value("50")))]
+struct ClubArgs {
+    /// Members benchmarked, the most recently active first (default: all)
+    #[arg(long, value_name = "N")]
+    members: Option<usize>,
+    /// Seconds to wait between members, to go easy on chess.com's API
+    #[arg(long, value_name = "SECS", default_value = "1")]
+    member_pause: u64,
+    #[command(flatten)]
+    run: Args,
+}
+
+/// The club a `club` run takes its players from.
+struct Club {
+    id: String,
+    members: Option<usize>,
+    pause: Duration,
+}
+
+#[derive(ClapArgs)]
 struct Args {
+    /// Player to benchmark; several comma-separated names are each run with
+    /// the same settings and ranked on a leaderboard
     #[arg(default_value = "hikaru")]
     username: String,
+    /// Games per player
     #[arg(default_value = "1000")]
     games: usize,
     #[arg(long, default_value = "4")]
     workers: usize,
+    /// Where to download games from
+    #[arg(long, value_enum, default_value = "chesscom")]
+    source: Source,
+    /// Analyze games from a PGN file ("-" for stdin) instead of chess.com
+    #[arg(long)]
+    pgn: Option<PathBuf>,
+    #[command(flatten)]
+    net: NetArgs,
+    /// Format of the results written to stdout; progress always goes to stderr
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+    /// Replace usernames and game URLs with stable pseudonyms in every
+    /// output; an optional key makes them unguessable
+    #[arg(long, value_name = "KEY", num_args = 0..=1, default_missing_value = "")]
+    anonymize: Option<String>,
+    /// Append each game's result to this file as one JSON line as soon as it
+    /// is analyzed, so an interrupted run keeps its finished games
+    #[arg(long, value_name = "FILE")]
+    results_log: Option<PathBuf>,
+    /// Also upload the JSON results, and the --html report and
+    /// --opening-tree when written, to this bucket and prefix, e.g.
+    /// s3://my-bucket/runs; credentials come from the AWS_* variables
+    #[arg(long, value_name = "S3_URL", value_parser = S3Location::parse, conflicts_with = "schedule")]
+    output: Option<S3Location>,
+    /// Endpoint of an S3-compatible store (MinIO, R2, ...) for --output,
+    /// e.g. http://localhost:9000
+    #[arg(long, value_name = "URL", env = "AWS_ENDPOINT_URL")]
+    s3_endpoint: Option<String>,
+    /// Also store the run, its games and their moves in this PostgreSQL
+    /// database (a postgres:// URL or key=value string), through psql
+    #[arg(long, value_name = "CONN")]
+    postgres: Option<String>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    book: BookArgs,
+    #[command(flatten)]
+    report: ReportArgs,
+    #[command(flatten)]
+    scoring: ScoringArgs,
+    /// Upload the best and worst games, annotated, as a new Lichess study (needs --lichess-token)
+    #[arg(long)]
+    publish_study: bool,
+    /// Games of each kind (best, worst) put in the study
+    #[arg(long, default_value = "3")]
+    study_games: usize,
+    /// Serve a live dashboard of the run at this address, e.g. 127.0.0.1:8080
+    #[arg(long)]
+    web: Option<String>,
+    /// Hand the engine work to `benchmark worker` processes connecting to
+    /// this address (e.g. 0.0.0.0:7878) instead of running engines here;
+    /// --workers sets how many games are in flight
+    #[arg(long, value_name = "ADDR")]
+    serve_jobs: Option<String>,
+    /// Seconds a worker may hold a game before it is handed to another
+    #[arg(long, value_name = "SECS", default_value = "600", value_parser = parse_timeout)]
+    job_lease: Duration,
+    /// Stay running and repeat the run on this cron schedule (UTC), e.g. "0 3 * * *"
+    #[arg(long, value_parser = Schedule::parse, conflicts_with = "web")]
+    schedule: Option<Schedule>,
+    /// Directory for the dated JSON snapshots written by --schedule
+    /// (default: ~/.local/share/chess-bench/history)
+    #[arg(long)]
+    snapshot_dir: Option<PathBuf>,
+    #[command(flatten)]
+    alerts: AlertArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    /// Record timed spans of each stage and write them to this file: a Chrome
+    /// trace if it ends in .json, folded stacks for flamegraph.pl otherwise
+    #[arg(long, value_name = "FILE", conflicts_with = "schedule")]
+    profile: Option<PathBuf>,
+    /// Keep every position's engine eval (score, WDL, best move and line) in
+    /// this file, adding to what it already holds (default:
+    /// ~/.cache/chess-bench/evals.bin)
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "", value_parser = parse_path)]
+    save_evals: Option<PathBuf>,
+    /// Evals file to score from instead of running the engine (set by `rescore`)
+    #[arg(skip)]
+    rescore: Option<PathBuf>,
+    /// Club whose members are benchmarked (set by `club`)
+    #[arg(skip)]
+    club: Option<Club>,
+    /// Moves left out of scoring (set from the config file)
+    #[arg(skip)]
+    exclude: Exclusions,
+    /// Report time per pipeline stage instead of the results (set by `profile`)
+    #[arg(skip)]
+    stage_profile: bool,
+    /// Exit with status 7 when the average accuracy is below this, for
+    /// scripted regression checks
+    #[arg(long, value_name = "ACCURACY", conflicts_with = "schedule")]
+    fail_under: Option<f64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Source {
+    Chesscom,
+    Lichess,
+}
+
+#[derive(ClapArgs)]
+struct NetArgs {
+    /// Directory for cached API responses
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+    /// Always download, ignoring and not writing the cache
+    #[arg(long, conflicts_with = "cache_dir")]
+    no_cache: bool,
+    /// Never touch the network; use only cached archives and local PGNs
+    #[arg(long, conflicts_with = "no_cache")]
+    offline: bool,
+    /// User-Agent sent to chess.com (overrides the config file)
+    #[arg(long)]
+    user_agent: Option<String>,
+    /// Contact email included in the default User-Agent
+    #[arg(long)]
+    email: Option<String>,
+    /// HTTP(S) proxy URL; HTTP_PROXY/HTTPS_PROXY are honored when unset
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Lichess personal API token for faster authenticated exports
+    #[arg(long, env = "LICHESS_TOKEN", hide_env_values = true)]
+    lichess_token: Option<String>,
+    /// Save every HTTP response to this directory for --replay
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer HTTP requests only from a --record directory (e.g. rust/fixtures/replay)
+    #[arg(long)]
+    replay: Option<PathBuf>,
+    /// Requests per second to HOST, or to every other host without one; 0
+    /// lifts a limit. Repeatable, and shared by all requests of the run
+    #[arg(long, value_name = "[HOST=]RATE", value_parser = parse_rate_limit)]
+    rate_limit: Vec<(Option<String>, f64)>,
+}
+
+/// A path, where "" picks the default location (clap's own parser rejects it).
+fn parse_path(s: &str) -> Result<PathBuf, String> { Ok(PathBuf::from(s)) }
+
+/// "api.chess.com=2" or "5".
+fn parse_rate_limit(s: &str) -> Result<(Option<String>, f64), String> {
+    let (host, rate) = match s.rsplit_once('=') {
+        Some((h, r)) => (Some(h.trim().to_string()), r),
+        None => (None, s),
+    };
+    match rate.trim().parse::<f64>() {
+        Ok(r) if r >= 0.0 && r.is_finite() => Ok((host, r)),
+        _ => Err(format!("expected [HOST=]REQUESTS_PER_SECOND, got {:?}", s)),
+    }
+}
+
+#[derive(ClapArgs)]
+struct BookArgs {
+    /// Exclude opening moves found in this explorer database from accuracy
+    #[arg(long, value_enum)]
+    book: Option<ExplorerDb>,
+    /// Games a move needs in the explorer database to count as book
+    #[arg(long, default_value = "100")]
+    book_min_games: u64,
+    /// Report how often the player's opening moves appear in the masters explorer
+    #[arg(long)]
+    master_match: bool,
+    /// Use a local PGN collection instead of the masters explorer for --master-match
+    #[arg(long, requires = "master_match")]
+    master_reference: Option<PathBuf>,
+    /// Plies from the start considered for --master-match
+    #[arg(long, default_value = "30")]
+    master_match_plies: usize,
+    /// Record where each game left this explorer database and report theory depth
+    #[arg(long, value_enum)]
+    novelty: Option<ExplorerDb>,
+    /// Report how often each opening line is played per color, with results and accuracy
+    #[arg(long)]
+    repertoire: bool,
+    /// Plies that make up a line for --repertoire
+    #[arg(long, default_value = "6")]
+    repertoire_plies: usize,
+}
+
+// How moves are scored from the engine's evals.
+#[derive(ClapArgs)]
+struct ScoringArgs {
+    /// Accuracy of a move from its drop in expected score
+    #[arg(long, value_enum, default_value = "linear")]
+    accuracy_formula: AccuracyFormula,
+    /// How each game's move accuracies combine into the game's accuracy
+    #[arg(long, value_enum, default_value = "mean")]
+    aggregation: Aggregation,
+    /// Expected-score drops from which a move is good, an inaccuracy, a
+    /// mistake and a blunder
+    #[arg(long, value_parser = parse_thresholds, value_name = "DROPS", default_value = "0.02,0.05,0.10,0.20")]
+    thresholds: [f64; 4],
+}
+
+fn parse_thresholds(s: &str) -> Result<[f64; 4], String> {
+    let drops: Vec<f64> = s.split(',').map(|d| d.trim().parse().map_err(|_| format!("invalid drop {:?}", d))).collect::<Result<_, _>>()?;
+    let drops: [f64; 4] = drops.try_into().map_err(|_| "expected four comma-separated drops".to_string())?;
+    if drops.windows(2).any(|w| w[0] > w[1]) { return Err("drops must be in increasing order".into()); }
+    Ok(drops)
+}
+
+impl ScoringArgs {
+    fn scoring(&self) -> Scoring {
+        let t = &self.thresholds;
+        Scoring { formula: self.accuracy_formula, aggregation: self.aggregation, excellent_drop: t[0], good_drop: t[1], inaccuracy_drop: t[2], mistake_drop: t[3] }
+    }
+}
+
+// Optional sections of the summary.
+#[derive(ClapArgs)]
+struct ReportArgs {
+    /// Print a table of every analyzed game (date, color, opponent, rating,
+    /// result, accuracy, blunders) after the summary
+    #[arg(long)]
+    table: bool,
+    /// Classify the endgames reached and report accuracy, conversion and hold rates per type
+    #[arg(long)]
+    endgames: bool,
+    /// Split the player's accuracy by being ahead, level or behind in material
+    #[arg(long)]
+    material: bool,
+    /// Split the player's accuracy by the piece moved, and for captures, checks and castling
+    #[arg(long)]
+    by_piece: bool,
+    /// Split the player's accuracy by move number, in buckets of SIZE moves (1-10, 11-20, ...)
+    #[arg(long, value_name = "SIZE", num_args = 0..=1, default_missing_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    by_move_number: Option<u32>,
+    /// Compare the engine's WDL forecasts at these move numbers with the game results
+    #[arg(long, value_delimiter = ',', num_args = 0..=1, default_missing_value = "20,30,40")]
+    calibration: Option<Vec<usize>>,
+    /// Report how often lost positions were saved (swindles) and winning ones converted
+    #[arg(long)]
+    swindles: bool,
+    /// Expected score at or below which a position counts as lost (and at or above one minus it, as winning)
+    #[arg(long, default_value = "0.2", value_parser = parse_swindle_threshold)]
+    swindle_threshold: f64,
+    /// Group games into sessions and report accuracy by game number and after losses
+    #[arg(long)]
+    sessions: bool,
+    /// Minutes between games that start a new session
+    #[arg(long, default_value = "30")]
+    session_gap: u64,
+    /// Report score, accuracy and blunder rate against each frequent opponent
+    #[arg(long)]
+    opponents: bool,
+    /// Games against an opponent before they are listed by --opponents
+    #[arg(long, value_name = "N", default_value = "3")]
+    opponent_min_games: usize,
+    /// Break accuracy and results down by hour of day and weekday
+    #[arg(long)]
+    time_of_day: bool,
+    /// UTC offset for --time-of-day, e.g. "+05:30" or "-08:00"
+    #[arg(long, default_value = "UTC", value_parser = parse_timezone)]
+    timezone: i64,
+    /// Rating over time per time class, alongside the accuracy trend
+    #[arg(long)]
+    ratings: bool,
+    /// List the N worst games and biggest blunders, and show N lines in
+    /// other listed sections such as the repertoire
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+    /// Order of the worst-games and blunder listings
+    #[arg(long, value_enum, default_value = "accuracy", requires = "top")]
+    sort: SortKey,
+    /// Count the player's blunders by likely cause: back-rank mate, hanging
+    /// piece, missed in-between move or lost material
+    #[arg(long)]
+    blunder_causes: bool,
+    /// List the N positions the player reached in the most games, with their
+    /// results and accuracy
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    repeated_positions: Option<usize>,
+    /// Plies from the start before positions count for --repeated-positions
+    #[arg(long, value_name = "PLIES", default_value = "8")]
+    repeated_min_ply: usize,
+    /// Write the player's openings as a JSON move tree: each position with
+    /// the moves played from it, their games, results and the accuracy after them
+    #[arg(long, value_name = "FILE")]
+    opening_tree: Option<PathBuf>,
+    /// Plies of each game taken into --opening-tree
+    #[arg(long, value_name = "PLIES", default_value = "20")]
+    tree_plies: usize,
+    /// Split the player's accuracy by time pressure relative to the time control and increment
+    #[arg(long)]
+    time_pressure: bool,
+    /// Include each game's ply-by-ply eval and win-probability series in the JSON output
+    #[arg(long)]
+    eval_graph: bool,
+    /// Write an HTML report with the summary and an eval graph of every game
+    #[arg(long, value_name = "FILE")]
+    html: Option<PathBuf>,
+    /// Write each game's eval graph as an SVG file into this directory
+    #[arg(long, value_name = "DIR")]
+    svg_dir: Option<PathBuf>,
+    /// Report the distribution of per-move centipawn loss (quantiles, share of
+    /// big losses) and export each game's losses in the JSON output
+    #[arg(long)]
+    cp_loss: bool,
+    /// Also report accuracy with moves weighted by position sharpness
+    #[arg(long, value_enum, value_name = "SCHEME")]
+    sharpness: Option<SharpnessScheme>,
+    /// Extra weight of the sharpest position: moves count 1 + strength × sharpness (0 to 1)
+    #[arg(long, default_value = "2.0")]
+    sharpness_strength: f64,
+    /// Split accuracy and results by how the games ended (mate, resignation, timeout, ...)
+    #[arg(long)]
+    terminations: bool,
+    /// Leave moves made with less than this on the clock ("10s", "1m") out of
+    /// the accuracy average and report them separately
+    #[arg(long, value_name = "TIME", value_parser = parse_clock)]
+    exclude_below_clock: Option<f64>,
+    /// Analyze and score only from this move number on; earlier moves count
+    /// as book and their positions are not searched
+    #[arg(long, value_name = "MOVE", value_parser = clap::value_parser!(u32).range(1..))]
+    from_move: Option<u32>,
+    /// Also replay and score the (...) side lines of annotated PGNs, reported
+    /// apart from the mainline accuracy
+    #[arg(long)]
+    include_variations: bool,
+}
+
+fn parse_swindle_threshold(s: &str) -> Result<f64, String> {
+    let t: f64 = s.parse().map_err(|_| format!("invalid expected score {:?}", s))?;
+    if !(t > 0.0 && t < 1.0) { return Err("expected a score between 0 and 1, exclusive".into()); }
+    Ok(t)
+}
+
+/// Seconds from "10", "10s", "1.5m" or "1m30s".
+fn parse_clock(s: &str) -> Result<f64, String> {
+    let err = || format!("expected a time like 10s or 1m30s, got {:?}", s);
+    let (mins, secs) = match s.split_once('m') {
+        Some((m, rest)) => (m.parse::<f64>().map_err(|_| err())?, rest),
+        None => (0.0, s),
+    };
+    let secs = secs.strip_suffix('s').unwrap_or(secs);
+    let secs = if secs.is_empty() { 0.0 } else { secs.parse::<f64>().map_err(|_| err())? };
+    Ok(mins * 60.0 + secs)
+}
+
+impl ReportArgs {
+    fn wants_eval_graph(&self) -> bool {
+        self.eval_graph || self.html.is_some() || self.svg_dir.is_some()
+    }
+}
+
+fn parse_timezone(s: &str) -> Result<i64, String> {
+    parse_utc_offset(s).ok_or_else(|| format!("expected UTC or an offset like +05:30, got {:?}", s))
+}
+
+// Which games are analyzed; the rest are skipped before the engine starts.
+#[derive(ClapArgs)]
+struct FilterArgs {
+    /// Only games the player won, lost or drew (comma-separated for several)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    result: Vec<Outcome>,
+    /// Only games whose opening name (Opening tag or chess.com ECOUrl) contains this, e.g. "Sicilian"
+    #[arg(long)]
+    opening: Option<String>,
+    /// Only games with an ECO code in these codes or ranges, e.g. "B20-B99,C42"
+    #[arg(long, value_delimiter = ',', value_parser = EcoRange::parse)]
+    eco: Vec<EcoRange>,
+    /// Only games against this player (repeatable)
+    #[arg(long, value_name = "NAME")]
+    opponent: Vec<String>,
+    /// Leave out games against this player, e.g. a bot account (repeatable)
+    #[arg(long, value_name = "NAME")]
+    exclude_opponent: Vec<String>,
+    /// Only games against titled players, optionally only these titles ("GM,IM")
+    #[arg(long, value_name = "TITLES", value_delimiter = ',', num_args = 0..=1, require_equals = true,
+          default_missing_values = TITLES, value_parser = parse_title)]
+    vs_titled: Option<Vec<String>>,
+    /// Only games of at least this many moves, e.g. to drop early disconnects
+    #[arg(long, value_name = "N")]
+    min_moves: Option<usize>,
+    /// Only games of at most this many moves
+    #[arg(long, value_name = "N")]
+    max_moves: Option<usize>,
+}
+
+/// One of chess.com's [`TITLES`], in any case.
+fn parse_title(s: &str) -> Result<String, String> {
+    let title = s.to_uppercase();
+    if TITLES.contains(&title.as_str()) { Ok(title) } else { Err(format!("expected one of {}", TITLES.join(", "))) }
+}
+
+impl FilterArgs {
+    fn filter(&self) -> GameFilter {
+        let lower = |names: &[String]| names.iter().map(|n| n.to_lowercase()).collect();
+        GameFilter {
+            results: self.result.clone(),
+            opening: self.opening.clone(),
+            eco: self.eco.clone(),
+            opponents: lower(&self.opponent),
+            excluded_opponents: lower(&self.exclude_opponent),
+            vs_titles: self.vs_titled.clone().unwrap_or_default(),
+            titled_players: HashMap::new(),
+            min_moves: self.min_moves,
+            max_moves: self.max_moves,
+        }
+    }
+
+    /// The filter, with chess.com's titled-player lists loaded for --vs-titled.
+    /// A list that cannot be fetched is skipped with a warning.
+    fn filter_with_titles(&self, client: &HttpClient) -> GameFilter {
+        let mut filter = self.filter();
+        for title in &filter.vs_titles {
+            match fetch_titled(client, title) {
+                Ok(players) => {
+                    verbose!("  {} chess.com players titled {}", players.len(), title);
+                    filter.titled_players.extend(players.into_iter().map(|p| (p, title.clone())));
+                }
+                Err(e) => eprintln!("Warning: cannot load chess.com {} list: {}", title, e),
+            }
+        }
+        filter
+    }
+}
+
+// Alerts raised by follow and --schedule runs.
+#[derive(ClapArgs)]
+struct AlertArgs {
+    /// Alert when the player's accuracy over the last --alert-window games drops below this
+    #[arg(long)]
+    alert_below: Option<f64>,
+    /// Alert when blunders per 100 moves over the last --alert-window games rise above this
+    #[arg(long)]
+    alert_blunder_rate: Option<f64>,
+    /// Games in the rolling window the alert thresholds are checked over
+    #[arg(long, default_value = "10")]
+    alert_window: usize,
+    /// POST alerts as JSON to this URL (Slack/Discord-compatible "text"/"content" fields)
+    #[arg(long)]
+    alert_webhook: Option<String>,
+    /// Exit with status 3 when an alert fires
+    #[arg(long)]
+    alert_exit: bool,
+}
+
+impl AlertArgs {
+    fn rules(&self) -> AlertRules {
+        AlertRules { below_accuracy: self.alert_below, blunder_rate_above: self.alert_blunder_rate, window: self.alert_window }
+    }
+}
+
+// Exit statuses; clap exits with 2 on usage errors and other failures use 1.
+/// An alert fired with --alert-exit.
+const ALERT_EXIT_CODE: i32 = 3;
+/// Games could not be downloaded.
+const FETCH_EXIT_CODE: i32 = 4;
+/// The engine failed on every game.
+const ENGINE_EXIT_CODE: i32 = 5;
+/// No game was left to analyze, e.g. after the filters.
+const NO_GAMES_EXIT_CODE: i32 = 6;
+/// The average accuracy is below --fail-under.
+const FAIL_UNDER_EXIT_CODE: i32 = 7;
+/// `validate` found problems in the PGN.
+const INVALID_PGN_EXIT_CODE: i32 = 8;
+
+/// An error that ends the process with a specific status.
+#[derive(Debug)]
+struct ExitError {
+    code: i32,
+    message: String,
+}
+
+impl std::fmt::Display for ExitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str(&self.message) }
+}
+
+impl std::error::Error for ExitError {}
+
+/// Status for an error from a run: its own for an [`ExitError`], else 1.
+fn exit_code(e: &RunError) -> i32 {
+    e.downcast_ref::<ExitError>().map_or(1, |e| e.code)
+}
+
+/// Why a finished run should exit non-zero, if it should.
+fn run_failure(args: &Args, summary: &Summary) -> Option<ExitError> {
+    let fail = |code, message: String| Some(ExitError { code, message });
+    if summary.games_analyzed == 0 {
+        if summary.skipped.contains_key("engine error") || summary.skipped.contains_key("engine timeout") {
+            return fail(ENGINE_EXIT_CODE, "the engine failed on every game".into());
+        }
+        let why = if summary.skipped.is_empty() { "no games to analyze" } else { "no games matched the filters" };
+        return fail(NO_GAMES_EXIT_CODE, why.into());
+    }
+    match args.fail_under {
+        Some(min) if summary.average_accuracy < min => fail(FAIL_UNDER_EXIT_CODE,
+            format!("average accuracy {:.2}% is below --fail-under {}%", summary.average_accuracy, min)),
+        _ => None,
+    }
+}
+
+/// Logs an alert, posts it to the webhook and exits when asked to.
+fn raise_alert(alert: &Alert, args: &AlertArgs, client: &HttpClient) {
+    eprintln!("ALERT: {}", alert.message);
+    if let Some(url) = &args.alert_webhook {
+        let mut payload = serde_json::to_value(alert).unwrap_or_default();
+        payload["text"] = alert.message.clone().into();
+        payload["content"] = alert.message.clone().into();
+        let sent = client.online(url)
+            .and_then(|c| Ok(c.post(url).json(&payload).send()?.error_for_status()?));
+        if let Err(e) = sent { eprintln!("Error: failed to post alert: {}", e); }
+    }
+    if args.alert_exit { std::process::exit(ALERT_EXIT_CODE); }
+}
+
+#[derive(ClapArgs, Clone)]
+struct EngineArgs {
     #[arg(long, default_value = "1")]
     threads: usize,
     #[arg(long, default_value = "4")]
     depth: u32,
+    /// Engine hash table size in MB (sized from the machine's memory, the
+    /// workers and threads when not given)
+    #[arg(long, value_name = "MB")]
+    hash: Option<u32>,
+    /// Bundled threads, depth and hash settings; see the README for the
+    /// trade-offs of each
+    #[arg(long, value_enum, conflicts_with_all = ["threads", "depth", "hash"])]
+    preset: Option<EnginePreset>,
+    /// Lines searched per position; 2 or more also records the gap to the
+    /// second-best move and reports accuracy on critical positions
+    #[arg(long, default_value = "1")]
+    multipv: u32,
+    /// Expected-score gap between the best and second-best move from which a
+    /// position counts as critical
+    #[arg(long, default_value = "0.1")]
+    critical_gap: f64,
+    /// Write every UCI command sent and line received, with timestamps, to
+    /// one numbered file per engine instance in this directory
+    #[arg(long, value_name = "DIR")]
+    record_uci: Option<PathBuf>,
+    /// Stop a search that takes longer, killing an engine that does not
+    /// answer; the game is scored up to that position
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    position_timeout: Option<Duration>,
+    /// Stop analyzing a game after this long and score it up to there
+    #[arg(long, value_name = "SECS", value_parser = parse_timeout)]
+    game_timeout: Option<Duration>,
+    /// Do not start and test one engine per worker before the games
+    #[arg(long)]
+    skip_engine_check: bool,
+    /// Engines running at once, which share the memory of the automatic hash.
+    #[arg(skip = 1usize)]
+    engines: usize,
 }
 
-#[derive(Deserialize)]
-struct ArchivesResponse { archives: Vec<String> }
+/// A positive number of seconds, e.g. "30" or "2.5".
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs > 0.0 && secs.is_finite() => Ok(Duration::from_secs_f64(secs)),
+        _ => Err(format!("expected a positive number of seconds, got {:?}", s)),
+    }
+}
 
-#[derive(Deserialize)]
-struct GamesResponse { games: Vec<GameData> }
+impl EngineArgs {
+    fn threads(&self) -> usize { self.preset.map_or(self.threads, |p| p.settings().threads) }
 
-#[derive(Deserialize, Clone)]
-struct GameData {
-    pgn: Option<String>,
-    white: Option<PlayerData>,
-    black: Option<PlayerData>,
-}
+    fn depth(&self) -> u32 { self.preset.map_or(self.depth, |p| p.settings().depth) }
 
-#[derive(Deserialize, Clone)]
-struct PlayerData { username: Option<String> }
+    /// Hash set by --hash or the preset.
+    fn explicit_hash(&self) -> Option<u32> { self.preset.map_or(self.hash, |p| Some(p.settings().hash_mb)) }
 
-struct StockfishEngine {
-    stdin: ChildStdin,
-    reader: BufReader<ChildStdout>,
-    depth: u32,
-    line_buf: String,
-}
-
-impl StockfishEngine {
-    fn new(threads: usize, depth: u32) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let mut child = Command::new(STOCKFISH_PATH)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()?;
-        
-        let stdin = child.stdin.take().unwrap();
-        let stdout = child.stdout.take().unwrap();
-        // Use smaller buffer for lower latency (like Python's bufsize=1)
-        let reader = BufReader::with_capacity(256, stdout);
-        
-        let mut engine = Self { 
-            stdin, 
-            reader, 
-            depth,
-            line_buf: String::with_capacity(512),
+    /// Hash of each engine: the explicit one, else [`auto_hash_mb`] for
+    /// this machine (the engine's default when its memory is unknown).
+    fn hash(&self) -> Option<u32> {
+        self.explicit_hash().or_else(|| total_memory_mb().map(|mb| auto_hash_mb(mb, self.engines, self.threads())))
+    }
+
+    /// These settings for `engines` engines running at once.
+    fn sized_for(&self, engines: usize) -> Self { Self { engines: engines.max(1), ..self.clone() } }
+
+    /// The settings remote workers analyze with. Without an explicit hash
+    /// each worker sizes its own.
+    fn job_settings(&self) -> JobSettings {
+        JobSettings {
+            depth: self.depth(),
+            multipv: self.multipv,
+            hash: self.explicit_hash(),
+            clear_hash: self.preset.is_some_and(|p| p.settings().clear_hash),
+        }
+    }
+
+    fn start(&self) -> Result<StockfishEngine, chess_bench::engine::EngineError> {
+        self.start_at(&stockfish_path())
+    }
+
+    /// Starts the engine binary at `path` with these settings.
+    fn start_at(&self, path: &Path) -> Result<StockfishEngine, chess_bench::engine::EngineError> {
+        static STARTED: AtomicUsize = AtomicUsize::new(0);
+        let transcript = match &self.record_uci {
+            Some(dir) => {
+                std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+                let n = STARTED.fetch_add(1, Ordering::Relaxed) + 1;
+                let binary = path.file_stem().map_or("engine".into(), |s| s.to_string_lossy());
+                Some(dir.join(format!("{:03}-{}.log", n, binary)))
+            }
+            None => None,
         };
-        
-        engine.send("uci")?;
-        engine.wait_for("uciok")?;
-        engine.send(&format!("setoption name Threads value {}", threads))?;
-        engine.send("setoption name UCI_ShowWDL value true")?;
-        engine.send("isready")?;
-        engine.wait_for("readyok")?;
+        let mut engine = StockfishEngine::recorded(path, self.threads(), self.depth(), transcript.as_deref())?;
+        if let Some(mb) = self.hash() { engine.set_option("Hash", mb)?; }
+        if self.multipv > 1 { engine.set_option("MultiPV", self.multipv)?; }
+        engine.set_timeout(self.position_timeout);
+        engine.set_clear_hash(self.preset.is_some_and(|p| p.settings().clear_hash));
         Ok(engine)
     }
 
-    #[inline]
-    fn send(&mut self, cmd: &str) -> Result<(), std::io::Error> {
-        writeln!(self.stdin, "{}", cmd)?;
-        self.stdin.flush()
-    }
-
-    fn wait_for(&mut self, token: &str) -> Result<(i32, i32, i32), Box<dyn std::error::Error + Send + Sync>> {
-        let mut wdl = (333, 334, 333);
-        
-        loop {
-            self.line_buf.clear();
-            self.reader.read_line(&mut self.line_buf)?;
-            
-            // Check for WDL in this line (avoid allocation by working with &str)
-            if let Some(wdl_pos) = self.line_buf.find(" wdl ") {
-                let after_wdl = &self.line_buf[wdl_pos + 5..];
-                let parts: Vec<&str> = after_wdl.split_whitespace().take(3).collect();
-                if parts.len() >= 3 {
-                    wdl = (
-                        parts[0].parse().unwrap_or(333),
-                        parts[1].parse().unwrap_or(334),
-                        parts[2].parse().unwrap_or(333),
-                    );
-                }
-            }
-            
-            if self.line_buf.contains(token) {
-                return Ok(wdl);
-            }
+    /// Starts one engine per worker, in parallel, and has each pass
+    /// [`StockfishEngine::self_check`], so that a misconfigured engine stops
+    /// the run before its first game rather than losing games as it goes.
+    fn check(&self, workers: usize) -> Result<(), RunError> {
+        let start = Instant::now();
+        let checked: Vec<Result<EngineInfo, String>> = (0..workers).into_par_iter().map(|i| {
+            let mut engine = self.start().map_err(|e| format!("engine {} did not start: {}", i + 1, e))?;
+            // A hung engine fails the check rather than the run
+            engine.set_timeout(Some(self.position_timeout.unwrap_or(ENGINE_CHECK_TIMEOUT)));
+            let checked = engine.self_check().map_err(|e| format!("engine {} failed its check: {}", i + 1, e));
+            engine.quit();
+            checked.map(|_| engine.info().clone())
+        }).collect();
+        let infos = checked.into_iter().collect::<Result<Vec<_>, _>>()
+            .map_err(|e| ExitError { code: ENGINE_EXIT_CODE, message: format!("{} ({})", e, stockfish_path().display()) })?;
+        let name = infos.first().map_or("", |i| i.name.as_str());
+        progress!("Checked {} engines ({}) in {:.2}s", infos.len(), name, start.elapsed().as_secs_f64());
+        Ok(())
+    }
+}
+
+/// Time each search of the engine check may take without --position-timeout.
+const ENGINE_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(ClapArgs)]
+struct AnalyzePgnArgs {
+    /// PGN file containing the game ("-" for stdin)
+    #[arg(required_unless_present = "text")]
+    file: Option<PathBuf>,
+    /// PGN text pasted on the command line
+    #[arg(long, conflicts_with = "file")]
+    text: Option<String>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    book: BookArgs,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(ClapArgs)]
+struct FollowArgs {
+    username: String,
+    /// Where to poll for new games
+    #[arg(long, value_enum, default_value = "chesscom")]
+    source: Source,
+    /// Seconds between polls
+    #[arg(long, default_value = "60")]
+    interval: u64,
+    /// Append one JSON result per line to this file instead of stdout
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// Also analyze the games already played when following starts
+    #[arg(long)]
+    backfill: bool,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    book: BookArgs,
+    #[command(flatten)]
+    report: ReportArgs,
+    #[command(flatten)]
+    alerts: AlertArgs,
+    #[command(flatten)]
+    filter: FilterArgs,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(ClapArgs)]
+struct CompareImplArgs {
+    #[arg(default_value = "hikaru")]
+    username: String,
+    #[arg(default_value = "100")]
+    games: usize,
+    #[arg(long, default_value = "4")]
+    workers: usize,
+    /// Use the games of this PGN file instead of the (cached) chess.com archives
+    #[arg(long)]
+    pgn: Option<PathBuf>,
+    /// The Python benchmark script
+    #[arg(long, default_value = "python/benchmark.py")]
+    python_script: PathBuf,
+    /// Python interpreter used to run it
+    #[arg(long, default_value = "python3")]
+    python: String,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(ClapArgs)]
+struct EventArgs {
+    /// Tournament page URL, e.g. https://www.chess.com/tournament/live/late-titled-tuesday-blitz-january-30-2024-4696001
+    url: String,
+    #[arg(long, default_value = "4")]
+    workers: usize,
+    /// Format of the standings written to stdout
+    #[arg(long, value_enum, default_value = "text")]
+    format: Format,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    book: BookArgs,
+    #[command(flatten)]
+    report: ReportArgs,
+    #[command(flatten)]
+    scoring: ScoringArgs,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(ClapArgs)]
+struct FetchArgs {
+    username: String,
+    /// Most recent games downloaded
+    #[arg(default_value = "1000")]
+    games: usize,
+    /// Where to download games from
+    #[arg(long, value_enum, default_value = "chesscom")]
+    source: Source,
+    /// Write the PGN here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    out: Option<PathBuf>,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(Subcommand)]
+enum CacheCmd {
+    /// Print the files and size of each cache and data directory
+    Stats,
+    /// Delete cached responses and evals (everything unless narrowed);
+    /// the config and run history are kept
+    Clear(CacheClearArgs),
+}
+
+#[derive(ClapArgs)]
+struct CacheClearArgs {
+    /// Only the cached HTTP responses
+    #[arg(long)]
+    http: bool,
+    /// Only the default --save-evals file
+    #[arg(long)]
+    evals: bool,
+}
+
+#[derive(Subcommand)]
+enum EngineCmd {
+    /// Search a built-in set of positions with known answers and report any
+    /// the engine gets wrong, before a long run depends on it
+    Check(EngineCheckArgs),
+    /// Search positions from the games several times with the same settings
+    /// and report how much evals and best moves change between searches
+    Stability(Box<EngineStabilityArgs>),
+}
+
+#[derive(ClapArgs)]
+struct EngineCheckArgs {
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(ClapArgs)]
+struct EngineStabilityArgs {
+    #[arg(default_value = "hikaru")]
+    username: String,
+    /// Games the positions are taken from
+    #[arg(default_value = "20")]
+    games: usize,
+    /// Distinct positions analyzed, in game order
+    #[arg(long, default_value = "100")]
+    positions: usize,
+    /// Searches of each position
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u32).range(2..))]
+    repeats: u32,
+    #[arg(long, default_value = "4")]
+    workers: usize,
+    /// Use the games of this PGN file instead of the (cached) chess.com archives
+    #[arg(long)]
+    pgn: Option<PathBuf>,
+    /// Also write the result as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(Subcommand)]
+enum BookCmd {
+    /// Write a Polyglot .bin book of the player's moves, weighted by how
+    /// often they were played and how they scored
+    Build(BookBuildArgs),
+}
+
+#[derive(ClapArgs)]
+struct BookBuildArgs {
+    username: String,
+    /// Most recent games downloaded
+    #[arg(default_value = "1000")]
+    games: usize,
+    /// Where to download games from
+    #[arg(long, value_enum, default_value = "chesscom")]
+    source: Source,
+    /// Read games from a local PGN file instead of downloading them ("-" for stdin)
+    #[arg(long, value_name = "FILE")]
+    pgn: Option<PathBuf>,
+    /// Book file to write
+    #[arg(short, long, value_name = "FILE")]
+    out: PathBuf,
+    /// Plies from the start taken into the book
+    #[arg(long, default_value = "30")]
+    max_ply: usize,
+    /// Games a move needs to be kept
+    #[arg(long, default_value = "1")]
+    min_games: u32,
+    /// Only the games where the player had this color
+    #[arg(long, value_enum)]
+    side: Option<Side>,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+#[derive(ClapArgs)]
+struct WorkerArgs {
+    /// Address of the coordinator, e.g. http://192.168.1.10:7878
+    url: String,
+    /// Seconds between polls while the coordinator has no job waiting
+    #[arg(long, value_name = "SECS", default_value = "2", value_parser = parse_timeout)]
+    poll: Duration,
+    /// Engine options; depth, MultiPV, hash and preset come from the coordinator
+    #[command(flatten)]
+    engine: EngineArgs,
+}
+
+#[derive(ClapArgs)]
+struct PositionsArgs {
+    /// Games to replay ("-" for stdin)
+    #[arg(long)]
+    pgn: PathBuf,
+    /// Write the FENs here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    out: Option<PathBuf>,
+    /// Write each position once, however many games reach it
+    #[arg(long)]
+    unique: bool,
+    /// Only positions with this side to move
+    #[arg(long, value_enum)]
+    side: Option<Side>,
+}
+
+#[derive(ClapArgs)]
+struct SplitArgs {
+    /// PGN file to split ("-" for stdin)
+    file: PathBuf,
+    #[arg(long, value_enum)]
+    by: SplitBy,
+    /// Directory the files are written to
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    out: PathBuf,
+}
+
+#[derive(ClapArgs)]
+struct ValidateArgs {
+    /// PGN file to check ("-" for stdin)
+    file: PathBuf,
+    /// Write a cleaned copy here: well-formed tags with the missing roster
+    /// tags added, and each game's legal moves up to its first bad one
+    #[arg(short, long, value_name = "FILE")]
+    out: Option<PathBuf>,
+    /// Also write the problems found as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
+}
+
+#[derive(ClapArgs)]
+struct CompareRunsArgs {
+    /// Results of the first run (`--format json`)
+    a: PathBuf,
+    /// Results of the second run
+    b: PathBuf,
+    /// Also write the comparison as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
+}
+
+#[derive(ClapArgs)]
+struct DiffArgs {
+    /// Results of the earlier run (`--format json`)
+    old: PathBuf,
+    /// Results of the later run
+    new: PathBuf,
+    /// Accuracy change, in points, from which a game is listed; games whose
+    /// blunder count changed are always listed
+    #[arg(long, default_value = "1.0")]
+    min_change: f64,
+    /// Also write the diff as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
+}
+
+#[derive(ClapArgs)]
+struct MergeArgs {
+    /// Results to combine (`--format json`)
+    #[arg(required = true, num_args = 2..)]
+    inputs: Vec<PathBuf>,
+    /// Write the combined results here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+#[derive(ClapArgs)]
+struct CompareEnginesArgs {
+    #[arg(default_value = "hikaru")]
+    username: String,
+    /// Games the positions are taken from
+    #[arg(default_value = "20")]
+    games: usize,
+    /// Baseline engine binary
+    #[arg(long, value_name = "PATH")]
+    engine_a: PathBuf,
+    /// Engine binary compared with the baseline
+    #[arg(long, value_name = "PATH")]
+    engine_b: PathBuf,
+    /// Distinct positions analyzed, in game order
+    #[arg(long, default_value = "500")]
+    positions: usize,
+    #[arg(long, default_value = "4")]
+    workers: usize,
+    /// Use the games of this PGN file instead of the (cached) chess.com archives
+    #[arg(long)]
+    pgn: Option<PathBuf>,
+    /// Also write the comparison as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    json: Option<PathBuf>,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    net: NetArgs,
+}
+
+impl NetArgs {
+    /// HTTP client options; flags override the config file.
+    fn options(&self, config: &Config) -> HttpOptions {
+        let email = self.email.as_deref().or(config.email.as_deref());
+        let mut rate_limits = RateLimits::default();
+        for (host, rate) in config.rate_limits.iter().map(|(h, &r)| (Some(h.as_str()).filter(|h| *h != "*"), r))
+            .chain(self.rate_limit.iter().map(|(h, r)| (h.as_deref(), *r))) {
+            rate_limits.set(host, rate);
+        }
+        HttpOptions {
+            user_agent: self.user_agent.clone().or(config.user_agent.clone()).unwrap_or_else(|| default_user_agent(email)),
+            cache_dir: if self.no_cache { None } else { self.cache_dir.clone().or_else(default_cache_dir) },
+            proxy: self.proxy.clone().or(config.proxy.clone()),
+            offline: self.offline,
+            record: self.record.clone(),
+            replay: self.replay.clone(),
+            rate_limits,
         }
     }
+}
 
-    #[inline]
-    fn analyze(&mut self, fen: &str) -> Result<(i32, i32, i32), Box<dyn std::error::Error + Send + Sync>> {
-        self.send(&format!("position fen {}", fen))?;
-        self.send(&format!("go depth {}", self.depth))?;
-        self.wait_for("bestmove")
+impl BookArgs {
+    fn explorer<'a>(&self, client: &'a HttpClient, net: &'a NetArgs) -> Option<Explorer<'a>> {
+        self.book.map(|db| Explorer::new(client, db, net.lichess_token.as_deref()))
     }
 
-    fn quit(&mut self) {
-        let _ = self.send("quit");
+    /// Reference for the master-move match rate, when requested.
+    fn master_reference<'a>(&self, client: &'a HttpClient, net: &'a NetArgs) -> std::io::Result<Option<Box<dyn MoveReference + 'a>>> {
+        if !self.master_match { return Ok(None); }
+        Ok(Some(match &self.master_reference {
+            Some(path) => {
+                let reference = LocalReference::from_pgn(&read_input(path)?);
+                progress!("Loaded {} reference positions from {}", reference.len(), path.display());
+                Box::new(reference)
+            }
+            None => Box::new(Explorer::new(client, ExplorerDb::Masters, net.lichess_token.as_deref())),
+        }))
     }
 }
 
-#[inline]
-fn wdl_to_prob(w: i32, d: i32, l: i32, is_white: bool) -> f64 {
-    let (w, l) = if is_white { (w, l) } else { (l, w) };
-    (w as f64 + d as f64 * 0.5) / 1000.0
+/// Shared state for analyzing the games of one run.
+struct RunContext<'a> {
+    target: String,
+    engine: &'a EngineArgs,
+    progress: &'a Progress,
+    workers: &'a Workers,
+    explorer: Option<Explorer<'a>>,
+    master: Option<Box<dyn MoveReference + 'a>>,
+    novelty: Option<Explorer<'a>>,
+    book: &'a BookArgs,
+    report: &'a ReportArgs,
+    annotate: bool,
+    filter: GameFilter,
+    engine_info: OnceLock<EngineInfo>,
+    /// Where engine evals are kept for --save-evals.
+    evals: Option<EvalStore>,
+    /// Evals to score from instead of the engine, for `rescore`.
+    saved: Option<EvalStore>,
+    scoring: Scoring,
+    /// Positions reached per game, for --repeated-positions.
+    positions: Option<PositionTally>,
+    /// The player's openings, for --opening-tree.
+    tree: Option<TreeBuilder>,
+    /// Analyze every game, from White's side, whoever played (for `event`).
+    every_game: bool,
+    /// Remote workers doing the engine work, for --serve-jobs.
+    remote: Option<Arc<Coordinator>>,
+    /// Moves left out of scoring, from the config file.
+    exclusions: Exclusions,
 }
 
-#[inline]
-fn calc_accuracy(before: f64, after: f64) -> f64 {
-    if after >= before { 100.0 } else { (100.0 * (1.0 - (before - after) * 2.0)).max(0.0) }
+impl<'a> RunContext<'a> {
+    #[allow(clippy::too_many_arguments)]
+    fn new(target: &str, client: &'a HttpClient, net: &'a NetArgs, engine: &'a EngineArgs, book: &'a BookArgs,
+           report: &'a ReportArgs, progress: &'a Progress, workers: &'a Workers) -> std::io::Result<Self> {
+        Ok(Self {
+            target: target.to_lowercase(),
+            engine,
+            progress,
+            workers,
+            explorer: book.explorer(client, net),
+            master: book.master_reference(client, net)?,
+            novelty: book.novelty.map(|db| Explorer::new(client, db, net.lichess_token.as_deref())),
+            book,
+            report,
+            annotate: false,
+            filter: GameFilter::default(),
+            engine_info: OnceLock::new(),
+            evals: None,
+            saved: None,
+            scoring: Scoring::default(),
+            positions: report.repeated_positions.map(|_| PositionTally::new(report.repeated_min_ply)),
+            tree: report.opening_tree.as_ref().map(|_| TreeBuilder::new(report.tree_plies)),
+            every_game: false,
+            remote: None,
+            exclusions: Exclusions::default(),
+        })
+    }
 }
 
-fn parse_pgn_moves(pgn: &str) -> Vec<&str> {
-    let mut moves = Vec::with_capacity(100);
-    let mut in_moves = false;
-    
-    for line in pgn.lines() {
-        let line = line.trim();
-        if line.starts_with('[') { continue; }
-        if !line.is_empty() { in_moves = true; }
-        if in_moves {
-            let mut i = 0;
-            let bytes = line.as_bytes();
-            while i < bytes.len() {
-                // Skip comments {...}
-                if bytes[i] == b'{' {
-                    while i < bytes.len() && bytes[i] != b'}' { i += 1; }
-                    i += 1;
-                    continue;
-                }
-                // Skip whitespace
-                if bytes[i].is_ascii_whitespace() { i += 1; continue; }
-                // Find token end
-                let start = i;
-                while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'{' { i += 1; }
-                let token = &line[start..i];
-                // Skip move numbers and results
-                if !token.contains('.') && token != "1-0" && token != "0-1" && token != "1/2-1/2" && token != "*" {
-                    moves.push(token);
-                }
+/// Marks the leading theory moves of a game as book.
+fn mark_book(records: &mut [MoveRecord], moves: &[&str], explorer: &Explorer, min_games: u64) -> Result<(), HttpError> {
+    let depth = book_depth(explorer, moves, min_games)?;
+    for r in records.iter_mut().take(depth) { r.in_book = true; }
+    Ok(())
+}
+
+/// Engine attempts per game.
+const ENGINE_ATTEMPTS: usize = 2;
+
+/// Analyzes `moves` with a fresh engine, restarting a crashed one for up to
+/// [`ENGINE_ATTEMPTS`] attempts in all. The first engine started fills
+/// `ctx.engine_info`.
+fn analyze_with_restarts<'a>(moves: &[&'a str], excluded: &[bool], ctx: &RunContext) -> Result<Vec<MoveRecord<'a>>, AnalysisError> {
+    if let Some(coordinator) = &ctx.remote { return analyze_remote(moves, excluded, ctx, coordinator); }
+    let mut attempt = 0;
+    // Restarts count against the same game deadline
+    let deadline = ctx.engine.game_timeout.map(|t| Instant::now() + t);
+    loop {
+        attempt += 1;
+        let started = {
+            let _span = spans::span("engine_start");
+            ctx.engine.start()
+        };
+        let result = started
+            .map_err(AnalysisError::Engine)
+            .and_then(|mut engine| {
+                ctx.engine_info.get_or_init(|| engine.info().clone());
+                engine.set_deadline(deadline);
+                let r = analyze_moves(&mut engine, moves, excluded, ctx.evals.as_ref(), &ctx.scoring);
+                engine.quit();
+                r
+            });
+        match result {
+            Err(AnalysisError::Engine(e)) if attempt < ENGINE_ATTEMPTS => {
+                verbose!("  Restarting engine: {}", e);
+                ctx.workers.record(|w| w.engine_restarts += 1);
             }
+            r => return r,
         }
     }
-    moves
 }
 
-fn fetch_archives(username: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::builder().user_agent("ChessBenchmark/1.0").build()?;
-    let resp: ArchivesResponse = client.get(format!("https://api.chess.com/pub/player/{}/games/archives", username)).send()?.json()?;
-    Ok(resp.archives)
+/// Has a remote worker analyze the positions of `moves` and scores the
+/// moves from its evals. Like a local engine, the worker only gets the
+/// positions that `excluded` leaves to search. A game the worker failed on
+/// is queued once more.
+fn analyze_remote<'a>(moves: &[&'a str], excluded: &[bool], ctx: &RunContext, coordinator: &Coordinator) -> Result<Vec<MoveRecord<'a>>, AnalysisError> {
+    let fens: Vec<String> = game_fens(moves).into_iter().enumerate()
+        .filter(|(ply, _)| needs_search(excluded, *ply)).map(|(_, fen)| fen).collect();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = coordinator.analyze(fens.clone());
+        if let Some(info) = &result.engine { ctx.engine_info.get_or_init(|| info.clone()); }
+        match &result.error {
+            Some(e) if !result.timed_out && attempt < ENGINE_ATTEMPTS => {
+                verbose!("  Worker failed, queuing the game again: {}", e);
+                ctx.workers.record(|w| w.engine_restarts += 1);
+                continue;
+            }
+            Some(e) if !result.timed_out => return Err(AnalysisError::Engine(e.clone().into())),
+            _ => {}
+        }
+        let depth = ctx.engine.depth();
+        let mut evals = result.evals.iter();
+        return score_searched(moves, excluded, &ctx.scoring, |pos, ply| {
+            let eval = evals.next().ok_or(AnalysisError::Timeout(ply))?.eval();
+            if let Some(store) = &ctx.evals { store.record(pos, depth, &eval); }
+            Ok(eval)
+        });
+    }
 }
 
-fn fetch_games(url: &str) -> Result<Vec<GameData>, Box<dyn std::error::Error>> {
-    let client = reqwest::blocking::Client::builder().user_agent("ChessBenchmark/1.0").build()?;
-    let resp: GamesResponse = client.get(url).send()?.json()?;
-    Ok(resp.games)
+/// Scores the side lines of a game with the run's engine, its saved evals or
+/// its remote workers, searching only positions the mainline and earlier
+/// lines did not reach. A line that cannot be scored is left out; an engine
+/// failure ends them all.
+fn analyze_variations<'a>(variations: &[Variation<'a>], moves: &[&'a str], records: &[MoveRecord<'a>], ctx: &RunContext)
+                          -> Result<Vec<Vec<MoveRecord<'a>>>, AnalysisError> {
+    let mut known = KnownEvals::from_line(moves, records);
+    let mut engine = match (&ctx.saved, &ctx.remote) {
+        (None, None) => Some(ctx.engine.start().map_err(AnalysisError::Engine)?),
+        _ => None,
+    };
+    let depth = ctx.engine.depth();
+    let mut lines = Vec::with_capacity(variations.len());
+    let mut failed = None;
+    for v in variations {
+        // A remote job holds the whole line, asked for at its first new position
+        let mut job: Option<JobResult> = None;
+        let scored = score_variation(v, &mut known, &ctx.scoring, |pos, ply| {
+            if let Some(store) = &ctx.saved { return store.get(pos).map(|s| s.eval).ok_or(AnalysisError::MissingEval(ply)); }
+            if let Some(engine) = engine.as_mut() { return engine_eval(engine, pos, ply, ctx.evals.as_ref()); }
+            let coordinator = ctx.remote.as_ref().expect("no engine without saved evals or workers");
+            let result = job.get_or_insert_with(|| coordinator.analyze(game_fens(&v.moves)));
+            match (result.evals.get(ply), &result.error) {
+                (Some(e), _) => {
+                    let eval = e.eval();
+                    if let Some(store) = &ctx.evals { store.record(pos, depth, &eval); }
+                    Ok(eval)
+                }
+                (None, Some(e)) if !result.timed_out => Err(AnalysisError::Engine(e.clone().into())),
+                (None, _) => Err(AnalysisError::Timeout(ply)),
+            }
+        });
+        match scored {
+            Ok(r) if !r.is_empty() => lines.push(r),
+            Ok(_) => {}
+            Err(e @ AnalysisError::Engine(_)) => {
+                failed = Some(e);
+                break;
+            }
+            Err(e) => verbose!("  Skipped a side line from ply {}: {}", v.branch + 1, e),
+        }
+    }
+    if let Some(mut engine) = engine { engine.quit(); }
+    failed.map_or(Ok(lines), Err)
 }
 
-fn analyze_game(game: &GameData, username: &str, sf_threads: usize, depth: u32) -> Option<(f64, f64, usize, String, String)> {
+/// Analyzes one game; `index` is its position in the run's input, from 1.
+fn analyze_game(game: &GameData, index: Option<usize>, ctx: &RunContext) -> Option<GameResult> {
     let pgn = game.pgn.as_ref()?;
     let white = game.white.as_ref()?.username.as_ref()?.to_lowercase();
     let black = game.black.as_ref()?.username.as_ref()?.to_lowercase();
-    let target = username.to_lowercase();
-    if white != target && black != target { return None; }
-
-    let moves = parse_pgn_moves(pgn);
-    if moves.is_empty() { return None; }
-
-    let mut engine = StockfishEngine::new(sf_threads, depth).ok()?;
-    let mut pos = Chess::default();
-    
-    // Pre-allocate accuracy vectors
-    let mut white_acc = Vec::with_capacity(moves.len() / 2 + 1);
-    let mut black_acc = Vec::with_capacity(moves.len() / 2 + 1);
-    
-    // Reuse FEN buffer
-    let mut fen_buf = Fen::from_position(&pos, EnPassantMode::Legal).to_string();
-    let (mut pw, mut pd, mut pl) = engine.analyze(&fen_buf).ok()?;
-
-    for m in moves {
-        let is_white = pos.turn() == Color::White;
-        let san = San::from_str(m).ok()?;
-        let mv = san.to_move(&pos).ok()?;
-        pos = pos.play(mv).ok()?;
-        
-        // Generate FEN
-        fen_buf = Fen::from_position(&pos, EnPassantMode::Legal).to_string();
-        let (cw, cd, cl) = engine.analyze(&fen_buf).ok()?;
-        
-        let acc = calc_accuracy(wdl_to_prob(pw, pd, pl, is_white), wdl_to_prob(cw, cd, cl, is_white));
-        if is_white { white_acc.push(acc); } else { black_acc.push(acc); }
-        pw = cw; pd = cd; pl = cl;
+    if !ctx.every_game && white != ctx.target && black != ctx.target {
+        verbose!("  Skipped {} vs {}: {} did not play", white, black, ctx.target);
+        ctx.progress.skip("not played");
+        return None;
     }
-    engine.quit();
 
-    let wa = if white_acc.is_empty() { 0.0 } else { white_acc.iter().sum::<f64>() / white_acc.len() as f64 };
-    let ba = if black_acc.is_empty() { 0.0 } else { black_acc.iter().sum::<f64>() / black_acc.len() as f64 };
-    Some((wa, ba, white_acc.len() + black_acc.len(), white, black))
+    let parse_start = Instant::now();
+    let parse_alloc = alloc::thread_snapshot();
+    let (headers, moves) = {
+        let _span = spans::span("parse");
+        let headers = GameHeaders::parse(pgn);
+        let _tokenize = spans::span("tokenize");
+        (headers, parse_pgn_moves(pgn))
+    };
+    ctx.progress.add_parse(parse_start.elapsed());
+    ctx.progress.add_parse_alloc(alloc::thread_snapshot().since(parse_alloc));
+    let as_white = ctx.every_game || white == ctx.target;
+    let side = if as_white { Side::White } else { Side::Black };
+    if let Some(reason) = aborted_reason(&headers, moves.len()).or_else(|| ctx.filter.rejects(&headers, side, moves.len())) {
+        verbose!("  Skipped {} vs {}: {}", white, black, reason);
+        ctx.progress.skip(reason);
+        return None;
+    }
+
+    let mut excluded = ctx.exclusions.mark(&moves);
+    let before = ctx.report.from_move.map_or(0, |m| 2 * (m as usize - 1));
+    if before >= moves.len() {
+        verbose!("  Skipped {} vs {}: over before move {}", white, black, before / 2 + 1);
+        ctx.progress.skip("before --from-move");
+        return None;
+    }
+    if before > 0 {
+        excluded.resize(moves.len(), false);
+        excluded[..before].fill(true);
+    }
+    let start = Instant::now();
+    // Games that already carry evals (e.g. Lichess server analysis) skip the engine
+    let (scored, eval_source) = match game.evals.as_deref().map(|e| score_with_evals(&moves, e, &ctx.scoring)) {
+        Some(Ok(r)) => (Ok(r), EvalSource::Server),
+        server => {
+            if let Some(Err(e)) = server { verbose!("  Server analysis of {} vs {} not usable ({}); analyzing it", white, black, e); }
+            match &ctx.saved {
+                Some(store) => (rescore_moves(&moves, store, &ctx.scoring), EvalSource::Saved),
+                None => (analyze_with_restarts(&moves, &excluded, ctx), EvalSource::Engine),
+            }
+        }
+    };
+    let mut records = match scored {
+        Ok(r) => r,
+        Err(e) => {
+            verbose!("  Skipped {} vs {}: {}", white, black, e);
+            ctx.progress.skip(match e {
+                AnalysisError::Engine(_) => "engine error",
+                AnalysisError::Pgn(_) => "invalid moves",
+                AnalysisError::MissingEval(_) => "missing evals",
+                AnalysisError::Timeout(_) => "engine timeout",
+            });
+            ctx.workers.record(|w| w.busy_s += start.elapsed().as_secs_f64());
+            return None;
+        }
+    };
+    if let Some(clocks) = game.clocks.clone().or_else(|| move_clocks(pgn)) {
+        for (r, c) in records.iter_mut().zip(clocks) { r.clock = Some(c); }
+    }
+    for (r, _) in records.iter_mut().zip(&excluded).filter(|(_, &e)| e) { r.in_book = true; }
+    if let Some(limit) = ctx.report.exclude_below_clock {
+        for r in records.iter_mut() { r.scramble = r.clock.is_some_and(|c| c < limit); }
+    }
+    if let Some(explorer) = &ctx.explorer {
+        if let Err(e) = mark_book(&mut records, &moves, explorer, ctx.book.book_min_games) {
+            verbose!("  Book lookup failed for {} vs {}: {}", white, black, e);
+        }
+    }
+    let color = if as_white { Color::White } else { Color::Black };
+    let master_match = ctx.master.as_deref().and_then(|m| match_rate(m, &moves, color, ctx.book.master_match_plies)
+        .map_err(|e| verbose!("  Master lookup failed for {} vs {}: {}", white, black, e))
+        .ok());
+    let theory_depth = ctx.novelty.as_ref().and_then(|n| book_depth(n, &moves, 1)
+        .map_err(|e| verbose!("  Novelty lookup failed for {} vs {}: {}", white, black, e))
+        .ok());
+    ctx.workers.record(|w| {
+        w.games += 1;
+        w.positions += records.len() + 1;
+        w.busy_s += start.elapsed().as_secs_f64();
+    });
+    let score = headers.result.as_deref().and_then(|r| match r { "1-0" => Some(1.0), "0-1" => Some(0.0), "1/2-1/2" => Some(0.5), _ => None })
+        .map(|s| if as_white { s } else { 1.0 - s });
+    if let Some(tally) = &ctx.positions {
+        tally.add_game(index.unwrap_or(usize::MAX), &moves, score, game_accuracy(&records, as_white, &ctx.scoring));
+    }
+    if let Some(tree) = &ctx.tree {
+        tree.add_game(index.unwrap_or(usize::MAX), &records, as_white, score);
+    }
+    verbose!("  {} vs {}: {:.1}% / {:.1}% over {} moves",
+        white, black, game_accuracy(&records, true, &ctx.scoring), game_accuracy(&records, false, &ctx.scoring), records.len());
+    let variations = if ctx.report.include_variations {
+        let lines = parse_variations(pgn);
+        if lines.is_empty() { None } else {
+            match analyze_variations(&lines, &moves, &records, ctx) {
+                Ok(scored) => SideLines::from_lines(&scored, color == Color::White),
+                Err(e) => {
+                    eprintln!("Warning: {} vs {}: side lines not scored: {}", white, black, e);
+                    None
+                }
+            }
+        }
+    } else { None };
+    // Only a timed-out search leaves moves unscored
+    let timed_out_at = (records.len() < moves.len()).then_some(records.len() + 1);
+    if let Some(ply) = timed_out_at { eprintln!("Warning: {} vs {}: search timed out at ply {}; scored the {} moves before it", white, black, ply, records.len()); }
+
+    Some(GameResult {
+        index,
+        url: game.url.clone(),
+        white,
+        black,
+        white_accuracy: game_accuracy(&records, true, &ctx.scoring),
+        black_accuracy: game_accuracy(&records, false, &ctx.scoring),
+        moves: records.len(),
+        eval_source,
+        master_match,
+        opening: headers.eco.clone().or_else(|| headers.opening.clone()),
+        theory_checked: theory_depth.is_some(),
+        novelty: theory_depth.and_then(|d| Novelty::from_depth(d, moves.len())),
+        result: headers.result.clone(),
+        line: ctx.book.repertoire.then(|| moves[..moves.len().min(ctx.book.repertoire_plies)].join(" ")),
+        endgame: ctx.report.endgames.then(|| EndgameEntry::from_records(&records, color == Color::White)).flatten(),
+        material: ctx.report.material.then(|| MaterialSplit::from_records(&records, color == Color::White, ())).flatten(),
+        pieces: ctx.report.by_piece.then(|| PieceSplit::from_records(&records, color == Color::White, ())).flatten(),
+        move_numbers: ctx.report.by_move_number.and_then(|size| MoveNumberSplit::from_records(&records, color == Color::White, size as usize)),
+        white_elo: headers.white_elo,
+        black_elo: headers.black_elo,
+        expected_range: ctx.report.swindles.then(|| expected_range(&records, color == Color::White)).flatten(),
+        end_time: game.end_time.or_else(|| end_time(pgn)),
+        time_class: game.time_class.clone()
+            .or_else(|| headers.time_control.as_deref().and_then(time_class).map(String::from)),
+        annotated_pgn: ctx.annotate.then(|| annotate(pgn, &records)),
+        white_blunders: blunders(&records, true),
+        black_blunders: blunders(&records, false),
+        forecasts: ctx.report.calibration.as_deref().map_or_else(Vec::new, |m| forecasts(&records, m)),
+        sharp_accuracy: ctx.report.sharpness
+            .and_then(|s| weighted_accuracy(&records, color == Color::White, s, ctx.report.sharpness_strength)),
+        cp_loss: ctx.report.cp_loss.then(|| GameCpLoss {
+            player: side_losses(&records, color == Color::White),
+            opponent: side_losses(&records, color != Color::White),
+        }),
+        criticality: CriticalitySplit::from_records(&records, color == Color::White, ctx.engine.critical_gap),
+        pressure: ctx.report.time_pressure.then(|| headers.time_control.as_deref().and_then(parse_time_control)
+            .and_then(|tc| PressureSplit::from_records(&records, color == Color::White, tc))).flatten(),
+        scramble: ctx.report.exclude_below_clock.map(|_| scramble_accuracy(&records, color == Color::White)),
+        termination: ctx.report.terminations.then(|| headers.termination.as_deref()
+            .map(|t| Termination::classify(t, headers.result.as_deref(), moves.last().copied()))).flatten(),
+        eval_graph: if ctx.report.wants_eval_graph() { eval_series(&records) } else { Vec::new() },
+        blunder_moves: if ctx.report.top.is_some() || ctx.report.blunder_causes { find_blunders(&records, color == Color::White) } else { Vec::new() },
+        max_swing: ctx.report.top.map(|_| max_swing(&records, color == Color::White)),
+        timed_out_at,
+        variations,
+        headers,
+    })
 }
 
-fn main() {
-    let args = Args::parse();
-    
-    println!("Rust Chess Benchmark");
-    println!("{}", "=".repeat(50));
-    println!("Username: {}", args.username);
-    println!("Max games: {}", args.games);
-    println!("Workers: {}", args.workers);
-    println!("SF threads/worker: {}", args.threads);
-    println!("Total CPU: {}", args.workers * args.threads);
-    println!("Depth: {}", args.depth);
+/// Non-book blunders of one side.
+fn blunders(records: &[MoveRecord], is_white: bool) -> usize {
+    records.iter().filter(|r| r.is_white == is_white && !r.in_book && r.classification == Classification::Blunder).count()
+}
+
+/// Lowest and highest expected score of one side over the game.
+fn expected_range(records: &[MoveRecord], is_white: bool) -> Option<(f64, f64)> {
+    records.iter().map(|r| wdl_to_prob(r.eval.wdl, is_white))
+        .fold(None, |acc, p| Some(acc.map_or((p, p), |(lo, hi): (f64, f64)| (lo.min(p), hi.max(p)))))
+}
+
+/// Uploads the player's best and worst games as chapters of a Lichess study.
+fn publish_study(client: &HttpClient, args: &Args, games: &[GameResult]) {
+    let Some(token) = args.net.lichess_token.as_deref() else {
+        eprintln!("Error: --publish-study needs --lichess-token");
+        return;
+    };
+    let target = args.username.to_lowercase();
+    let mut ranked: Vec<&GameResult> = games.iter().filter(|g| g.annotated_pgn.is_some()).collect();
+    ranked.sort_by(|a, b| b.accuracy_for(&target).total_cmp(&a.accuracy_for(&target)));
+    let best = args.study_games.min(ranked.len());
+    let worst = args.study_games.min(ranked.len() - best);
+    let picked = ranked[..best].iter().map(|g| ("Best", g)).chain(ranked[ranked.len() - worst..].iter().map(|g| ("Worst", g)));
+    let chapters: Vec<(String, String)> = picked.map(|(kind, g)| {
+        let name = format!("{}: {} vs {} ({:.1}%)", kind, g.white, g.black, g.accuracy_for(&target));
+        (name, g.annotated_pgn.clone().unwrap_or_default())
+    }).collect();
+    if chapters.is_empty() {
+        eprintln!("Error: not enough games to publish a study");
+        return;
+    }
+    let name = format!("chess-bench: {} ({} games)", args.username, games.len());
+    match lichess::publish_study(client, token, &name, &chapters) {
+        Ok(url) => progress!("Published study {}", url),
+        Err(e) => eprintln!("Error: failed to publish study: {}", e),
+    }
+}
+
+/// Reads a whole input file, treating "-" as stdin.
+fn read_input(path: &Path) -> std::io::Result<String> {
+    if path == Path::new("-") {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf)?;
+        Ok(buf)
+    } else {
+        std::fs::read_to_string(path)
+    }
+}
+
+/// Turns a local PGN collection into the same records the chess.com API returns.
+fn games_from_pgn(text: &str) -> Vec<GameData> {
+    split_games(text).into_iter().map(|g| GameData {
+        url: tag_value(g, "Link").or_else(|| tag_value(g, "Site")).map(String::from),
+        pgn: Some(g.to_string()),
+        white: Some(PlayerData { username: tag_value(g, "White").map(String::from) }),
+        black: Some(PlayerData { username: tag_value(g, "Black").map(String::from) }),
+        end_time: end_time(g),
+        ..Default::default()
+    }).collect()
+}
+
+fn print_move_table(records: &[MoveRecord]) {
+    println!("{:<12} {:>7} {:>7} {:>8}  Class", "Move", "Eval", "Win%", "Acc");
+    println!("{}", "-".repeat(50));
+    for r in records {
+        let num = r.ply.div_ceil(2);
+        let mv = if r.is_white { format!("{}. {}", num, r.san) } else { format!("{}... {}", num, r.san) };
+        let class = if r.in_book { "book" } else { r.classification.as_str() };
+        println!("{:<12} {:>7} {:>6.1}% {:>7.1}%  {}",
+            mv, r.eval.score.to_string(), r.prob_after * 100.0, r.accuracy, class);
+    }
+}
+
+/// Columns of the eval graph printed by analyze-pgn.
+const TERMINAL_GRAPH_WIDTH: usize = 64;
+
+fn analyze_pgn(args: &AnalyzePgnArgs, config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pgn = match (&args.text, &args.file) {
+        (Some(text), _) => text.clone(),
+        (None, Some(path)) => read_input(path)?,
+        (None, None) => unreachable!("clap requires a file or --text"),
+    };
+    let game = split_games(&pgn).into_iter().next().ok_or("empty PGN")?;
+    let moves = parse_pgn_moves(game);
+    if moves.is_empty() { return Err("no moves found in PGN".into()); }
+
+    let mut engine = args.engine.start()?;
+    let mut records = analyze_moves(&mut engine, &moves, &[], None, &Scoring::default())?;
+    engine.quit();
+    let client = HttpClient::new(args.net.options(config))?;
+    if let Some(explorer) = args.book.explorer(&client, &args.net) {
+        mark_book(&mut records, &moves, &explorer, args.book.book_min_games)?;
+    }
+
+    print_move_table(&records);
+    println!();
+    print!("{}", graph::terminal(&eval_series(&records), TERMINAL_GRAPH_WIDTH));
     println!();
+    for (name, is_white) in [("White", true), ("Black", false)] {
+        let counts: Vec<String> = Classification::ALL.iter()
+            .map(|&c| (c, records.iter().filter(|r| r.is_white == is_white && !r.in_book && r.classification == c).count()))
+            .filter(|&(_, n)| n > 0)
+            .map(|(c, n)| format!("{} {}", n, c.as_str()))
+            .collect();
+        println!("{} accuracy: {:.2}% ({})", name, side_accuracy(&records, is_white), counts.join(", "));
+    }
+    if let Some(reference) = args.book.master_reference(&client, &args.net)? {
+        for (name, color) in [("White", Color::White), ("Black", Color::Black)] {
+            let m = match_rate(reference.as_ref(), &moves, color, args.book.master_match_plies)?;
+            println!("{} master-move match: {}/{} positions", name, m.matched, m.positions);
+        }
+    }
+    if let Some(db) = args.book.novelty {
+        let depth = book_depth(&Explorer::new(&client, db, args.net.lichess_token.as_deref()), &moves, 1)?;
+        match Novelty::from_depth(depth, moves.len()) {
+            Some(n) => println!("Novelty: ply {} ({}) by {:?}", n.ply, moves[n.ply - 1], n.by),
+            None => println!("Novelty: none, the whole game is in the database"),
+        }
+    }
+    Ok(())
+}
 
-    rayon::ThreadPoolBuilder::new().num_threads(args.workers).build_global().unwrap();
+/// Headline numbers of one implementation's run.
+struct ImplRun {
+    games: f64,
+    moves: f64,
+    accuracy: f64,
+    analysis_s: f64,
+    games_per_second: f64,
+    moves_per_second: f64,
+    wall_s: f64,
+}
 
-    println!("Fetching archives...");
-    let fetch_start = Instant::now();
-    let mut archives = fetch_archives(&args.username).expect("Failed to fetch");
-    archives.reverse();
+/// Runs `cmd` to completion, failing with its stderr when it exits unsuccessfully.
+fn run_impl(cmd: &mut std::process::Command) -> Result<(String, f64), RunError> {
+    let start = Instant::now();
+    let output = cmd.stderr(std::process::Stdio::piped()).output()
+        .map_err(|e| format!("cannot run {:?}: {}", cmd.get_program(), e))?;
+    if !output.status.success() {
+        return Err(format!("{:?} failed ({}): {}", cmd.get_program(), output.status, String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok((String::from_utf8(output.stdout)?, start.elapsed().as_secs_f64()))
+}
+
+/// Number after `label` on a line of the Python summary, e.g. "Analysis time: 1.23s".
+fn summary_number(text: &str, label: &str) -> f64 {
+    text.lines().find_map(|l| {
+        let rest = l.strip_prefix(label)?;
+        let value = rest.rsplit(':').next()?.trim().trim_end_matches(['s', '%']);
+        value.parse().ok()
+    }).unwrap_or(f64::NAN)
+}
+
+/// PGN text of the games a comparison runs on: the file, or the player's
+/// recent chess.com games.
+fn comparison_pgn(pgn: Option<&Path>, username: &str, games: usize, net: &NetArgs, config: &Config) -> Result<String, RunError> {
+    Ok(match pgn {
+        Some(path) => read_input(path)?,
+        None => {
+            let client = HttpClient::new(net.options(config))?;
+            progress!("Fetching archives...");
+            let fetched = fetch_recent_games(&client, username, games)?;
+            fetched.games.iter().filter_map(|g| g.pgn.as_deref()).collect::<Vec<_>>().join("\n\n")
+        }
+    })
+}
+
+fn compare_impl(args: &CompareImplArgs, config: &Config) -> Result<(), RunError> {
+    let text = comparison_pgn(args.pgn.as_deref(), &args.username, args.games, &args.net, config)?;
+    let games: Vec<&str> = split_games(&text).into_iter().take(args.games).collect();
+    if games.is_empty() { return Err("no games to compare".into()); }
+    let path = std::env::temp_dir().join(format!("chess-bench-compare-{}.pgn", std::process::id()));
+    std::fs::write(&path, games.join("\n"))?;
+    progress!("Comparing on {} games ({})", games.len(), path.display());
+
+    let common = [
+        args.username.clone(), games.len().to_string(), "--pgn".into(), path.display().to_string(),
+        "--workers".into(), args.workers.to_string(),
+        "--threads".into(), args.engine.threads().to_string(), "--depth".into(), args.engine.depth().to_string(),
+    ];
+    progress!("Running Rust...");
+    let rust = run_impl(std::process::Command::new(std::env::current_exe()?).args(&common).args(["--format", "json", "-q"]))
+        .and_then(|(out, wall_s)| {
+            let v: serde_json::Value = serde_json::from_str(&out)?;
+            let f = |k: &str| v["summary"][k].as_f64().unwrap_or(f64::NAN);
+            Ok(ImplRun {
+                games: f("games_analyzed"), moves: f("total_moves"), accuracy: f("average_accuracy"),
+                analysis_s: f("analysis_time_s"), games_per_second: f("games_per_second"),
+                moves_per_second: f("moves_per_second"), wall_s,
+            })
+        });
+    progress!("Running Python...");
+    let python = run_impl(std::process::Command::new(&args.python).arg(&args.python_script).args(&common))
+        .map(|(out, wall_s)| ImplRun {
+            games: summary_number(&out, "Games analyzed"), moves: summary_number(&out, "Total moves analyzed"),
+            accuracy: summary_number(&out, "Average accuracy"), analysis_s: summary_number(&out, "Analysis time"),
+            games_per_second: summary_number(&out, "Games per second"),
+            moves_per_second: summary_number(&out, "Moves per second"), wall_s,
+        });
+    let _ = std::fs::remove_file(&path);
+    let (rust, python) = (rust?, python?);
+
+    println!("\n{:<20} {:>12} {:>12} {:>12}", "", "Rust", "Python", "Rust/Python");
+    println!("{}", "=".repeat(59));
+    let rows = [
+        ("Games analyzed", rust.games, python.games, 0),
+        ("Total moves", rust.moves, python.moves, 0),
+        ("Average accuracy", rust.accuracy, python.accuracy, 2),
+        ("Analysis time (s)", rust.analysis_s, python.analysis_s, 2),
+        ("Wall time (s)", rust.wall_s, python.wall_s, 2),
+        ("Games per second", rust.games_per_second, python.games_per_second, 2),
+        ("Moves per second", rust.moves_per_second, python.moves_per_second, 1),
+    ];
+    for (name, r, p, prec) in rows {
+        println!("{:<20} {:>12.prec$} {:>12.prec$} {:>11.2}x", name, r, p, r / p, prec = prec);
+    }
+    println!("\nResult parity: games {}, moves {}, accuracy differs by {:.2} points",
+        if rust.games == python.games { "match" } else { "differ" },
+        if rust.moves == python.moves { "match" } else { "differ" },
+        (rust.accuracy - python.accuracy).abs());
+    Ok(())
+}
+
+/// Analyzes the positions with the engine at `path`, split over `workers`
+/// engines, and times the whole set.
+fn run_engine(path: &Path, engine: &EngineArgs, positions: &[TestPosition], workers: usize) -> Result<EngineRun, RunError> {
+    let engine = &engine.sized_for(workers);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(workers.max(1)).build()?;
+    let chunk = positions.len().div_ceil(workers.max(1)).max(1);
+    let start = Instant::now();
+    let parts: Vec<Result<(EngineInfo, Vec<Eval>), RunError>> = pool.install(|| positions.par_chunks(chunk).map(|chunk| {
+        let mut e = engine.start_at(path).map_err(|err| format!("cannot start {}: {}", path.display(), err))?;
+        let evals: Result<Vec<Eval>, _> = chunk.iter().map(|p| e.analyze(&p.fen, p.white_to_move)).collect();
+        e.quit();
+        Ok((e.info().clone(), evals?))
+    }).collect());
+    let seconds = start.elapsed().as_secs_f64();
+    let mut run = EngineRun { info: EngineInfo::default(), evals: Vec::with_capacity(positions.len()), seconds };
+    for part in parts {
+        let (info, evals) = part?;
+        run.info = info;
+        run.evals.extend(evals);
+    }
+    Ok(run)
+}
+
+fn compare_engines(args: &CompareEnginesArgs, config: &Config) -> Result<(), RunError> {
+    let text = comparison_pgn(args.pgn.as_deref(), &args.username, args.games, &args.net, config)?;
+    let games: Vec<&str> = split_games(&text).into_iter().take(args.games).collect();
+    let positions = game_positions(&games, args.positions);
+    if positions.is_empty() { return Err("no positions to compare".into()); }
+    progress!("Comparing on {} positions from {} games", positions.len(), games.len());
+
+    progress!("Running A ({})...", args.engine_a.display());
+    let a = run_engine(&args.engine_a, &args.engine, &positions, args.workers)?;
+    progress!("Running B ({})...", args.engine_b.display());
+    let b = run_engine(&args.engine_b, &args.engine, &positions, args.workers)?;
+    let comparison = EngineComparison::new(a, b);
+    comparison.write_text(&mut std::io::stdout())?;
+    if let Some(path) = &args.json {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &comparison)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Searches the same positions `--repeats` times, each pass with fresh
+/// engines given the positions in the same order, so that only the
+/// engine's own nondeterminism (threads, timing) tells the passes apart.
+fn engine_stability(args: &EngineStabilityArgs, config: &Config) -> Result<(), RunError> {
+    let text = comparison_pgn(args.pgn.as_deref(), &args.username, args.games, &args.net, config)?;
+    let games: Vec<&str> = split_games(&text).into_iter().take(args.games).collect();
+    let positions = game_positions(&games, args.positions);
+    if positions.is_empty() { return Err("no positions to search".into()); }
+    progress!("Searching {} positions from {} games {} times", positions.len(), games.len(), args.repeats);
+    let mut runs = Vec::with_capacity(args.repeats as usize);
+    for i in 0..args.repeats {
+        progress!("Pass {}/{}...", i + 1, args.repeats);
+        runs.push(run_engine(&stockfish_path(), &args.engine, &positions, args.workers)?);
+    }
+    let stability = EvalStability::new(&positions, runs);
+    stability.write_text(&mut std::io::stdout())?;
+    if let Some(path) = &args.json {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &stability)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn compare_runs(args: &CompareRunsArgs) -> Result<(), RunError> {
+    let load = |path: &Path| -> Result<RunValues, RunError> {
+        RunValues::from_json(&read_input(path)?).map_err(|e| format!("{}: {}", path.display(), e).into())
+    };
+    let comparison = RunComparison::new(&load(&args.a)?, &load(&args.b)?);
+    comparison.write_text(&mut std::io::stdout())?;
+    if let Some(path) = &args.json {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &comparison)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn load_results(path: &Path) -> Result<SavedResults, RunError> {
+    SavedResults::from_json(&read_input(path)?).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+fn diff(args: &DiffArgs) -> Result<(), RunError> {
+    let diff = RunDiff::new(&load_results(&args.old)?, &load_results(&args.new)?, args.min_change);
+    diff.write_text(&mut std::io::stdout())?;
+    if let Some(path) = &args.json {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &diff)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn merge(args: &MergeArgs) -> Result<(), RunError> {
+    let runs = args.inputs.iter().map(|path| load_results(path)).collect::<Result<Vec<_>, RunError>>()?;
+    let merged = Merged::new(runs)?;
+    let mut summary = Summary::new(&merged.username, &merged.games, merged.fetch_time_s, merged.parse_time_s, merged.analysis_time_s);
+    summary.engine = merged.engine;
+    match &args.out {
+        Some(path) => {
+            let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+            report::write_json(&mut out, &summary, &merged.games)?;
+            out.flush()?;
+        }
+        None => report::write_json(&mut std::io::stdout().lock(), &summary, &merged.games)?,
+    }
+    progress!("Merged {} games from {} files ({} duplicates, {} taken from a deeper analysis)",
+        merged.games.len(), args.inputs.len(), merged.duplicates, merged.replaced);
+    Ok(())
+}
+
+fn event(args: &EventArgs, config: &Config) -> Result<(), RunError> {
+    rayon::ThreadPoolBuilder::new().num_threads(args.workers).build_global()?;
+    let client = HttpClient::new(args.net.options(config))?;
+    let id = tournament_id(&args.url).ok_or_else(|| format!("no tournament ID in {:?}", args.url))?;
+    progress!("Fetching tournament {}...", id);
+    let tournament = fetch_tournament(&client, id).map_err(|e| ExitError { code: FETCH_EXIT_CODE, message: format!("failed to fetch {}: {}", id, e) })?;
+    let games: Vec<(usize, &GameData)> = tournament.rounds.iter().enumerate()
+        .flat_map(|(i, round)| round.iter().map(move |g| (i + 1, g)))
+        .collect();
+    progress!("{}: {} rounds, {} games\n", tournament.name, tournament.rounds.len(), games.len());
+
+    progress!("Analyzing games...");
+    let progress = Progress::new("Analyzed", games.len(), 10, Duration::ZERO);
+    let workers = Workers::new(args.workers);
+    let engine = args.engine.sized_for(args.workers);
+    let mut ctx = RunContext::new("", &client, &args.net, &engine, &args.book, &args.report, &progress, &workers)?;
+    ctx.every_game = true;
+    ctx.scoring = args.scoring.scoring();
+    ctx.exclusions = config.exclude.clone();
+    if !args.engine.skip_engine_check && !games.is_empty() { engine.check(args.workers)?; }
+    let results: Vec<(usize, GameResult)> = games.par_iter().enumerate().filter_map(|(i, &(round, g))| {
+        let r = analyze_game(g, Some(i + 1), &ctx).map(|r| (round, r));
+        progress.tick();
+        r
+    }).collect();
+    if results.is_empty() {
+        return Err(ExitError { code: NO_GAMES_EXIT_CODE, message: "no games to analyze".into() }.into());
+    }
+
+    let standings = EventStandings::new(&tournament.name, tournament.rounds.len(), &results);
+    let mut out = std::io::stdout().lock();
+    match args.format {
+        Format::Text => standings.write_text(&mut out)?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &standings)?;
+            writeln!(out)?;
+        }
+        Format::Csv => standings.write_csv(&mut out)?,
+    }
+    Ok(())
+}
+
+/// A buffered reader of `path`, treating "-" as stdin.
+fn open_input(path: &Path) -> Result<Box<dyn std::io::BufRead>, RunError> {
+    Ok(if path == Path::new("-") {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(std::io::BufReader::new(std::fs::File::open(path).map_err(|e| format!("cannot open {}: {}", path.display(), e))?))
+    })
+}
+
+/// The `count` most recent games of `username` on `source`.
+fn download_games(client: &HttpClient, username: &str, count: usize, source: Source, net: &NetArgs) -> Result<Vec<GameData>, RunError> {
+    Ok(match source {
+        Source::Chesscom => {
+            progress!("Fetching archives...");
+            let fetched = fetch_recent_games(client, username, count)?;
+            for url in &fetched.missing { eprintln!("Warning: cannot load {}", url); }
+            fetched.games
+        }
+        Source::Lichess => {
+            progress!("Fetching lichess games...");
+            lichess::fetch_user_games(client, username, count, net.lichess_token.as_deref())?
+        }
+    })
+}
+
+fn fetch(args: &FetchArgs, config: &Config) -> Result<(), RunError> {
+    let client = HttpClient::new(args.net.options(config))?;
+    let mut games = download_games(&client, &args.username, args.games, args.source, &args.net)?;
+    games.sort_by_key(|g| g.end_time);
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut written = 0;
+    for pgn in games.iter().filter_map(|g| g.pgn.as_deref()) {
+        // Clock comments and tags are kept as the site wrote them
+        writeln!(out, "{}\n", pgn.trim())?;
+        written += 1;
+    }
+    out.flush()?;
+    match &args.out {
+        Some(path) => progress!("Wrote {} games to {}", written, path.display()),
+        None => progress!("Wrote {} games", written),
+    }
+    Ok(())
+}
+
+/// The cache and data areas of [`dirs`], by name.
+fn cache_areas() -> Vec<(&'static str, Option<PathBuf>)> {
+    vec![
+        ("HTTP responses", dirs::http_cache_dir()),
+        ("Eval cache", dirs::eval_cache_path()),
+        ("Run history", dirs::history_dir()),
+    ]
+}
+
+fn cache_stats() -> Result<(), RunError> {
+    let mut out = std::io::stdout().lock();
+    let mut cached = dirs::Usage::default();
+    for (name, path) in cache_areas() {
+        let Some(path) = path else { continue };
+        let used = dirs::usage(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        if path.starts_with(dirs::cache_dir().unwrap_or_default()) { cached.add(used); }
+        writeln!(out, "{:<15} {:>7} files {:>11}  {}", name, used.files, report::fmt_bytes(used.bytes), path.display())?;
+    }
+    // Responses cached at the top of the cache directory by older versions
+    if let Some(dir) = dirs::cache_dir() {
+        let total = dirs::usage(&dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+        let (files, bytes) = (total.files - cached.files, total.bytes - cached.bytes);
+        if files > 0 { writeln!(out, "{:<15} {:>7} files {:>11}  {}", "Other cache", files, report::fmt_bytes(bytes), dir.display())?; }
+    }
+    if let Some(dir) = dirs::old_cache_dir() {
+        let used = dirs::usage(&dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+        writeln!(out, "{:<15} {:>7} files {:>11}  {}", "Old cache", used.files, report::fmt_bytes(used.bytes), dir.display())?;
+    }
+    if let Some(path) = default_config_path() {
+        writeln!(out, "{:<15} {:>25}  {}", "Config", if path.exists() { "" } else { "(none)" }, path.display())?;
+    }
+    Ok(())
+}
+
+/// Searches each of [`REFERENCE_POSITIONS`] and prints whether the engine
+/// got it right; any miss or engine failure exits with [`ENGINE_EXIT_CODE`].
+fn engine_check(args: &EngineCheckArgs) -> Result<(), RunError> {
+    let fail = |message: String| ExitError { code: ENGINE_EXIT_CODE, message };
+    let mut engine = args.engine.start().map_err(|e| fail(format!("engine did not start: {} ({})", e, stockfish_path().display())))?;
+    engine.set_timeout(Some(args.engine.position_timeout.unwrap_or(ENGINE_CHECK_TIMEOUT)));
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "Engine: {}", engine.info().describe())?;
+    let mut failed = 0;
+    for p in &REFERENCE_POSITIONS {
+        let started = Instant::now();
+        let eval = engine.analyze(p.fen, p.white_to_move()).map_err(|e| fail(format!("{}: {}", p.name, e)))?;
+        let best = eval.best_move.as_ref().map_or("-".to_string(), |m| m.to_string());
+        let problem = p.problem(&eval);
+        if problem.is_some() { failed += 1; }
+        writeln!(out, "{:<4}  {:<28} {:>6}  {:<6} {:>6.2}s{}", if problem.is_some() { "FAIL" } else { "ok" }, p.name,
+            eval.score.to_string(), best, started.elapsed().as_secs_f64(), problem.map_or(String::new(), |p| format!("  {}", p)))?;
+    }
+    engine.quit();
+    writeln!(out, "{} of {} positions passed", REFERENCE_POSITIONS.len() - failed, REFERENCE_POSITIONS.len())?;
+    if failed > 0 { return Err(fail(format!("engine got {} reference positions wrong", failed)).into()); }
+    Ok(())
+}
+
+fn cache_clear(args: &CacheClearArgs) -> Result<(), RunError> {
+    let mut targets = match (args.http, args.evals) {
+        (false, false) => vec![dirs::cache_dir(), dirs::old_cache_dir()],
+        (http, evals) => [(http, dirs::http_cache_dir()), (evals, dirs::eval_cache_path())].into_iter()
+            .filter(|t| t.0).map(|t| t.1).collect(),
+    };
+    if args.http {
+        targets.extend(dirs::old_responses().map_err(|e| format!("cannot read the cache: {}", e))?.into_iter().map(Some));
+    }
+    let mut freed = dirs::Usage::default();
+    for path in targets.into_iter().flatten() {
+        freed.add(dirs::clear(&path).map_err(|e| format!("cannot clear {}: {}", path.display(), e))?);
+    }
+    println!("Removed {} files, {}", freed.files, report::fmt_bytes(freed.bytes));
+    Ok(())
+}
+
+fn book_build(args: &BookBuildArgs, config: &Config) -> Result<(), RunError> {
+    let games = match &args.pgn {
+        Some(path) => games_from_pgn(&read_input(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?),
+        None => download_games(&HttpClient::new(args.net.options(config))?, &args.username, args.games, args.source, &args.net)?,
+    };
+    let target = args.username.to_lowercase();
+    let mut book = BookBuilder::new();
+    for game in &games {
+        let Some(pgn) = game.pgn.as_deref() else { continue };
+        let name = |p: &Option<PlayerData>| p.as_ref().and_then(|p| p.username.as_deref()).map(str::to_lowercase);
+        let color = if name(&game.white).as_deref() == Some(target.as_str()) {
+            Color::White
+        } else if name(&game.black).as_deref() == Some(target.as_str()) {
+            Color::Black
+        } else {
+            continue;
+        };
+        if args.side.is_some_and(|s| (s == Side::White) != (color == Color::White)) { continue; }
+        let white_score = match tag_value(pgn, "Result") {
+            Some("1-0") => Some(1.0),
+            Some("0-1") => Some(0.0),
+            Some("1/2-1/2") => Some(0.5),
+            _ => None,
+        };
+        let score = white_score.map(|s| if color == Color::White { s } else { 1.0 - s });
+        book.add_game(&parse_pgn_moves(pgn), color, score, args.max_ply);
+    }
+    if book.is_empty() {
+        return Err(ExitError { code: NO_GAMES_EXIT_CODE, message: format!("no games of {} to build a book from", args.username) }.into());
+    }
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&args.out).map_err(|e| format!("cannot create {}: {}", args.out.display(), e))?);
+    let (positions, written) = book.write(&mut out, args.min_games)?;
+    out.flush()?;
+    progress!("Wrote {} entries for {} positions from {} games to {}", written, positions, book.games, args.out.display());
+    Ok(())
+}
+
+/// Failed requests in a row after which a worker gives up on the coordinator.
+const WORKER_RETRIES: usize = 5;
+
+fn worker(args: &WorkerArgs) -> Result<(), RunError> {
+    let base = args.url.trim_end_matches('/');
+    let client = reqwest::blocking::Client::new();
+    let mut engine: Option<(JobSettings, StockfishEngine)> = None;
+    let (mut failures, mut done) = (0, 0);
+    progress!("Waiting for jobs from {}", base);
+    loop {
+        let response = match client.get(format!("{}/job", base)).send() {
+            Ok(r) => { failures = 0; r }
+            Err(e) => {
+                failures += 1;
+                // A coordinator that served jobs and went away has finished its run
+                if failures >= WORKER_RETRIES && done > 0 { break; }
+                if failures >= WORKER_RETRIES { return Err(format!("cannot reach {}: {}", base, e).into()); }
+                verbose!("  Coordinator unreachable ({}), retrying", e);
+                std::thread::sleep(args.poll);
+                continue;
+            }
+        };
+        match response.status().as_u16() {
+            200 => {}
+            410 => break,
+            204 => { std::thread::sleep(args.poll); continue; }
+            code => return Err(format!("{} answered {} for a job", base, code).into()),
+        }
+        let job: Job = response.json()?;
+        // One engine serves every job with the same settings
+        if engine.as_ref().is_none_or(|(s, _)| *s != job.settings) {
+            if let Some((_, mut old)) = engine.take() { old.quit(); }
+            let settings = EngineArgs { depth: job.settings.depth, multipv: job.settings.multipv, hash: job.settings.hash, preset: None, ..args.engine.clone() };
+            match settings.start() {
+                Ok(mut e) => {
+                    e.set_clear_hash(job.settings.clear_hash);
+                    engine = Some((job.settings, e));
+                }
+                Err(e) => eprintln!("Warning: cannot start the engine: {}", e),
+            }
+        }
+        let result = match &mut engine {
+            Some((_, e)) => run_job(e, &job),
+            None => JobResult { error: Some("cannot start the engine".into()), ..Default::default() },
+        };
+        // A failed engine is replaced for the next job
+        if result.error.is_some() {
+            if let Some((_, mut e)) = engine.take() { e.quit(); }
+        }
+        client.post(format!("{}/result/{}", base, job.id)).json(&result).send()?.error_for_status()?;
+        done += 1;
+        verbose!("  Job {}: {} positions", job.id, result.evals.len());
+    }
+    if let Some((_, mut e)) = engine { e.quit(); }
+    progress!("Run finished; analyzed {} games", done);
+    Ok(())
+}
+
+fn positions(args: &PositionsArgs) -> Result<(), RunError> {
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?)),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+    let filter = PositionFilter { unique: args.unique, side: args.side };
+    let stats = write_positions(open_input(&args.pgn)?, filter, &mut out)?;
+    out.flush()?;
+    if stats.truncated > 0 { eprintln!("Warning: {} games stopped at an unplayable move", stats.truncated); }
+    progress!("Wrote {} of {} positions from {} games", stats.written, stats.positions, stats.games);
+    Ok(())
+}
+
+fn split(args: &SplitArgs) -> Result<(), RunError> {
+    let stats = split_pgn(open_input(&args.file)?, args.by, &args.out)?;
+    for (path, games) in &stats.files { verbose!("  {}: {} games", path.display(), games); }
+    progress!("Split {} games into {} files in {}", stats.games, stats.files.len(), args.out.display());
+    Ok(())
+}
 
-    let mut all_games = Vec::new();
-    for url in &archives {
-        if all_games.len() >= args.games { break; }
-        if let Ok(games) = fetch_games(url) {
-            let parts: Vec<&str> = url.split('/').collect();
-            println!("  Fetched {} games from {}/{}", games.len(), parts[parts.len()-2], parts[parts.len()-1]);
-            all_games.extend(games);
+fn validate(args: &ValidateArgs) -> Result<(), RunError> {
+    let mut out = match &args.out {
+        Some(path) => Some(std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?)),
+        None => None,
+    };
+    let checked = validate::validate(open_input(&args.file)?, |game| match &mut out {
+        Some(out) => writeln!(out, "{}", game),
+        None => Ok(()),
+    })?;
+    if let Some(out) = &mut out { out.flush()?; }
+    let name = args.file.display();
+    for i in &checked.issues {
+        println!("{}:{}: game {}: {}{}{}", name, i.line, i.game, i.kind.as_str(), if i.detail.is_empty() { "" } else { ": " }, i.detail);
+    }
+    println!("Checked {} games: {} with problems ({} issues)", checked.games, checked.bad_games, checked.issues.len());
+    if let Some(path) = &args.out { progress!("Cleaned copy written to {}", path.display()); }
+    if let Some(path) = &args.json {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &checked)?;
+        writeln!(out)?;
+    }
+    if checked.bad_games > 0 {
+        return Err(ExitError { code: INVALID_PGN_EXIT_CODE, message: format!("{} of {} games have problems", checked.bad_games, checked.games) }.into());
+    }
+    Ok(())
+}
+
+/// Games polled per Lichess request in follow mode.
+const FOLLOW_LICHESS_GAMES: usize = 20;
+
+/// Polls for finished games forever, writing each new result as a JSON line.
+fn follow(args: &FollowArgs, config: &Config) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = HttpClient::new(args.net.options(config))?;
+    let mut out: Box<dyn Write> = match &args.out {
+        Some(path) => Box::new(std::fs::OpenOptions::new().create(true).append(true).open(path)?),
+        None => Box::new(std::io::stdout()),
+    };
+    let progress = Progress::new("Analyzed", 0, usize::MAX, Duration::ZERO);
+    let workers = Workers::new(1);
+    let mut ctx = RunContext::new(&args.username, &client, &args.net, &args.engine, &args.book, &args.report, &progress, &workers)?;
+    ctx.filter = args.filter.filter_with_titles(&client);
+    ctx.exclusions = config.exclude.clone();
+    let mut monitor = AlertMonitor::new(args.alerts.rules(), &args.username);
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut last_month = None;
+    let mut first = true;
+    progress!("Following {} every {}s", args.username, args.interval);
+
+    loop {
+        let polled = match args.source {
+            Source::Chesscom => {
+                let month = current_month();
+                // Finish the previous month once more after the month changes
+                let months: Vec<(i32, u32)> = last_month.filter(|&m| m != month).into_iter().chain([month]).collect();
+                last_month = Some(month);
+                months.iter()
+                    .map(|&m| fetch_games(&client, &month_url(&args.username, m)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(|g| g.concat())
+            }
+            Source::Lichess => lichess::fetch_user_games(&client, &args.username, FOLLOW_LICHESS_GAMES, args.net.lichess_token.as_deref()),
+        };
+        match polled {
+            Ok(games) => {
+                let new: Vec<GameData> = games.into_iter()
+                    .filter(|g| g.url.as_ref().is_some_and(|u| seen.insert(u.clone())))
+                    .collect();
+                if first && !args.backfill {
+                    progress!("  {} games already played; waiting for new ones", new.len());
+                } else {
+                    for g in &new {
+                        let Some(r) = analyze_game(g, None, &ctx) else { continue };
+                        progress!("  {} vs {}: {:.1}% / {:.1}%", r.white, r.black, r.white_accuracy, r.black_accuracy);
+                        writeln!(out, "{}", serde_json::to_string(&r)?)?;
+                        out.flush()?;
+                        if let Some(alert) = monitor.push(&r) { raise_alert(&alert, &args.alerts, &client); }
+                    }
+                }
+                first = false;
+            }
+            Err(e) => progress!("  Poll failed: {}", e),
         }
+        std::thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+/// Repeats the run forever on `schedule`, writing each result to a dated snapshot.
+fn run_scheduled(args: &Args, client: &HttpClient, schedule: &Schedule) -> ! {
+    let snapshot_dir = args.snapshot_dir.clone().or_else(dirs::history_dir).unwrap_or_else(|| PathBuf::from("."));
+    if let Err(e) = std::fs::create_dir_all(&snapshot_dir) {
+        eprintln!("Error: cannot create {}: {}", snapshot_dir.display(), e);
+        std::process::exit(1);
     }
+    loop {
+        let now = now_unix();
+        let next = schedule.next_after(now).unwrap_or_else(|| {
+            eprintln!("Error: the schedule never fires");
+            std::process::exit(1);
+        });
+        progress!("Next run at {}", fmt_unix(next));
+        std::thread::sleep(Duration::from_secs((next - now) as u64));
+
+        let anonymizer = args.anonymize.as_deref().map(Anonymizer::new);
+        let name = anonymizer.as_ref().map_or_else(|| args.username.to_lowercase(), |a| a.name(&args.username));
+        let snapshot = snapshot_dir.join(format!("{}-{}.json", name, snapshot_stamp(next)));
+        let written = run(args, client).and_then(|(mut summary, mut games)| {
+            let mut recent: Vec<&GameResult> = games.iter().collect();
+            recent.sort_by_key(|g| g.end_time);
+            if let Some(alert) = args.alerts.rules().check(&recent, &args.username.to_lowercase()) {
+                raise_alert(&alert, &args.alerts, client);
+            }
+            if let Some(a) = &anonymizer { a.results(&mut summary, &mut games); }
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&snapshot)?);
+            report::write_json(&mut file, &summary, &games)?;
+            report::write_text(&mut std::io::stderr(), &summary)?;
+            if let Err(e) = store_run(args, &summary, &games) { eprintln!("Warning: {}", e); }
+            Ok(())
+        });
+        match written {
+            Ok(()) => progress!("Wrote {}", snapshot.display()),
+            Err(e) => eprintln!("Error: scheduled run failed: {}", e),
+        }
+    }
+}
+
+type RunError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Fetches or loads the games of one run and analyzes them.
+fn run(args: &Args, client: &HttpClient) -> Result<(Summary, Vec<GameResult>), RunError> {
+    let mut meta = RunMetadata::start();
+    let fetch_start = Instant::now();
+    alloc::reset_peak();
+    let fetch_alloc = alloc::snapshot();
+    let fetch_span = spans::span("fetch");
+    let mut all_games = if let Some(path) = &args.pgn {
+        progress!("Reading {}...", path.display());
+        games_from_pgn(&read_input(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?)
+    } else {
+        let fetched = match args.source {
+            Source::Chesscom => {
+                progress!("Fetching archives...");
+                fetch_recent_games(client, &args.username, args.games)
+            }
+            Source::Lichess => {
+                progress!("Fetching lichess games...");
+                lichess::fetch_user_games(client, &args.username, args.games, args.net.lichess_token.as_deref())
+                    .map(|games| RecentGames { games, missing: Vec::new() })
+            }
+        }.map_err(|e| ExitError { code: FETCH_EXIT_CODE, message: format!("failed to fetch games for {}: {}", args.username, e) })?;
+        if args.net.offline && !fetched.missing.is_empty() {
+            eprintln!("Missing from the cache (offline mode):");
+            for url in &fetched.missing { eprintln!("  {}", url); }
+            if fetched.games.is_empty() { return Err("no cached games (offline mode)".into()); }
+        }
+        fetched.games
+    };
     all_games.truncate(args.games);
+    drop(fetch_span);
     let fetch_time = fetch_start.elapsed();
-    println!("Fetched {} games in {:.2}s\n", all_games.len(), fetch_time.as_secs_f64());
+    let verb = if args.pgn.is_some() { "Loaded" } else { "Fetched" };
+    progress!("{} {} games in {:.2}s\n", verb, all_games.len(), fetch_time.as_secs_f64());
+
+    let fetch_alloc = StageAlloc::new(alloc::snapshot().since(fetch_alloc), Some(alloc::peak()));
+    alloc::reset_peak();
+    let analysis_alloc = alloc::snapshot();
 
-    println!("Analyzing games...");
+    progress!("Analyzing games...");
     let analysis_start = Instant::now();
-    let completed = Arc::new(AtomicUsize::new(0));
-    let total = all_games.len();
+    let progress = Progress::new("Analyzed", all_games.len(), 10, fetch_time);
+    let workers = Workers::new(args.workers);
+    let mut ctx = RunContext::new(&args.username, client, &args.net, &args.engine, &args.book, &args.report, &progress, &workers)
+        .map_err(|e| format!("failed to read master reference: {}", e))?;
+    ctx.annotate = args.publish_study;
+    ctx.filter = args.filter.filter_with_titles(client);
+    ctx.scoring = args.scoring.scoring();
+    ctx.exclusions = args.exclude.clone();
+    if let Some(path) = &args.save_evals {
+        ctx.evals = Some(EvalStore::open(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?);
+    }
+    if let Some(path) = &args.rescore {
+        let store = EvalStore::load(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        progress!("Rescoring from {} saved evals", store.len());
+        ctx.saved = Some(store);
+    }
+
+    let dashboard = match args.web.as_deref() {
+        Some(addr) => {
+            let d = Dashboard::serve(addr, &args.username, all_games.len())
+                .map_err(|e| format!("cannot serve dashboard on {}: {}", addr, e))?;
+            progress!("Dashboard at http://{}/", addr);
+            Some(d)
+        }
+        None => None,
+    };
+
+    if let Some(addr) = &args.serve_jobs {
+        ctx.remote = Some(Coordinator::serve(addr, args.engine.job_settings(), args.job_lease)
+            .map_err(|e| format!("cannot serve jobs on {}: {}", addr, e))?);
+        progress!("Serving jobs at http://{}/; start workers with `benchmark worker http://{}`", addr, addr);
+    }
+
+    let log = match &args.results_log {
+        Some(path) => Some((path, std::sync::Mutex::new(std::fs::OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("cannot open {}: {}", path.display(), e))?))),
+        None => None,
+    };
+    let anonymizer = args.anonymize.as_deref().map(Anonymizer::new);
+
+    // Only games without evals of their own reach the engine
+    if ctx.saved.is_none() && ctx.remote.is_none() && !args.engine.skip_engine_check && all_games.iter().any(|g| g.evals.is_none()) {
+        args.engine.check(args.workers)?;
+    }
 
-    let results: Vec<_> = all_games.par_iter().map(|g| {
-        let r = analyze_game(g, &args.username, args.threads, args.depth);
-        let c = completed.fetch_add(1, Ordering::Relaxed) + 1;
-        if c % 10 == 0 || c == total {
-            println!("  Analyzed {}/{} games ({:.2} games/sec)", c, total, c as f64 / analysis_start.elapsed().as_secs_f64());
+    // Collected in input order whatever order the games finish in
+    let results: Vec<_> = all_games.par_iter().enumerate().map(|(i, g)| {
+        let r = {
+            let _span = spans::span("game");
+            analyze_game(g, Some(i + 1), &ctx)
+        };
+        if let (Some((path, log)), Some(r)) = (&log, &r) {
+            let mut r = r.clone();
+            if let Some(a) = &anonymizer { a.game(&mut r); }
+            // One write per line, so lines of concurrent workers never interleave
+            let line = serde_json::to_string(&r).map(|mut l| { l.push('\n'); l });
+            if let Err(e) = line.map_err(std::io::Error::from).and_then(|l| log.lock().unwrap().write_all(l.as_bytes())) {
+                eprintln!("Warning: cannot append to {}: {}", path.display(), e);
+            }
         }
+        progress.tick();
+        if let Some(d) = &dashboard { d.push(r.as_ref()); }
         r
     }).collect();
 
     let analysis_time = analysis_start.elapsed();
-    let target = args.username.to_lowercase();
-    let mut user_acc = Vec::new();
-    let mut total_moves = 0;
-    let mut analyzed = 0;
-
-    for r in results.into_iter().flatten() {
-        analyzed += 1;
-        total_moves += r.2;
-        if r.3 == target { user_acc.push(r.0); } else { user_acc.push(r.1); }
-    }
-
-    let avg = if user_acc.is_empty() { 0.0 } else { user_acc.iter().sum::<f64>() / user_acc.len() as f64 };
-
-    println!("\nResults");
-    println!("{}", "=".repeat(50));
-    println!("Games analyzed: {}", analyzed);
-    println!("Total moves: {}", total_moves);
-    println!("Average accuracy for {}: {:.2}%", args.username, avg);
-    println!("\nPerformance");
-    println!("{}", "=".repeat(50));
-    println!("Fetch time: {:.2}s", fetch_time.as_secs_f64());
-    println!("Analysis time: {:.2}s", analysis_time.as_secs_f64());
-    println!("Total time: {:.2}s", fetch_time.as_secs_f64() + analysis_time.as_secs_f64());
-    println!("Games per second: {:.4}", analyzed as f64 / analysis_time.as_secs_f64());
-    println!("Moves per second: {:.2}", total_moves as f64 / analysis_time.as_secs_f64());
+    if let Some(coordinator) = &ctx.remote { coordinator.close(); }
+    let games: Vec<GameResult> = results.into_iter().flatten().collect();
+    let mut summary = Summary::new(&args.username, &games,
+        fetch_time.as_secs_f64(), progress.parse_time().as_secs_f64(), analysis_time.as_secs_f64());
+    if let Some(scheme) = args.report.sharpness {
+        let acc: Vec<f64> = games.iter().filter_map(|g| g.sharp_accuracy).collect();
+        summary.sharp_accuracy = (!acc.is_empty()).then(|| SharpAccuracy {
+            scheme,
+            strength: args.report.sharpness_strength,
+            accuracy: acc.iter().sum::<f64>() / acc.len() as f64,
+        });
+    }
+    if args.report.swindles {
+        summary.resourcefulness = Resourcefulness::from_games(&games, &ctx.target, args.report.swindle_threshold);
+    }
+    if args.report.sessions {
+        summary.sessions = SessionStats::from_games(&games, &ctx.target, args.report.session_gap);
+    }
+    if args.report.opponents {
+        summary.opponents = OpponentStats::from_games(&games, &ctx.target, args.report.opponent_min_games);
+    }
+    if args.report.time_of_day {
+        summary.time_of_day = TimeOfDay::from_games(&games, &ctx.target, args.report.timezone);
+    }
+    if args.report.ratings {
+        summary.ratings = rating_series(&games, &ctx.target);
+    }
+    if let Some(top) = args.report.top {
+        summary.listings = Listings::from_games(&games, &ctx.target, top, args.report.sort);
+        if let Some(r) = &mut summary.repertoire { r.text_lines = top; }
+    }
+    if args.report.blunder_causes {
+        summary.blunder_causes = BlunderCauses::from_games(&games);
+    }
+    if let (Some(tally), Some(top)) = (ctx.positions.take(), args.report.repeated_positions) {
+        summary.repeated_positions = tally.finish(top);
+    }
+    if let (Some(tree), Some(path)) = (ctx.tree.take().and_then(|t| t.finish(&ctx.target)), &args.report.opening_tree) {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)
+            .map_err(|e| format!("cannot create {}: {}", path.display(), e))?);
+        serde_json::to_writer(&mut out, &tree).map_err(std::io::Error::from).and_then(|_| out.flush())?;
+        progress!("Opening tree of {} positions written to {}", tree.nodes.len(), path.display());
+    }
+    summary.skipped = progress.skipped();
+    summary.engine = ctx.engine_info.take();
+    if let (Some(store), Some(path)) = (&ctx.evals, &args.save_evals) {
+        match store.save(path) {
+            Ok(()) => progress!("Saved {} evals to {}", store.len(), path.display()),
+            Err(e) => eprintln!("Warning: cannot write {}: {}", path.display(), e),
+        }
+    }
+    meta.finish();
+    summary.run = Some(meta);
+    drop(ctx);
+    summary.workers = workers.finish(analysis_time);
+    if alloc::enabled() {
+        let parse = progress.parse_alloc();
+        let analysis = alloc::snapshot().since(analysis_alloc).since(parse);
+        summary.allocations = Some(AllocStats {
+            fetch: fetch_alloc,
+            parse: StageAlloc::new(parse, None),
+            analysis: StageAlloc::new(analysis, Some(alloc::peak())),
+        });
+    }
+
+    if args.publish_study {
+        publish_study(client, args, &games);
+    }
+    Ok((summary, games))
+}
+
+/// A command's flags and subcommands, as printed by --dump-cli-json.
+fn cli_json(cmd: &clap::Command) -> serde_json::Value {
+    let text = |s: Option<&clap::builder::StyledStr>| s.map(|s| s.to_string());
+    let args: Vec<_> = cmd.get_arguments().filter(|a| !a.is_hide_set()).map(|a| {
+        let takes_value = a.get_num_args().is_some_and(|n| n.takes_values());
+        serde_json::json!({
+            "id": a.get_id().as_str(),
+            "long": a.get_long(),
+            "short": a.get_short(),
+            "positional": a.is_positional(),
+            "help": text(a.get_help()),
+            "required": a.is_required_set(),
+            "takes_value": takes_value,
+            "multiple": matches!(a.get_action(), clap::ArgAction::Append | clap::ArgAction::Count)
+                || a.get_num_args().is_some_and(|n| n.max_values() > 1),
+            "value_name": a.get_value_names().map(|v| v.iter().map(|n| n.as_str()).collect::<Vec<_>>()),
+            "default": a.get_default_values().iter().map(|v| v.to_string_lossy()).collect::<Vec<_>>(),
+            "possible_values": a.get_possible_values().iter().map(|v| v.get_name()).collect::<Vec<_>>(),
+            "env": a.get_env().map(|e| e.to_string_lossy()),
+            "global": a.is_global_set(),
+        })
+    }).collect();
+    serde_json::json!({
+        "name": cmd.get_name(),
+        "about": text(cmd.get_about()),
+        "args": args,
+        "subcommands": cmd.get_subcommands().filter(|c| c.get_name() != "help").map(cli_json).collect::<Vec<_>>(),
+    })
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if cli.dump_cli_json {
+        let mut cmd = Cli::command().name("benchmark");
+        cmd.build();
+        println!("{}", serde_json::to_string_pretty(&cli_json(&cmd)).unwrap());
+        return;
+    }
+    log::set_level(if cli.quiet { log::QUIET } else { log::NORMAL + cli.verbose.min(2) });
+    match dirs::migrate() {
+        Ok(moved) => for (old, new) in moved { progress!("Moved {} to {}", old.display(), new.display()); },
+        Err(e) => eprintln!("Warning: cannot move the files of an earlier version: {}", e),
+    }
+    let config = Config::load(cli.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("Error: invalid config {}", e);
+        std::process::exit(1);
+    });
+    let args = match cli.command {
+        Some(Cmd::Rescore(r)) => {
+            let RescoreArgs { evals, mut run } = *r;
+            run.rescore = Some(evals);
+            run
+        }
+        Some(Cmd::Profile(p)) => {
+            let mut run = p.run;
+            run.stage_profile = true;
+            run
+        }
+        Some(Cmd::Club(c)) => {
+            let ClubArgs { members, member_pause, mut run } = *c;
+            run.club = Some(Club { id: run.username.clone(), members, pause: Duration::from_secs(member_pause) });
+            run
+        }
+        Some(cmd) => {
+            run_command(&cmd, &config);
+            return;
+        }
+        None => cli.run,
+    };
+    run_main(args, &config);
+}
+
+/// Runs a subcommand other than `rescore`, `profile` and `club`.
+fn run_command(cmd: &Cmd, config: &Config) {
+    let done = match cmd {
+        Cmd::AnalyzePgn(a) => analyze_pgn(a, config),
+        Cmd::Follow(a) => follow(a, config),
+        Cmd::CompareImpl(a) => compare_impl(a, config),
+        Cmd::CompareEngines(a) => compare_engines(a, config),
+        Cmd::CompareRuns(a) => compare_runs(a),
+        Cmd::Diff(a) => diff(a),
+        Cmd::Merge(a) => merge(a),
+        Cmd::Event(a) => event(a, config),
+        Cmd::Split(a) => split(a),
+        Cmd::Positions(a) => positions(a),
+        Cmd::Fetch(a) => fetch(a, config),
+        Cmd::Book(BookCmd::Build(a)) => book_build(a, config),
+        Cmd::Cache(CacheCmd::Stats) => cache_stats(),
+        Cmd::Cache(CacheCmd::Clear(a)) => cache_clear(a),
+        Cmd::Engine(EngineCmd::Check(a)) => engine_check(a),
+        Cmd::Engine(EngineCmd::Stability(a)) => engine_stability(a, config),
+        Cmd::Validate(a) => validate(a),
+        Cmd::Worker(a) => worker(a),
+        Cmd::Rescore(_) | Cmd::Profile(_) | Cmd::Club(_) => unreachable!("a benchmark run, started by run_main"),
+        Cmd::Completions { shell } => {
+            clap_complete::generate(*shell, &mut Cli::command(), "benchmark", &mut std::io::stdout());
+            Ok(())
+        }
+    };
+    if let Err(e) = done {
+        eprintln!("Error: {}", e);
+        std::process::exit(exit_code(&e));
+    }
+}
+
+/// The benchmark run: analyze the games and print the report.
+fn run_main(mut args: Args, config: &Config) {
+    args.exclude = config.exclude.clone();
+    args.engine = args.engine.sized_for(args.workers);
+    if args.save_evals.as_deref() == Some(Path::new("")) {
+        let path = dirs::eval_cache_path().unwrap_or_else(|| {
+            eprintln!("Error: no home directory for the default --save-evals file; give one");
+            std::process::exit(1);
+        });
+        if let Some(dir) = path.parent() { let _ = std::fs::create_dir_all(dir); }
+        args.save_evals = Some(path);
+    }
+    // The moves table is filled from each game's eval series
+    if args.postgres.is_some() { args.report.eval_graph = true; }
+    progress!("Rust Chess Benchmark");
+    progress!("{}", "=".repeat(50));
+    match &args.club {
+        Some(club) => progress!("Club: {}", club.id),
+        None => progress!("Username: {}", args.username),
+    }
+    progress!("Max games: {}", args.games);
+    progress!("Workers: {}", args.workers);
+    if let Some(preset) = args.engine.preset { progress!("Preset: {}", preset.as_str()); }
+    progress!("SF threads/worker: {}", args.engine.threads());
+    progress!("Total CPU: {}", args.workers * args.engine.threads());
+    progress!("Depth: {}", args.engine.depth());
+    if let Some(mb) = args.engine.hash() {
+        let auto = if args.engine.explicit_hash().is_none() { " (auto)" } else { "" };
+        progress!("Hash: {} MB{}", mb, auto);
+    }
+    progress!();
+
+    rayon::ThreadPoolBuilder::new().num_threads(args.workers).build_global().unwrap();
+
+    // Missing credentials stop the run before its games, not after
+    let s3 = args.output.as_ref().map(|_| S3Config::from_env(args.s3_endpoint.clone()).unwrap_or_else(|e| {
+        eprintln!("Error: --output: {}", e);
+        std::process::exit(1);
+    }));
+    let client = HttpClient::new(args.net.options(config)).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    });
+    if let Some(club) = &args.club {
+        let mut members = fetch_club_members(&client, &club.id).unwrap_or_else(|e| {
+            eprintln!("Error: failed to fetch the members of {}: {}", club.id, e);
+            std::process::exit(FETCH_EXIT_CODE);
+        });
+        progress!("Club {}: {} members", club.id, members.len());
+        if let Some(n) = club.members { members.truncate(n); }
+        run_leaderboard(args, &client, &members);
+        return;
+    }
+    let players: Vec<String> = args.username.split(',').map(str::trim).filter(|n| !n.is_empty()).map(String::from).collect();
+    if players.len() > 1 {
+        run_leaderboard(args, &client, &players);
+        return;
+    }
+    if let Some(schedule) = &args.schedule {
+        run_scheduled(&args, &client, schedule);
+    }
+    if args.profile.is_some() || args.stage_profile { spans::enable(); }
+    let started = Instant::now();
+    let (mut summary, mut games) = run(&args, &client).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        std::process::exit(exit_code(&e));
+    });
+    let wall_ms = started.elapsed().as_secs_f64() * 1000.0;
+    if let Some(key) = &args.anonymize { Anonymizer::new(key).results(&mut summary, &mut games); }
+    let events = spans::take();
+    if let Some(path) = &args.profile {
+        match spans::write_file(path, &events) {
+            Ok(()) => progress!("Profile written to {}", path.display()),
+            Err(e) => eprintln!("Warning: cannot write profile {}: {}", path.display(), e),
+        }
+    }
+    if args.stage_profile {
+        // The stage table is the output; the summary goes with the progress
+        let profile = StageProfile::from_events(&events, summary.games_analyzed, wall_ms);
+        let mut out = std::io::stdout().lock();
+        let written = match args.format {
+            Format::Json => serde_json::to_writer_pretty(&mut out, &profile).map_err(std::io::Error::from).and_then(|_| writeln!(out)),
+            _ => profile.write_text(&mut out),
+        };
+        written.and_then(|_| report::write_text(&mut std::io::stderr(), &summary)).expect("Failed to write results");
+        return;
+    }
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let text = |out: &mut dyn Write| {
+        report::write_text(out, &summary)?;
+        if args.report.table { report::write_table(out, &games, &summary.username.to_lowercase())?; }
+        Ok(())
+    };
+    let written = match args.format {
+        Format::Text => text(&mut out),
+        Format::Json => report::write_json(&mut out, &summary, &games).and_then(|_| text(&mut std::io::stderr())),
+        Format::Csv => report::write_csv(&mut out, &games).and_then(|_| text(&mut std::io::stderr())),
+    };
+    written.expect("Failed to write results");
+    if let Err(e) = write_graphs(&args.report, &summary, &games) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    if let (Some(s3), Some(location)) = (&s3, &args.output) {
+        if let Err(e) = upload_results(s3, location, &client, &args.report, &summary, &games) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Err(e) = store_run(&args, &summary, &games) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(e) = run_failure(&args, &summary) {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.code);
+    }
+}
+
+/// Runs each player in turn and prints them ranked. A player whose run fails
+/// is left off with a warning.
+fn run_leaderboard(mut args: Args, client: &HttpClient, players: &[String]) {
+    if args.schedule.is_some() || args.web.is_some() || args.publish_study {
+        eprintln!("Error: --schedule, --web and --publish-study take a single username");
+        std::process::exit(1);
+    }
+    // ACPL is averaged from the per-move losses
+    args.report.cp_loss = true;
+    let anonymizer = args.anonymize.as_deref().map(Anonymizer::new);
+    let pause = args.club.as_ref().map_or(Duration::ZERO, |c| c.pause);
+    let mut rows = Vec::new();
+    for (i, name) in players.iter().enumerate() {
+        if i > 0 && args.pgn.is_none() { std::thread::sleep(pause); }
+        progress!("Player {}/{}: {}", i + 1, players.len(), name);
+        args.username = name.clone();
+        match run(&args, client) {
+            Ok((summary, _)) if summary.games_analyzed == 0 => eprintln!("Warning: no games analyzed for {}", name),
+            Ok((mut summary, mut games)) => {
+                if let Some(a) = &anonymizer { a.results(&mut summary, &mut games); }
+                if let Err(e) = store_run(&args, &summary, &games) { eprintln!("Warning: {}", e); }
+                rows.push(LeaderboardRow::new(&summary, &games));
+            }
+            Err(e) => eprintln!("Warning: skipping {}: {}", name, e),
+        }
+    }
+    if rows.is_empty() {
+        eprintln!("Error: no games analyzed for any player");
+        std::process::exit(NO_GAMES_EXIT_CODE);
+    }
+    let mut board = Leaderboard::new(rows);
+    board.name = args.club.as_ref().map(|c| format!("club {}", c.id));
+    let mut out = std::io::stdout().lock();
+    let written = match args.format {
+        Format::Text => board.write_text(&mut out),
+        Format::Json => serde_json::to_writer_pretty(&mut out, &board).map_err(std::io::Error::from)
+            .and_then(|_| writeln!(out))
+            .and_then(|_| board.write_text(&mut std::io::stderr())),
+        Format::Csv => board.write_csv(&mut out).and_then(|_| board.write_text(&mut std::io::stderr())),
+    };
+    written.expect("Failed to write results");
+}
+
+/// Stores the run in the --postgres database, if any.
+fn store_run(args: &Args, summary: &Summary, games: &[GameResult]) -> Result<(), String> {
+    let Some(conn) = &args.postgres else { return Ok(()) };
+    postgres::store(conn, summary, games).map_err(|e| format!("cannot store the run in PostgreSQL: {}", e))?;
+    progress!("Stored {} games of run {} in PostgreSQL", games.len(), postgres::run_id(summary));
+    Ok(())
+}
+
+/// Uploads the JSON results, named after the player and the start of the
+/// run, and the --html and --opening-tree files written next to them.
+fn upload_results(s3: &S3Config, location: &S3Location, client: &HttpClient, report: &ReportArgs, summary: &Summary, games: &[GameResult]) -> Result<(), RunError> {
+    let started = summary.run.as_ref().map_or_else(now_unix, |r| r.started_at);
+    let stem = format!("{}-{}", summary.username, snapshot_stamp(started));
+    let mut json = Vec::new();
+    report::write_json(&mut json, summary, games)?;
+    let mut files = vec![(format!("{}.json", stem), json, "application/json")];
+    for (path, suffix, kind) in [(&report.html, ".html", "text/html; charset=utf-8"), (&report.opening_tree, "-tree.json", "application/json")] {
+        let Some(path) = path else { continue };
+        let body = std::fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        files.push((format!("{}{}", stem, suffix), body, kind));
+    }
+    for (name, body, kind) in files {
+        s3.put(client, location, &name, body, kind).map_err(|e| format!("cannot upload {}: {}", location.url(&name), e))?;
+        progress!("Uploaded {}", location.url(&name));
+    }
+    Ok(())
+}
+
+/// Writes the --html report and --svg-dir graphs.
+fn write_graphs(report: &ReportArgs, summary: &Summary, games: &[GameResult]) -> Result<(), RunError> {
+    if let Some(path) = &report.html {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)
+            .map_err(|e| format!("cannot create {}: {}", path.display(), e))?);
+        report::write_html(&mut out, summary, games).and_then(|_| out.flush())?;
+        progress!("HTML report written to {}", path.display());
+    }
+    if let Some(dir) = &report.svg_dir {
+        std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+        let safe = |s: &str| s.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect::<String>();
+        for (i, g) in games.iter().enumerate() {
+            let path = dir.join(format!("{:04}_{}_vs_{}.svg", i + 1, safe(&g.white), safe(&g.black)));
+            std::fs::write(&path, graph::svg(&g.eval_graph)).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+        }
+        progress!("{} eval graphs written to {}", games.len(), dir.display());
+    }
+    Ok(())
 }
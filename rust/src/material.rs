@@ -0,0 +1,48 @@
+//! Accuracy split by the material balance at the time of the move.
+
+use crate::accuracy::{Bucket, BucketSplit};
+use crate::analysis::MoveRecord;
+use serde::Serialize;
+use shakmaty::{Board, ByRole, Color};
+
+/// Pawn units of advantage from which a side counts as ahead.
+const EDGE: i32 = 2;
+
+/// Material of `color` minus the opponent's, in pawn units (1/3/3/5/9).
+pub fn material_balance(board: &Board, color: Color) -> i32 {
+    let value = |r: ByRole<u8>| (r.pawn as i32) + 3 * (r.knight + r.bishop) as i32 + 5 * r.rook as i32 + 9 * r.queen as i32;
+    value(board.material_side(color)) - value(board.material_side(color.other()))
+}
+
+/// A player's accuracy when ahead, level and behind in material.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct MaterialSplit {
+    pub ahead: Bucket,
+    pub equal: Bucket,
+    pub behind: Bucket,
+}
+
+impl BucketSplit for MaterialSplit {
+    const TITLE: &'static str = "Accuracy by Material";
+    type By = ();
+
+    fn keys(r: &MoveRecord, _: ()) -> Vec<usize> {
+        vec![match r.material {
+            m if m >= EDGE => 0,
+            m if m <= -EDGE => 2,
+            _ => 1,
+        }]
+    }
+
+    fn bucket_mut(&mut self, key: usize) -> &mut Bucket {
+        match key {
+            0 => &mut self.ahead,
+            1 => &mut self.equal,
+            _ => &mut self.behind,
+        }
+    }
+
+    fn rows(&self) -> Vec<(String, Bucket)> {
+        vec![("Ahead".into(), self.ahead), ("Equal".into(), self.equal), ("Behind".into(), self.behind)]
+    }
+}
@@ -0,0 +1,103 @@
+//! Saved runs (`--format json`) of the same player combined into one, for
+//! histories analyzed in chunks or on several machines.
+
+use crate::engine::EngineInfo;
+use crate::report::GameResult;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+pub struct SavedResults {
+    summary: SavedSummary,
+    games: Vec<GameResult>,
+}
+
+#[derive(Deserialize)]
+struct SavedSummary {
+    username: String,
+    #[serde(default)]
+    engine: Option<EngineInfo>,
+    #[serde(default)]
+    fetch_time_s: f64,
+    #[serde(default)]
+    parse_time_s: f64,
+    #[serde(default)]
+    analysis_time_s: f64,
+}
+
+impl SavedResults {
+    pub fn from_json(text: &str) -> Result<Self, serde_json::Error> { serde_json::from_str(text) }
+
+    pub fn username(&self) -> &str { &self.summary.username }
+
+    pub fn games(&self) -> &[GameResult] { &self.games }
+
+    pub fn engine(&self) -> Option<&EngineInfo> { self.summary.engine.as_ref() }
+}
+
+/// Identifies a game across runs: its URL, else the players, date and length.
+pub fn game_key(g: &GameResult) -> String {
+    g.url.clone().unwrap_or_else(|| format!("{}|{}|{}|{}", g.white, g.black, g.headers.date.as_deref().unwrap_or(""), g.moves))
+}
+
+/// The games of several runs, each kept once.
+pub struct Merged {
+    pub username: String,
+    pub games: Vec<GameResult>,
+    /// The runs' engine when they all used the same one.
+    pub engine: Option<EngineInfo>,
+    pub fetch_time_s: f64,
+    pub parse_time_s: f64,
+    pub analysis_time_s: f64,
+    /// Games found in more than one run.
+    pub duplicates: usize,
+    /// Duplicates whose result came from a later run analyzed deeper.
+    pub replaced: usize,
+}
+
+impl Merged {
+    /// Keeps each game once, from the run that analyzed it deepest (the
+    /// earliest of those on a tie), in order of first appearance.
+    pub fn new(runs: Vec<SavedResults>) -> Result<Self, String> {
+        let username = runs.first().ok_or("nothing to merge")?.summary.username.clone();
+        if let Some(r) = runs.iter().find(|r| !r.summary.username.eq_ignore_ascii_case(&username)) {
+            return Err(format!("runs are for different players ({} and {})", username, r.summary.username));
+        }
+        let describe = |r: &SavedResults| r.summary.engine.as_ref().map(EngineInfo::describe);
+        let same_engine = runs.iter().all(|r| describe(r) == describe(&runs[0]));
+        let mut merged = Self {
+            username,
+            games: Vec::new(),
+            engine: if same_engine { runs[0].summary.engine.clone() } else { None },
+            fetch_time_s: 0.0,
+            parse_time_s: 0.0,
+            analysis_time_s: 0.0,
+            duplicates: 0,
+            replaced: 0,
+        };
+        let mut seen: HashMap<String, (usize, u32)> = HashMap::new();
+        for run in runs {
+            merged.fetch_time_s += run.summary.fetch_time_s;
+            merged.parse_time_s += run.summary.parse_time_s;
+            merged.analysis_time_s += run.summary.analysis_time_s;
+            let depth = run.summary.engine.as_ref().map_or(0, |e| e.depth);
+            for game in run.games {
+                match seen.get_mut(&game_key(&game)) {
+                    Some((index, kept_depth)) => {
+                        merged.duplicates += 1;
+                        if depth > *kept_depth {
+                            merged.games[*index] = game;
+                            *kept_depth = depth;
+                            merged.replaced += 1;
+                        }
+                    }
+                    None => {
+                        seen.insert(game_key(&game), (merged.games.len(), depth));
+                        merged.games.push(game);
+                    }
+                }
+            }
+        }
+        Ok(merged)
+    }
+}
@@ -0,0 +1,84 @@
+//! Where and how a run happened, so saved results can be compared later
+//! without guessing the machine, version or flags behind them.
+
+use crate::date::{fmt_unix, now_unix};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RunMetadata {
+    pub crate_version: String,
+    /// Commit the binary was built from, when built inside the git checkout.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_commit: Option<String>,
+    pub os: String,
+    pub arch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<String>,
+    pub cores: usize,
+    /// The command line, program name first.
+    pub args: Vec<String>,
+    /// Unix times.
+    pub started_at: i64,
+    pub finished_at: i64,
+}
+
+impl RunMetadata {
+    /// Metadata of the current process, started now.
+    pub fn start() -> Self {
+        let now = now_unix();
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: option_env!("CHESS_BENCH_GIT_COMMIT").map(str::to_string),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu: cpu_model(),
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            args: std::env::args().collect(),
+            started_at: now,
+            finished_at: now,
+        }
+    }
+
+    pub fn finish(&mut self) { self.finished_at = now_unix(); }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let commit = self.git_commit.as_deref().map(|c| format!(" ({})", c)).unwrap_or_default();
+        writeln!(out, "Run: chess-bench {}{}, {} to {}", self.crate_version, commit,
+            fmt_unix(self.started_at), fmt_unix(self.finished_at))?;
+        let cpu = self.cpu.as_deref().map(|c| format!(", {}", c)).unwrap_or_default();
+        writeln!(out, "Host: {} {}{}, {} cores", self.os, self.arch, cpu, self.cores)?;
+        writeln!(out, "Command: {}", self.args.join(" "))
+    }
+}
+
+/// Total memory in MB, from /proc/meminfo on Linux or sysctl on macOS.
+/// Read once per process.
+pub fn total_memory_mb() -> Option<u64> {
+    static TOTAL: OnceLock<Option<u64>> = OnceLock::new();
+    *TOTAL.get_or_init(|| {
+        if cfg!(target_os = "macos") {
+            let out = std::process::Command::new("sysctl").args(["-n", "hw.memsize"]).output().ok()?;
+            let bytes: u64 = String::from_utf8(out.stdout).ok()?.trim().parse().ok()?;
+            return Some(bytes >> 20);
+        }
+        let info = std::fs::read_to_string("/proc/meminfo").ok()?;
+        let kb: u64 = info.lines().find_map(|l| l.strip_prefix("MemTotal:"))?.trim().trim_end_matches("kB").trim().parse().ok()?;
+        Some(kb >> 10)
+    })
+}
+
+/// CPU model name from /proc/cpuinfo on Linux or sysctl on macOS.
+fn cpu_model() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        let out = std::process::Command::new("sysctl").args(["-n", "machdep.cpu.brand_string"]).output().ok()?;
+        let name = String::from_utf8(out.stdout).ok()?.trim().to_string();
+        return (!name.is_empty()).then_some(name);
+    }
+    let info = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    info.lines().find_map(|l| {
+        let (key, value) = l.split_once(':')?;
+        (key.trim() == "model name").then(|| value.trim().to_string())
+    })
+}
@@ -0,0 +1,145 @@
+//! Tactical motifs in the engine's refutation of a blunder.
+
+use serde::{Deserialize, Serialize};
+use shakmaty::attacks::{attacks, between};
+use shakmaty::{uci::UciMove, Bitboard, Chess, Move, Position, Role};
+
+/// Opponent moves of the refutation line that are looked at.
+const REPLIES: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Motif {
+    /// One piece attacks two or more pieces it can win.
+    Fork,
+    /// A slider pins a piece to the king or queen behind it.
+    Pin,
+    /// A slider attacks the king or queen with a lesser piece behind it.
+    Skewer,
+    /// A move uncovers an attack by another piece.
+    DiscoveredAttack,
+    /// Mate is forced, or threatened in one if the player passes.
+    MateThreat,
+}
+
+impl Motif {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fork => "fork",
+            Self::Pin => "pin",
+            Self::Skewer => "skewer",
+            Self::DiscoveredAttack => "discovered attack",
+            Self::MateThreat => "mate threat",
+        }
+    }
+}
+
+/// Motifs of the first opponent moves of `pv`, the engine's line from `pos`
+/// (the position right after the blunder). `mated` is whether the engine
+/// already sees mate against the player.
+pub fn find_motifs<'a>(pos: &Chess, pv: impl Iterator<Item = &'a UciMove>, mated: bool) -> Vec<Motif> {
+    let mut found = Vec::new();
+    if mated { found.push(Motif::MateThreat); }
+    let mut pos = pos.clone();
+    for (i, uci) in pv.take(2 * REPLIES - 1).enumerate() {
+        let Ok(mv) = uci.to_move(&pos) else { break };
+        let before = pos.clone();
+        pos.play_unchecked(mv);
+        if i % 2 == 0 {
+            found.extend(motifs_of(&before, mv, &pos));
+        }
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+fn motifs_of(before: &Chess, mv: Move, after: &Chess) -> Vec<Motif> {
+    let us = after.turn();
+    let board = after.board();
+    let ours = board.by_color(us);
+    // A castling move's `to` is the rook's square; the king is the piece that lands
+    let to = mv.castling_side().map_or(mv.to(), |side| side.king_to(us.other()));
+    let piece = board.piece_at(to).expect("moved piece");
+    let mut found = Vec::new();
+    // A piece the player can take for nothing makes no fork, pin or skewer
+    let safe = board.attacks_to(to, us, board.occupied()).is_empty() || board.attacks_to(to, us.other(), board.occupied()).any();
+
+    // Fork: the moved piece attacks two targets it can win
+    let winnable = (board.attacks_from(to) & ours).into_iter()
+        .filter(|&t| {
+            let role = board.role_at(t).expect("occupied");
+            role == Role::King || value(role) > value(piece.role)
+                || (role != Role::Pawn && board.attacks_to(t, us, board.occupied()).is_empty())
+        })
+        .count();
+    if safe && winnable >= 2 { found.push(Motif::Fork); }
+
+    if safe && matches!(piece.role, Role::Bishop | Role::Rook | Role::Queen) {
+        let line = attacks(to, piece, Bitboard::EMPTY);
+        for target in (ours & (board.kings() | board.queens())).into_iter().filter(|&t| line.contains(t)) {
+            let target_value = value(board.role_at(target).expect("occupied"));
+            // Pin: a single lesser piece of ours between the slider and the target
+            let blockers = between(to, target) & board.occupied();
+            if blockers.count() == 1 && (blockers & ours).any()
+                && blockers.first().and_then(|b| board.role_at(b)).is_some_and(|r| value(r) < target_value) {
+                found.push(Motif::Pin);
+            }
+            // Skewer: the target is attacked and a lesser piece of ours stands behind it
+            if blockers.is_empty() {
+                let x_ray = attacks(to, piece, board.occupied().without(target)) & !attacks(to, piece, board.occupied()) & ours;
+                if x_ray.into_iter().any(|b| board.role_at(b).is_some_and(|r| value(r) < target_value)) {
+                    found.push(Motif::Skewer);
+                }
+            }
+        }
+    }
+
+    // Discovered attack: a piece other than the moved one newly attacks a
+    // piece of ours
+    let discovered = (ours & !board.pawns()).into_iter().any(|t| {
+        let now = board.attacks_to(t, us.other(), board.occupied()).without(to);
+        let then = before.board().attacks_to(t, us.other(), before.board().occupied());
+        (now & !then).any()
+    });
+    if discovered { found.push(Motif::DiscoveredAttack); }
+
+    if !after.is_check() && threatens_mate(after) { found.push(Motif::MateThreat); }
+    found
+}
+
+/// Whether the side that just moved would mate in one if it moved again.
+fn threatens_mate(pos: &Chess) -> bool {
+    let Ok(passed) = pos.clone().swap_turn() else { return false };
+    passed.legal_moves().into_iter().any(|m| {
+        let mut next = passed.clone();
+        next.play_unchecked(m);
+        next.is_checkmate()
+    })
+}
+
+/// Pawn units of a piece, the king counted as more than all the others.
+pub fn value(role: Role) -> u32 {
+    match role {
+        Role::Pawn => 1,
+        Role::Knight | Role::Bishop => 3,
+        Role::Rook => 5,
+        Role::Queen => 9,
+        Role::King => 100,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::CastlingMode;
+
+    #[test]
+    fn castling_in_the_refutation() {
+        let fen: Fen = "rnbqk2r/pppp1ppp/5n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 5 4".parse().unwrap();
+        let pos: Chess = fen.into_position(CastlingMode::Standard).unwrap();
+        let pv: Vec<UciMove> = vec!["e8g8".parse().unwrap()];
+        assert_eq!(find_motifs(&pos, pv.iter(), false), Vec::<Motif>::new());
+    }
+}
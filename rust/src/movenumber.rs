@@ -0,0 +1,114 @@
+//! Accuracy split by move number, in buckets of a fixed number of moves
+//! (1–10, 11–20, ...), showing where in a game the player's play drops off.
+
+use crate::accuracy::{Bucket, BucketSplit};
+use crate::analysis::MoveRecord;
+use serde::Serialize;
+
+/// A player's accuracy per bucket of `size` moves: bucket `i` holds moves
+/// `i * size + 1` to `(i + 1) * size`, counted as full moves.
+#[derive(Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct MoveNumberSplit {
+    pub size: usize,
+    /// Up to the last bucket the player moved in; earlier ones may be empty
+    /// (all book moves).
+    pub buckets: Vec<Bucket>,
+}
+
+impl BucketSplit for MoveNumberSplit {
+    const TITLE: &'static str = "Accuracy by Move Number";
+    /// Moves per bucket.
+    type By = usize;
+
+    fn keys(r: &MoveRecord, size: usize) -> Vec<usize> { vec![(r.ply.div_ceil(2) - 1) / size.max(1)] }
+
+    fn bucket_mut(&mut self, key: usize) -> &mut Bucket {
+        if self.buckets.len() <= key { self.buckets.resize(key + 1, Bucket::default()); }
+        &mut self.buckets[key]
+    }
+
+    fn rows(&self) -> Vec<(String, Bucket)> {
+        self.buckets.iter().enumerate().map(|(i, &b)| {
+            let moves = if self.size == 1 { (i + 1).to_string() } else { format!("{}-{}", i * self.size + 1, (i + 1) * self.size) };
+            (moves, b)
+        }).collect()
+    }
+
+    fn empty(size: usize) -> Self { Self { size: size.max(1), buckets: Vec::new() } }
+
+    /// Games split with another bucket size (merged runs) do not match.
+    fn matches(&self, other: &Self) -> bool { self.size == other.size }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::Classification;
+    use crate::engine::{Eval, Pv, Score, Wdl};
+    use shakmaty::Role;
+
+    fn record(ply: usize, accuracy: f64) -> MoveRecord<'static> {
+        MoveRecord {
+            ply,
+            san: "e4",
+            is_white: ply % 2 == 1,
+            eval: Eval { score: Score::Cp(0), wdl: Wdl::default(), best_move: None, second: None, pv: Pv::default() },
+            prob_before: 0.5,
+            prob_after: 0.5,
+            accuracy,
+            classification: Classification::Best,
+            clock: None,
+            in_book: false,
+            scramble: false,
+            endgame: None,
+            material: 0,
+            criticality: None,
+            cp_loss: 0,
+            entropy: 0.0,
+            piece: Role::Pawn,
+            capture: false,
+            check: false,
+            castle: false,
+        }
+    }
+
+    fn records(moves: &[(usize, f64)]) -> Vec<MoveRecord<'static>> {
+        moves.iter().map(|&(ply, accuracy)| record(ply, accuracy)).collect()
+    }
+
+    #[test]
+    fn buckets_hold_full_moves() {
+        // White's moves 1, 10, 11 and 21 are plies 1, 19, 21 and 41; ply 20 is Black's
+        let split = MoveNumberSplit::from_records(&records(&[(1, 100.0), (19, 80.0), (20, 0.0), (21, 60.0), (41, 40.0)]), true, 10).unwrap();
+        assert_eq!(split.buckets, vec![
+            Bucket { moves: 2, accuracy: 90.0 },
+            Bucket { moves: 1, accuracy: 60.0 },
+            Bucket { moves: 1, accuracy: 40.0 },
+        ]);
+        let labels: Vec<String> = split.rows().into_iter().map(|r| r.0).collect();
+        assert_eq!(labels, ["1-10", "11-20", "21-30"]);
+    }
+
+    #[test]
+    fn black_move_ten_is_in_the_first_bucket() {
+        let split = MoveNumberSplit::from_records(&records(&[(20, 50.0), (22, 70.0)]), false, 10).unwrap();
+        assert_eq!(split.buckets, vec![Bucket { moves: 1, accuracy: 50.0 }, Bucket { moves: 1, accuracy: 70.0 }]);
+    }
+
+    #[test]
+    fn buckets_of_one_move_are_labeled_by_the_move() {
+        let split = MoveNumberSplit::from_records(&records(&[(1, 100.0), (5, 50.0)]), true, 1).unwrap();
+        let labels: Vec<String> = split.rows().into_iter().map(|r| r.0).collect();
+        assert_eq!(labels, ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn merge_weighs_by_moves_and_skips_other_sizes() {
+        let a = MoveNumberSplit::from_records(&records(&[(1, 100.0), (3, 100.0)]), true, 10).unwrap();
+        let b = MoveNumberSplit::from_records(&records(&[(1, 70.0), (21, 40.0)]), true, 10).unwrap();
+        let c = MoveNumberSplit::from_records(&records(&[(1, 0.0)]), true, 5).unwrap();
+        let merged = MoveNumberSplit::merge([&a, &b, &c]).unwrap();
+        assert_eq!(merged.size, 10);
+        assert_eq!(merged.buckets, vec![Bucket { moves: 3, accuracy: 90.0 }, Bucket { moves: 1, accuracy: 40.0 }]);
+    }
+}
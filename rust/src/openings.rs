@@ -0,0 +1,161 @@
+//! Opening statistics derived from the analyzed games.
+
+use crate::accuracy::mean;
+use crate::report::{GameResult, Side};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Where a game left the reference database.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Novelty {
+    /// 1-based ply of the first move not in the reference.
+    pub ply: usize,
+    pub by: Side,
+}
+
+impl Novelty {
+    /// From the number of leading plies found in the reference; `None` when
+    /// the whole game stayed in theory.
+    pub fn from_depth(depth: usize, total_plies: usize) -> Option<Self> {
+        (depth < total_plies).then(|| Novelty {
+            ply: depth + 1,
+            by: if depth.is_multiple_of(2) { Side::White } else { Side::Black },
+        })
+    }
+
+    pub fn theory_depth(&self) -> usize { self.ply - 1 }
+}
+
+#[derive(Serialize, Debug)]
+pub struct OpeningDepth {
+    pub opening: String,
+    pub games: usize,
+    pub avg_depth: f64,
+}
+
+/// Average theory depth (plies) per color of the player and per opening.
+#[derive(Serialize, Debug)]
+pub struct TheoryStats {
+    pub games: usize,
+    pub avg_depth_as_white: Option<f64>,
+    pub avg_depth_as_black: Option<f64>,
+    /// Games in which the player, not the opponent, played the first new move.
+    pub player_deviated: usize,
+    pub by_opening: Vec<OpeningDepth>,
+}
+
+impl TheoryStats {
+    pub fn from_games(games: &[GameResult], target: &str) -> Option<Self> {
+        let with: Vec<(&GameResult, usize)> = games.iter()
+            .filter(|g| g.theory_checked)
+            .map(|g| (g, g.novelty.map_or(g.moves, |n| n.theory_depth())))
+            .collect();
+        if with.is_empty() { return None; }
+
+        let avg = |side: Side| {
+            let d: Vec<f64> = with.iter().filter(|(g, _)| g.side_of(target) == side).map(|&(_, d)| d as f64).collect();
+            (!d.is_empty()).then(|| mean(&d))
+        };
+        let mut openings: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+        for (g, d) in &with {
+            openings.entry(g.opening.as_deref().unwrap_or("?")).or_default().push(*d as f64);
+        }
+        let mut by_opening: Vec<OpeningDepth> = openings.into_iter()
+            .map(|(o, d)| OpeningDepth { opening: o.to_string(), games: d.len(), avg_depth: mean(&d) })
+            .collect();
+        by_opening.sort_by(|a, b| b.games.cmp(&a.games).then_with(|| a.opening.cmp(&b.opening)));
+
+        Some(Self {
+            games: with.len(),
+            avg_depth_as_white: avg(Side::White),
+            avg_depth_as_black: avg(Side::Black),
+            player_deviated: with.iter().filter(|(g, _)| g.novelty.is_some_and(|n| n.by == g.side_of(target))).count(),
+            by_opening,
+        })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nTheory Depth")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        let fmt = |d: Option<f64>| d.map_or("-".to_string(), |d| format!("{:.1} plies", d));
+        writeln!(out, "As White: {}", fmt(self.avg_depth_as_white))?;
+        writeln!(out, "As Black: {}", fmt(self.avg_depth_as_black))?;
+        writeln!(out, "Player deviated first: {}/{} games", self.player_deviated, self.games)?;
+        writeln!(out, "{:<30} {:>6} {:>10}", "Opening", "Games", "Avg depth")?;
+        for o in &self.by_opening {
+            writeln!(out, "{:<30} {:>6} {:>10.1}", o.opening, o.games, o.avg_depth)?;
+        }
+        Ok(())
+    }
+}
+
+/// How often the player reached one opening line with one color.
+#[derive(Serialize, Debug)]
+pub struct RepertoireLine {
+    pub side: Side,
+    /// The first plies of the game in SAN.
+    pub line: String,
+    /// Most common ECO/opening tag among the games of this line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening: Option<String>,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub avg_accuracy: f64,
+}
+
+/// The player's opening lines per color, most played first.
+#[derive(Serialize, Debug)]
+pub struct Repertoire {
+    pub lines: Vec<RepertoireLine>,
+    /// Lines listed per color in the text report.
+    #[serde(skip)]
+    pub text_lines: usize,
+}
+
+/// Default for [`Repertoire::text_lines`].
+pub const TEXT_LINES: usize = 10;
+
+impl Repertoire {
+    pub fn from_games(games: &[GameResult], target: &str) -> Option<Self> {
+        let mut groups: BTreeMap<(Side, &str), Vec<&GameResult>> = BTreeMap::new();
+        for g in games {
+            if let Some(line) = &g.line { groups.entry((g.side_of(target), line)).or_default().push(g); }
+        }
+        if groups.is_empty() { return None; }
+
+        let mut lines: Vec<RepertoireLine> = groups.into_iter().map(|((side, line), gs)| {
+            let mut tags: BTreeMap<&str, usize> = BTreeMap::new();
+            for o in gs.iter().filter_map(|g| g.opening.as_deref()) { *tags.entry(o).or_default() += 1; }
+            let scores: Vec<f64> = gs.iter().filter_map(|g| g.score_for(target)).collect();
+            let acc: Vec<f64> = gs.iter().map(|g| g.accuracy_for(target)).collect();
+            RepertoireLine {
+                side,
+                line: line.to_string(),
+                opening: tags.into_iter().max_by_key(|&(_, n)| n).map(|(o, _)| o.to_string()),
+                games: gs.len(),
+                wins: scores.iter().filter(|&&s| s == 1.0).count(),
+                draws: scores.iter().filter(|&&s| s == 0.5).count(),
+                losses: scores.iter().filter(|&&s| s == 0.0).count(),
+                avg_accuracy: mean(&acc),
+            }
+        }).collect();
+        lines.sort_by(|a, b| a.side.cmp(&b.side).then(b.games.cmp(&a.games)).then_with(|| a.line.cmp(&b.line)));
+        Some(Self { lines, text_lines: TEXT_LINES })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        for side in [Side::White, Side::Black] {
+            writeln!(out, "\nRepertoire as {:?}", side)?;
+            writeln!(out, "{}", "=".repeat(50))?;
+            writeln!(out, "{:<36} {:<6} {:>5} {:>11} {:>8}", "Line", "ECO", "Games", "+/=/-", "Accuracy")?;
+            for l in self.lines.iter().filter(|l| l.side == side).take(self.text_lines) {
+                writeln!(out, "{:<36} {:<6} {:>5} {:>11} {:>7.1}%", l.line, l.opening.as_deref().unwrap_or("-"),
+                    l.games, format!("{}/{}/{}", l.wins, l.draws, l.losses), l.avg_accuracy)?;
+            }
+        }
+        Ok(())
+    }
+}
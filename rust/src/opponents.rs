@@ -0,0 +1,72 @@
+//! The player's record against each frequent opponent: a rivalry report.
+
+use crate::accuracy::mean;
+use crate::report::GameResult;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+#[derive(Serialize, Debug)]
+pub struct OpponentRow {
+    pub opponent: String,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// The player's mean score over the finished games.
+    pub score: Option<f64>,
+    pub accuracy: f64,
+    pub opponent_accuracy: f64,
+    /// The player's blunders per 100 of their moves.
+    pub blunder_rate: f64,
+}
+
+/// Opponents met in at least `min_games` games, most games first.
+#[derive(Serialize, Debug)]
+pub struct OpponentStats {
+    pub min_games: usize,
+    pub rows: Vec<OpponentRow>,
+}
+
+impl OpponentStats {
+    pub fn from_games(games: &[GameResult], target: &str, min_games: usize) -> Option<Self> {
+        let mut by_opponent: HashMap<&str, Vec<&GameResult>> = HashMap::new();
+        for g in games {
+            let opponent = if g.white == target { &g.black } else { &g.white };
+            by_opponent.entry(opponent).or_default().push(g);
+        }
+        let mut rows: Vec<OpponentRow> = by_opponent.into_iter().filter(|(_, g)| g.len() >= min_games.max(1)).map(|(opponent, group)| {
+            let count = |s: f64| group.iter().filter(|g| g.score_for(target) == Some(s)).count();
+            let scores: Vec<f64> = group.iter().filter_map(|g| g.score_for(target)).collect();
+            let moves: usize = group.iter().map(|g| g.moves_for(target)).sum();
+            let blunders: usize = group.iter().map(|g| g.blunders_for(target)).sum();
+            OpponentRow {
+                opponent: opponent.to_string(),
+                games: group.len(),
+                wins: count(1.0),
+                draws: count(0.5),
+                losses: count(0.0),
+                score: (!scores.is_empty()).then(|| mean(&scores)),
+                accuracy: mean(&group.iter().map(|g| g.accuracy_for(target)).collect::<Vec<_>>()),
+                opponent_accuracy: mean(&group.iter().map(|g| g.accuracy_for_opponent(target)).collect::<Vec<_>>()),
+                blunder_rate: if moves == 0 { 0.0 } else { 100.0 * blunders as f64 / moves as f64 },
+            }
+        }).collect();
+        if rows.is_empty() { return None; }
+        rows.sort_by(|a, b| b.games.cmp(&a.games).then_with(|| a.opponent.cmp(&b.opponent)));
+        Some(Self { min_games, rows })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nOpponents ({}+ games)", self.min_games)?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        let width = self.rows.iter().map(|r| r.opponent.len()).max().unwrap_or(0).max(8);
+        writeln!(out, "{:<w$} {:>6} {:>11} {:>6} {:>9} {:>9} {:>9}", "Opponent", "Games", "W/D/L", "Score", "Accuracy", "Opponent", "Blun/100", w = width)?;
+        for r in &self.rows {
+            let score = r.score.map_or("-".to_string(), |s| format!("{:.0}%", 100.0 * s));
+            writeln!(out, "{:<w$} {:>6} {:>11} {:>6} {:>8.2}% {:>8.2}% {:>9.2}", r.opponent, r.games,
+                format!("{}/{}/{}", r.wins, r.draws, r.losses), score, r.accuracy, r.opponent_accuracy, r.blunder_rate, w = width)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,435 @@
+use memchr::{memchr, memchr3};
+use serde::{Deserialize, Serialize};
+
+/// SAN tokens of a game's mainline, borrowed from the PGN without copying.
+///
+/// Tag lines, `{...}` and `;` comments, `%` escape lines, NAGs, move
+/// numbers, `(...)` variations and the result are skipped, and moves come
+/// without their `!`/`?` annotation (see [`split_annotation`]); comments and
+/// tag lines are jumped over with memchr.
+pub struct MoveTokens<'a> {
+    text: &'a str,
+    pos: usize,
+    /// Variations open at `pos`.
+    depth: usize,
+}
+
+/// A movetext token: a move, or the start or end of a variation.
+enum Token<'a> {
+    Move(&'a str),
+    Open,
+    Close,
+}
+
+impl<'a> MoveTokens<'a> {
+    pub fn new(pgn: &'a str) -> Self { Self { text: pgn, pos: 0, depth: 0 } }
+
+    /// Index just past the next `byte` at or after `from`, or the end of the text.
+    #[inline]
+    fn skip_past(&self, from: usize, byte: u8) -> usize {
+        memchr(byte, &self.text.as_bytes()[from..]).map_or(self.text.len(), |i| from + i + 1)
+    }
+
+    /// The next move or variation bracket, of the mainline or any variation.
+    fn token(&mut self) -> Option<Token<'a>> {
+        let bytes = self.text.as_bytes();
+        while self.pos < bytes.len() {
+            match bytes[self.pos] {
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                b'{' => self.pos = self.skip_past(self.pos, b'}'),
+                b'[' | b';' => self.pos = self.skip_past(self.pos, b'\n'),
+                // An escape only in the first column
+                b'%' if self.pos == 0 || bytes[self.pos - 1] == b'\n' => self.pos = self.skip_past(self.pos, b'\n'),
+                b'(' => {
+                    self.pos += 1;
+                    return Some(Token::Open);
+                }
+                b')' => {
+                    self.pos += 1;
+                    return Some(Token::Close);
+                }
+                _ => {
+                    let start = self.pos;
+                    let rest = &bytes[start..];
+                    // Tokens are short; find the nearest delimiter, then trim at other whitespace and brackets
+                    let mut end = start + memchr3(b' ', b'\n', b'{', rest).unwrap_or(rest.len());
+                    if let Some(ws) = bytes[start..end].iter().position(|&b| b.is_ascii_whitespace() || b == b'(' || b == b')') { end = start + ws; }
+                    self.pos = end;
+                    let token = &self.text[start..end];
+                    // "12." / "12..." alone, or glued to the move as in "12.Nf3"
+                    let token = match token.find('.') {
+                        Some(dot) if token[..dot].bytes().all(|b| b.is_ascii_digit()) => token[dot..].trim_start_matches('.'),
+                        _ => token,
+                    };
+                    if token.is_empty() || token.starts_with('$') || is_result(token) { continue; }
+                    return Some(Token::Move(split_annotation(token).0));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for MoveTokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            match self.token()? {
+                Token::Move(m) if self.depth == 0 => return Some(m),
+                Token::Move(_) => {}
+                Token::Open => self.depth += 1,
+                Token::Close => self.depth = self.depth.saturating_sub(1),
+            }
+        }
+    }
+}
+
+/// A move token without its `!`/`?` annotation, and the annotation as a
+/// NAG: `!` $1, `?` $2, `!!` $3, `??` $4, `!?` $5, `?!` $6 (none for other
+/// runs of marks).
+pub fn split_annotation(token: &str) -> (&str, Option<u8>) {
+    let san = token.trim_end_matches(['!', '?']);
+    let nag = match &token[san.len()..] {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    };
+    (san, nag)
+}
+
+#[inline]
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+/// SAN moves of a game, in order.
+pub fn parse_pgn_moves(pgn: &str) -> Vec<&str> {
+    let mut moves = Vec::with_capacity(100);
+    moves.extend(MoveTokens::new(pgn));
+    moves
+}
+
+/// A side line of a game: `moves` is the whole line from the initial
+/// position, of which the first `branch` plies are shared with the line it
+/// branches off.
+#[derive(Debug)]
+pub struct Variation<'a> {
+    pub moves: Vec<&'a str>,
+    pub branch: usize,
+}
+
+/// The `(...)` variations of a game, nested ones included, in the order
+/// they open. Each replaces the move just before it; empty ones are left out.
+pub fn parse_variations(pgn: &str) -> Vec<Variation<'_>> {
+    fn close<'a>(open: &mut Vec<(usize, Vec<&'a str>)>, variations: &mut [Variation<'a>]) {
+        if open.len() > 1 {
+            let (slot, moves) = open.pop().expect("checked above");
+            variations[slot].moves = moves;
+        }
+    }
+
+    let mut tokens = MoveTokens::new(pgn);
+    let mut variations: Vec<Variation> = Vec::new();
+    // The mainline, then each open variation with its slot in `variations`
+    let mut open: Vec<(usize, Vec<&str>)> = vec![(usize::MAX, Vec::new())];
+    while let Some(token) = tokens.token() {
+        match token {
+            Token::Move(m) => open.last_mut().expect("the mainline stays open").1.push(m),
+            Token::Open => {
+                let parent = &open.last().expect("the mainline stays open").1;
+                let moves = parent[..parent.len().saturating_sub(1)].to_vec();
+                variations.push(Variation { moves: Vec::new(), branch: moves.len() });
+                open.push((variations.len() - 1, moves));
+            }
+            Token::Close => close(&mut open, &mut variations),
+        }
+    }
+    // Unbalanced movetext: variations left open end with it
+    while open.len() > 1 { close(&mut open, &mut variations); }
+    variations.retain(|v| v.moves.len() > v.branch);
+    variations
+}
+
+/// Remaining clock after each move, in seconds, from `{[%clk 0:02:59.9]}`
+/// comments; `None` unless every move has one.
+pub fn move_clocks(pgn: &str) -> Option<Vec<f64>> {
+    let mut tokens = MoveTokens::new(pgn);
+    let mut clocks = Vec::new();
+    while tokens.next().is_some() {
+        let rest = pgn[tokens.pos..].trim_start();
+        let comment = rest.strip_prefix('{').and_then(|c| c.split_once('}')).map(|(c, _)| c)?;
+        let clk = comment.split_once("[%clk ")?.1;
+        let clk = &clk[..clk.find(']')?];
+        let mut secs = 0.0;
+        for part in clk.trim().split(':') { secs = secs * 60.0 + part.parse::<f64>().ok()?; }
+        clocks.push(secs);
+    }
+    (!clocks.is_empty()).then_some(clocks)
+}
+
+/// Splits a multi-game PGN into one slice per game. A game ends where a tag
+/// line follows movetext.
+pub fn split_games(text: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut seen_moves = false;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && seen_moves {
+            games.push(&text[start..offset]);
+            start = offset;
+            seen_moves = false;
+        } else if !trimmed.is_empty() && !trimmed.starts_with('[') {
+            seen_moves = true;
+        }
+        offset += line.len();
+    }
+    if text[start..].lines().any(|l| !l.trim().is_empty()) { games.push(&text[start..]); }
+    games
+}
+
+/// Games of a PGN stream, one at a time, split as [`split_games`] does, so
+/// files larger than memory can be processed.
+pub struct GameReader<R> {
+    input: R,
+    /// Tag line read past the end of the previous game.
+    pending: Option<String>,
+}
+
+impl<R: std::io::BufRead> GameReader<R> {
+    pub fn new(input: R) -> Self { Self { input, pending: None } }
+}
+
+impl<R: std::io::BufRead> Iterator for GameReader<R> {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut game = self.pending.take().unwrap_or_default();
+        let mut seen_moves = false;
+        loop {
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Err(e) => return Some(Err(e)),
+                Ok(0) => return game.lines().any(|l| !l.trim().is_empty()).then_some(Ok(game)),
+                Ok(_) => {}
+            }
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && seen_moves {
+                self.pending = Some(line);
+                return Some(Ok(game));
+            }
+            if !trimmed.is_empty() && !trimmed.starts_with('[') { seen_moves = true; }
+            game.push_str(&line);
+        }
+    }
+}
+
+/// Value of the first `[Name "Value"]` tag pair, if present.
+pub fn tag_value<'a>(pgn: &'a str, name: &str) -> Option<&'a str> {
+    pgn.lines().map(str::trim).filter(|l| l.starts_with('[')).find_map(|l| {
+        let rest = l[1..].strip_prefix(name)?.trim_start();
+        let value = rest.strip_prefix('"')?;
+        Some(&value[..value.rfind('"')?])
+    })
+}
+
+/// The standard tag pairs of a game, parsed in one pass over its tag section.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GameHeaders {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site: Option<String>,
+    /// "2024.01.31" as written in the tag.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// "Normal", "Time forfeit", "abandoned", or chess.com's
+    /// "hikaru won by resignation".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub termination: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_control: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eco: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening: Option<String>,
+    /// chess.com's opening page, e.g. ".../openings/Sicilian-Defense-Najdorf".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eco_url: Option<String>,
+    /// FIDE or Lichess title ("GM", "BOT"), from the WhiteTitle/BlackTitle tags.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white_elo: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black_elo: Option<u32>,
+}
+
+impl GameHeaders {
+    pub fn parse(pgn: &str) -> Self {
+        let mut h = Self::default();
+        for line in pgn.lines().map(str::trim) {
+            if !line.starts_with('[') {
+                if line.is_empty() { continue; } else { break; }
+            }
+            let Some((name, rest)) = line[1..].split_once(char::is_whitespace) else { continue };
+            let Some(value) = rest.trim_start().strip_prefix('"').and_then(|v| Some(&v[..v.rfind('"')?])) else { continue };
+            let slot = match name {
+                "Event" => &mut h.event,
+                "Site" => &mut h.site,
+                "Date" => &mut h.date,
+                "White" => &mut h.white,
+                "Black" => &mut h.black,
+                "Result" => &mut h.result,
+                "Termination" => &mut h.termination,
+                "TimeControl" => &mut h.time_control,
+                "ECO" => &mut h.eco,
+                "Opening" => &mut h.opening,
+                "ECOUrl" => &mut h.eco_url,
+                "WhiteTitle" => &mut h.white_title,
+                "BlackTitle" => &mut h.black_title,
+                "WhiteElo" => { h.white_elo = h.white_elo.or(value.parse().ok()); continue; }
+                "BlackElo" => { h.black_elo = h.black_elo.or(value.parse().ok()); continue; }
+                _ => continue,
+            };
+            // The first occurrence wins, as with tag_value
+            if slot.is_none() { *slot = Some(value.to_string()); }
+        }
+        h
+    }
+
+    pub fn is_empty(&self) -> bool { *self == Self::default() }
+
+    /// The Opening tag (Lichess), or the name in chess.com's ECOUrl.
+    pub fn opening_name(&self) -> Option<String> {
+        self.opening.clone().or_else(|| {
+            let page = self.eco_url.as_deref()?.rsplit('/').next()?;
+            Some(page.replace('-', " "))
+        })
+    }
+}
+
+/// Why a game is not worth analyzing: abandoned, unfinished (`*`), or over
+/// before the second move.
+pub fn aborted_reason(headers: &GameHeaders, moves: usize) -> Option<&'static str> {
+    if headers.termination.as_deref().is_some_and(|t| t.to_lowercase().contains("abandon")) { return Some("abandoned"); }
+    if headers.result.as_deref() == Some("*") { return Some("unfinished"); }
+    if moves <= 1 { return Some("one move or fewer"); }
+    None
+}
+
+/// Unix end time of a game from its EndDate/EndTime tags (chess.com), or its
+/// UTCDate/UTCTime start when those are missing.
+pub fn end_time(pgn: &str) -> Option<i64> {
+    let pair = |d, t| crate::date::parse_pgn_datetime(tag_value(pgn, d)?, tag_value(pgn, t)?);
+    pair("EndDate", "EndTime").or_else(|| pair("UTCDate", "UTCTime"))
+}
+
+/// Base and increment, in seconds, of a "180+2" style TimeControl; `None`
+/// for daily ("1/86400") and untimed games.
+pub fn parse_time_control(tc: &str) -> Option<(f64, f64)> {
+    if tc.contains('/') { return None; }
+    let (base, inc) = tc.split_once('+').unwrap_or((tc, "0"));
+    Some((base.parse().ok()?, inc.parse().ok()?))
+}
+
+/// Speed category of a PGN TimeControl ("180+2", "1/86400"), by estimated
+/// game length (base + 40 × increment) as Lichess defines it.
+pub fn time_class(time_control: &str) -> Option<&'static str> {
+    if time_control.contains('/') { return Some("daily"); }
+    let (base, inc) = parse_time_control(time_control)?;
+    Some(match (base + 40.0 * inc) as u32 {
+        0..180 => "bullet",
+        180..480 => "blitz",
+        480..1500 => "rapid",
+        _ => "classical",
+    })
+}
+
+/// The game's tags followed by its moves annotated with the engine eval
+/// (`[%eval]`, White's point of view) and ?!/?/?? for inaccuracies,
+/// mistakes and blunders.
+pub fn annotate(pgn: &str, records: &[crate::analysis::MoveRecord]) -> String {
+    use crate::accuracy::Classification;
+    use crate::engine::Score;
+    use std::fmt::Write;
+
+    let mut out = String::with_capacity(pgn.len() * 2);
+    for tag in pgn.lines().map(str::trim).filter(|l| l.starts_with('[')) {
+        out.push_str(tag);
+        out.push('\n');
+    }
+    out.push('\n');
+    for r in records {
+        if r.is_white { let _ = write!(out, "{}. ", r.ply.div_ceil(2)); }
+        else if r.ply == 1 { out.push_str("1... "); }
+        out.push_str(r.san);
+        out.push_str(match r.classification {
+            Classification::Inaccuracy if !r.in_book => "?!",
+            Classification::Mistake if !r.in_book => "?",
+            Classification::Blunder if !r.in_book => "??",
+            _ => "",
+        });
+        let eval = match r.eval.score {
+            Score::Cp(cp) => format!("{:.2}", cp as f64 / 100.0),
+            Score::Mate(n) => format!("#{}", n),
+        };
+        let _ = write!(out, " {{ [%eval {}] }} ", eval);
+    }
+    out.push_str(tag_value(pgn, "Result").unwrap_or("*"));
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANNOTATED: &str = "[Event \"Study\"]\n\n\
+        1. e4! $14 e5 2. Nf3 {a comment} Nc6?! (2... d6!? 3. d4 (3. Bc4 Be7?) exd4) 3. Bb5!! a6?? $4\n\
+        % an escape line 4. h4 h5\n\
+        4. Ba4 Nf6 *\n";
+
+    #[test]
+    fn tokens_come_without_annotations() {
+        assert_eq!(parse_pgn_moves(ANNOTATED), ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6"]);
+    }
+
+    #[test]
+    fn percent_escapes_only_in_the_first_column() {
+        // Elsewhere it is no escape: the moves after it on the line stay
+        assert_eq!(parse_pgn_moves("1. e4 e5 %\n2. Nf3 Nc6 % 3. Bb5 *"), ["e4", "e5", "%", "Nf3", "Nc6", "%", "Bb5"]);
+    }
+
+    #[test]
+    fn variations_nest() {
+        let lines: Vec<(Vec<&str>, usize)> = parse_variations(ANNOTATED).into_iter().map(|v| (v.moves, v.branch)).collect();
+        assert_eq!(lines, [
+            (vec!["e4", "e5", "Nf3", "d6", "d4", "exd4"], 3),
+            (vec!["e4", "e5", "Nf3", "d6", "Bc4", "Be7"], 4),
+        ]);
+    }
+
+    #[test]
+    fn annotations_map_to_nags() {
+        assert_eq!(split_annotation("Qxb7??"), ("Qxb7", Some(4)));
+        assert_eq!(split_annotation("e8=Q+!"), ("e8=Q+", Some(1)));
+        assert_eq!(split_annotation("O-O"), ("O-O", None));
+        assert_eq!(split_annotation("Nf3!!?"), ("Nf3", None));
+    }
+}
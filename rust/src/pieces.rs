@@ -0,0 +1,72 @@
+//! Accuracy split by the piece moved, and for captures, checks and castling.
+
+use crate::accuracy::{Bucket, BucketSplit};
+use crate::analysis::MoveRecord;
+use serde::Serialize;
+use shakmaty::Role;
+
+/// A player's accuracy per piece moved and per kind of move. The piece
+/// buckets cover every move; a capture with check counts in both of the
+/// move-kind buckets.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct PieceSplit {
+    pub pawn: Bucket,
+    pub knight: Bucket,
+    pub bishop: Bucket,
+    pub rook: Bucket,
+    pub queen: Bucket,
+    /// King moves other than castling.
+    pub king: Bucket,
+    pub captures: Bucket,
+    pub checks: Bucket,
+    pub castling: Bucket,
+}
+
+impl BucketSplit for PieceSplit {
+    const TITLE: &'static str = "Accuracy by Piece";
+    type By = ();
+
+    /// The piece (castling apart from the king), then capture and check.
+    fn keys(r: &MoveRecord, _: ()) -> Vec<usize> {
+        let piece = match r.piece {
+            Role::Pawn => 0,
+            Role::Knight => 1,
+            Role::Bishop => 2,
+            Role::Rook => 3,
+            Role::Queen => 4,
+            Role::King => 5,
+        };
+        let mut keys = vec![if r.castle { 8 } else { piece }];
+        if r.capture { keys.push(6); }
+        if r.check { keys.push(7); }
+        keys
+    }
+
+    fn bucket_mut(&mut self, key: usize) -> &mut Bucket {
+        match key {
+            0 => &mut self.pawn,
+            1 => &mut self.knight,
+            2 => &mut self.bishop,
+            3 => &mut self.rook,
+            4 => &mut self.queen,
+            5 => &mut self.king,
+            6 => &mut self.captures,
+            7 => &mut self.checks,
+            _ => &mut self.castling,
+        }
+    }
+
+    fn rows(&self) -> Vec<(String, Bucket)> {
+        [
+            ("Pawn", self.pawn),
+            ("Knight", self.knight),
+            ("Bishop", self.bishop),
+            ("Rook", self.rook),
+            ("Queen", self.queen),
+            ("King", self.king),
+            ("Capture", self.captures),
+            ("Check", self.checks),
+            ("Castling", self.castling),
+        ].into_iter().map(|(name, b)| (name.to_string(), b)).collect()
+    }
+}
@@ -0,0 +1,139 @@
+//! Polyglot opening books (.bin) built from a player's games, readable by
+//! most GUIs and engines.
+//!
+//! A book is a sorted list of 16-byte big-endian entries: the position's
+//! Polyglot hash (shakmaty's `Zobrist64`), the move, its weight and an unused
+//! learn field.
+
+use crate::evalstore::EvalStore;
+use shakmaty::san::San;
+use shakmaty::{Chess, Color, Move, Position};
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Move in Polyglot's encoding: to-square, from-square and promotion piece
+/// in 6 + 6 + 3 bits; castling is the king taking its own rook.
+pub fn encode_move(m: Move) -> u16 {
+    let from = m.from().map_or(0, |s| s as u16);
+    let promotion = m.promotion().map_or(0, |r| r as u16 - 1);
+    m.to() as u16 | from << 6 | promotion << 12
+}
+
+/// Games and results behind one book move.
+#[derive(Default, Clone, Copy)]
+struct MoveStats {
+    games: u32,
+    wins: u32,
+    draws: u32,
+}
+
+impl MoveStats {
+    /// Each game counts 1, plus 1 for a draw and 2 for a win, so moves that
+    /// scored well and moves played often both rank high.
+    fn weight(self) -> u32 { self.games + self.draws + 2 * self.wins }
+}
+
+/// Collects the player's moves, per position, from their games.
+#[derive(Default)]
+pub struct BookBuilder {
+    moves: HashMap<(u64, u16), MoveStats>,
+    pub games: usize,
+}
+
+impl BookBuilder {
+    pub fn new() -> Self { Self::default() }
+
+    /// Adds the moves `color` played in the first `max_ply` plies, with the
+    /// game's score for that side (1, 0.5 or 0, if known). The game stops at
+    /// its first unplayable move.
+    pub fn add_game(&mut self, moves: &[&str], color: Color, score: Option<f64>, max_ply: usize) {
+        let mut pos = Chess::default();
+        for san in moves.iter().take(max_ply) {
+            let Some(mv) = san.parse::<San>().ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+            if pos.turn() == color {
+                let s = self.moves.entry((EvalStore::key(&pos), encode_move(mv))).or_default();
+                s.games += 1;
+                match score {
+                    Some(1.0) => s.wins += 1,
+                    Some(0.5) => s.draws += 1,
+                    _ => {}
+                }
+            }
+            pos.play_unchecked(mv);
+        }
+        self.games += 1;
+    }
+
+    pub fn is_empty(&self) -> bool { self.moves.is_empty() }
+
+    /// Writes the book, keeping moves played in at least `min_games` games.
+    /// Weights are scaled down per position when they exceed 16 bits.
+    /// Returns the positions and entries written.
+    pub fn write(&self, out: &mut dyn Write, min_games: u32) -> io::Result<(usize, usize)> {
+        let mut by_position: HashMap<u64, Vec<(u16, u32)>> = HashMap::new();
+        for (&(key, mv), s) in self.moves.iter().filter(|(_, s)| s.games >= min_games) {
+            by_position.entry(key).or_default().push((mv, s.weight()));
+        }
+        let mut keys: Vec<u64> = by_position.keys().copied().collect();
+        keys.sort_unstable();
+        let mut written = 0;
+        for &key in &keys {
+            let mut moves = by_position.remove(&key).unwrap_or_default();
+            moves.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            let max = moves.first().map_or(1, |m| m.1).max(1);
+            for (mv, weight) in moves {
+                let weight = if max > u16::MAX as u32 { (weight as u64 * u16::MAX as u64 / max as u64).max(1) as u16 } else { weight as u16 };
+                out.write_all(&key.to_be_bytes())?;
+                out.write_all(&mv.to_be_bytes())?;
+                out.write_all(&weight.to_be_bytes())?;
+                out.write_all(&0u32.to_be_bytes())?;
+                written += 1;
+            }
+        }
+        Ok((keys.len(), written))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::uci::UciMove;
+    use shakmaty::CastlingMode;
+
+    fn position(fen: &str) -> Chess {
+        fen.parse::<Fen>().unwrap().into_position(CastlingMode::Standard).unwrap()
+    }
+
+    fn uci(pos: &Chess, uci: &str) -> Move { uci.parse::<UciMove>().unwrap().to_move(pos).unwrap() }
+
+    fn encode(pos: &Chess, m: &str) -> u16 { encode_move(uci(pos, m)) }
+
+    #[test]
+    fn keys_are_polyglot_hashes() {
+        // From the Polyglot book format description
+        let start = Chess::default();
+        assert_eq!(EvalStore::key(&start), 0x463b96181691fc9c);
+        let e4 = start.clone().play(uci(&start, "e2e4")).unwrap();
+        assert_eq!(EvalStore::key(&e4), 0x823c9b50fd114196);
+    }
+
+    #[test]
+    fn moves_are_to_from_and_promotion() {
+        assert_eq!(encode(&Chess::default(), "e2e4"), 28 | 12 << 6);
+        let promotion = position("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+        assert_eq!(encode(&promotion, "e7e8q"), 60 | 52 << 6 | 4 << 12);
+        assert_eq!(encode(&promotion, "e7e8n"), 60 | 52 << 6 | 1 << 12);
+    }
+
+    #[test]
+    fn castling_is_the_king_taking_its_rook() {
+        let pos = position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert_eq!(encode(&pos, "e1h1"), 7 | 4 << 6);
+        // The same move as a GUI sends it
+        assert_eq!(encode(&pos, "e1g1"), 7 | 4 << 6);
+        assert_eq!(encode(&pos, "e1c1"), 4 << 6);
+        let black = position("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert_eq!(encode(&black, "e8g8"), 63 | 60 << 6);
+    }
+}
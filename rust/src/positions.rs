@@ -0,0 +1,202 @@
+//! Positions of a PGN collection: every one as FEN, for engine test sets and
+//! other tools, and the ones a player reaches again and again.
+
+use crate::evalstore::EvalStore;
+use crate::pgn::{parse_pgn_moves, GameReader};
+use crate::report::Side;
+use shakmaty::fen::Fen;
+use shakmaty::san::San;
+use shakmaty::{Chess, Color, EnPassantMode, Position};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
+
+/// Which positions are written.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PositionFilter {
+    /// Each position once, however many games reach it (move counters aside).
+    pub unique: bool,
+    /// Only positions with this side to move.
+    pub side: Option<Side>,
+}
+
+/// Counts of a dump.
+#[derive(Debug, Default)]
+pub struct PositionStats {
+    pub games: usize,
+    /// Positions reached, before filtering.
+    pub positions: usize,
+    pub written: usize,
+    /// Games cut short at an unplayable move.
+    pub truncated: usize,
+}
+
+/// Writes the FEN of the start position and of the position after every
+/// move of each game in `input`, one per line. A game stops at its first
+/// unplayable move.
+pub fn write_positions(input: impl BufRead, filter: PositionFilter, out: &mut dyn Write) -> io::Result<PositionStats> {
+    let mut stats = PositionStats::default();
+    let mut seen = HashSet::new();
+    for game in GameReader::new(input) {
+        let game = game?;
+        stats.games += 1;
+        let mut pos = Chess::default();
+        let mut emit = |pos: &Chess, stats: &mut PositionStats| -> io::Result<()> {
+            stats.positions += 1;
+            let side = if pos.turn() == Color::White { Side::White } else { Side::Black };
+            if filter.side.is_some_and(|s| s != side) { return Ok(()); }
+            if filter.unique && !seen.insert(EvalStore::key(pos)) { return Ok(()); }
+            stats.written += 1;
+            writeln!(out, "{}", Fen::from_position(pos, EnPassantMode::Legal))
+        };
+        emit(&pos, &mut stats)?;
+        for san in parse_pgn_moves(&game) {
+            let Some(mv) = san.parse::<San>().ok().and_then(|s| s.to_move(&pos).ok()) else {
+                stats.truncated += 1;
+                break;
+            };
+            pos.play_unchecked(mv);
+            emit(&pos, &mut stats)?;
+        }
+    }
+    Ok(stats)
+}
+
+/// Games that reached one position, and how the player fared in them.
+#[derive(Default)]
+struct Tally {
+    /// Input index of the first game that reached it, which gives the FEN
+    /// counters and line, whatever order the games were analyzed in.
+    first: usize,
+    fen: String,
+    /// Moves of the first game that reached it.
+    line: String,
+    ply: usize,
+    games: usize,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    /// The player's accuracy in each game, by input index, summed in input
+    /// order so that the mean does not depend on the order of the workers.
+    accuracy: Vec<(usize, f64)>,
+}
+
+/// Counts, across the player's games, the games reaching each position.
+/// Shared by the workers of a run.
+pub struct PositionTally {
+    min_ply: usize,
+    positions: Mutex<HashMap<u64, Tally>>,
+}
+
+impl PositionTally {
+    /// Counts positions from ply `min_ply` on, leaving out the openings every
+    /// game shares.
+    pub fn new(min_ply: usize) -> Self { Self { min_ply, positions: Mutex::new(HashMap::new()) } }
+
+    /// Adds the distinct positions of game `index` (its place in the input)
+    /// with the player's score (1, 0.5 or 0, if known) and accuracy in it.
+    pub fn add_game(&self, index: usize, moves: &[&str], score: Option<f64>, accuracy: f64) {
+        let mut pos = Chess::default();
+        let mut reached = Vec::with_capacity(moves.len());
+        for (i, san) in moves.iter().enumerate() {
+            let Some(mv) = san.parse::<San>().ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+            pos.play_unchecked(mv);
+            if i + 1 >= self.min_ply { reached.push((EvalStore::key(&pos), i + 1, pos.clone())); }
+        }
+        let mut seen = HashSet::new();
+        let mut positions = self.positions.lock().unwrap();
+        for (key, ply, pos) in reached {
+            // Repetitions within a game count once
+            if !seen.insert(key) { continue; }
+            let t = positions.entry(key).or_insert_with(|| Tally { first: usize::MAX, ..Default::default() });
+            if index < t.first {
+                t.first = index;
+                t.fen = Fen::from_position(&pos, EnPassantMode::Legal).to_string();
+                t.line = numbered(&moves[..ply]);
+                t.ply = ply;
+            }
+            t.games += 1;
+            match score {
+                Some(1.0) => t.wins += 1,
+                Some(0.0) => t.losses += 1,
+                Some(_) => t.draws += 1,
+                None => {}
+            }
+            t.accuracy.push((index, accuracy));
+        }
+    }
+
+    /// The `top` positions reached in the most games (at least two), deeper
+    /// positions first among equals.
+    pub fn finish(self, top: usize) -> Option<RepeatedPositions> {
+        let mut tallies: Vec<Tally> = self.positions.into_inner().unwrap().into_values().filter(|t| t.games >= 2).collect();
+        tallies.sort_by(|a, b| b.games.cmp(&a.games)
+            .then_with(|| b.ply.cmp(&a.ply))
+            .then_with(|| a.fen.cmp(&b.fen)));
+        tallies.truncate(top);
+        let rows: Vec<RepeatedPosition> = tallies.into_iter().map(|mut t| {
+            let decided = t.wins + t.draws + t.losses;
+            t.accuracy.sort_by_key(|a| a.0);
+            RepeatedPosition {
+                score: (decided > 0).then(|| (t.wins as f64 + 0.5 * t.draws as f64) / decided as f64),
+                accuracy: t.accuracy.iter().map(|a| a.1).sum::<f64>() / t.games as f64,
+                fen: t.fen,
+                line: t.line,
+                games: t.games,
+                wins: t.wins,
+                draws: t.draws,
+                losses: t.losses,
+            }
+        }).collect();
+        (!rows.is_empty()).then_some(RepeatedPositions { min_ply: self.min_ply, rows })
+    }
+}
+
+/// "1. e4 e5 2. Nf3".
+fn numbered(moves: &[&str]) -> String {
+    let mut line = String::new();
+    for (i, san) in moves.iter().enumerate() {
+        if i > 0 { line.push(' '); }
+        if i % 2 == 0 { line.push_str(&format!("{}. ", i / 2 + 1)); }
+        line.push_str(san);
+    }
+    line
+}
+
+#[derive(Serialize, Debug)]
+pub struct RepeatedPosition {
+    pub fen: String,
+    /// Moves of the first game that reached it.
+    pub line: String,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// The player's mean score in these games.
+    pub score: Option<f64>,
+    /// The player's mean accuracy over these games.
+    pub accuracy: f64,
+}
+
+/// The positions the player keeps reaching, most games first.
+#[derive(Serialize, Debug)]
+pub struct RepeatedPositions {
+    /// Plies from the start before positions were counted.
+    pub min_ply: usize,
+    pub rows: Vec<RepeatedPosition>,
+}
+
+impl RepeatedPositions {
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nRepeated Positions")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:>5}  {:>9}  {:>6}  {:>8}  Line", "Games", "W/D/L", "Score", "Accuracy")?;
+        for r in &self.rows {
+            let score = r.score.map_or("-".to_string(), |s| format!("{:.0}%", 100.0 * s));
+            writeln!(out, "{:>5}  {:>9}  {:>6}  {:>7.2}%  {}", r.games, format!("{}/{}/{}", r.wins, r.draws, r.losses), score, r.accuracy, r.line)?;
+            writeln!(out, "{:>36}{}", "", r.fen)?;
+        }
+        Ok(())
+    }
+}
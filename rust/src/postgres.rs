@@ -0,0 +1,217 @@
+//! Writing runs into a shared PostgreSQL database, so several analysts or
+//! scheduled daemons can feed one place to query.
+//!
+//! Each run becomes one transaction of upserts piped to `psql`, which
+//! handles the connection string, authentication and TLS:
+//!
+//! - `runs`: one row per run, keyed by `run_id` (player and start time),
+//!   with the whole summary as `jsonb`.
+//! - `games`: one row per game, keyed by `game_id` (its URL, else a hash of
+//!   its players, end time and moves); analyzing a game again replaces it.
+//! - `moves`: one row per scored move of a game, keyed by `game_id` and `ply`.
+
+use crate::graph::EvalPoint;
+use crate::report::{GameResult, Summary};
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Tables and indexes, created when missing.
+pub const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS runs (
+    run_id text PRIMARY KEY,
+    player text NOT NULL,
+    started_at timestamptz,
+    finished_at timestamptz,
+    engine text,
+    games_analyzed integer NOT NULL,
+    average_accuracy double precision,
+    summary jsonb NOT NULL
+);
+CREATE TABLE IF NOT EXISTS games (
+    game_id text PRIMARY KEY,
+    run_id text NOT NULL REFERENCES runs,
+    url text,
+    white text NOT NULL,
+    black text NOT NULL,
+    white_elo integer,
+    black_elo integer,
+    result text,
+    time_class text,
+    end_time timestamptz,
+    moves integer NOT NULL,
+    white_accuracy double precision,
+    black_accuracy double precision,
+    white_blunders integer NOT NULL,
+    black_blunders integer NOT NULL,
+    opening text,
+    eval_source text NOT NULL,
+    data jsonb NOT NULL
+);
+CREATE INDEX IF NOT EXISTS games_run_id ON games (run_id);
+CREATE TABLE IF NOT EXISTS moves (
+    game_id text NOT NULL REFERENCES games ON DELETE CASCADE,
+    ply integer NOT NULL,
+    san text NOT NULL,
+    cp integer,
+    mate integer,
+    white_expected double precision NOT NULL,
+    error text,
+    PRIMARY KEY (game_id, ply)
+);
+";
+
+/// Key of a run: the player and the Unix time it started.
+pub fn run_id(summary: &Summary) -> String {
+    format!("{}-{}", summary.username.to_lowercase(), summary.run.as_ref().map_or(0, |r| r.started_at))
+}
+
+/// Key of a game: its URL, or for games without one (PGN files) a hash of
+/// what identifies it, so that the same game analyzed twice is one row.
+pub fn game_id(g: &GameResult) -> String {
+    if let Some(url) = &g.url { return url.clone(); }
+    // FNV-1a, stable across builds unlike the std hasher
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let end = g.end_time.map(|t| t.to_string()).unwrap_or_default();
+    for part in [g.white.as_str(), g.black.as_str(), end.as_str()].into_iter().chain(g.eval_graph.iter().map(|p| p.san.as_str())) {
+        for b in part.bytes().chain([0]) {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("pgn:{:016x}", hash)
+}
+
+/// A SQL string literal.
+fn text(s: &str) -> String { format!("'{}'", s.replace('\0', "").replace('\'', "''")) }
+
+fn opt_text(s: Option<&str>) -> String { s.map_or("NULL".into(), text) }
+
+fn opt<T: ToString>(v: Option<T>) -> String { v.map_or("NULL".into(), |v| v.to_string()) }
+
+fn float(v: f64) -> String { if v.is_finite() { v.to_string() } else { "NULL".into() } }
+
+fn timestamp(t: Option<i64>) -> String { t.map_or("NULL".into(), |t| format!("to_timestamp({})", t)) }
+
+/// `INSERT ... ON CONFLICT (key) DO UPDATE` of one row, every other column
+/// taking the new value.
+fn upsert(out: &mut dyn Write, table: &str, key: &[&str], row: &[(&str, String)]) -> io::Result<()> {
+    let columns: Vec<&str> = row.iter().map(|c| c.0).collect();
+    let values: Vec<&str> = row.iter().map(|c| c.1.as_str()).collect();
+    let updates: Vec<String> = columns.iter().filter(|c| !key.contains(c)).map(|c| format!("{} = EXCLUDED.{}", c, c)).collect();
+    writeln!(out, "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO UPDATE SET {};",
+        table, columns.join(", "), values.join(", "), key.join(", "), updates.join(", "))
+}
+
+fn move_row(game: &str, p: &EvalPoint) -> Vec<(&'static str, String)> {
+    vec![
+        ("game_id", text(game)),
+        ("ply", p.ply.to_string()),
+        ("san", text(&p.san)),
+        ("cp", opt(p.cp)),
+        ("mate", opt(p.mate)),
+        ("white_expected", float(p.white_expected)),
+        ("error", opt_text(p.error.as_deref())),
+    ]
+}
+
+/// Writes the SQL storing a run: the schema, the run, then each game with
+/// its moves. Moves come from the games' eval series.
+pub fn write_sql(out: &mut dyn Write, summary: &Summary, games: &[GameResult]) -> io::Result<()> {
+    writeln!(out, "SET standard_conforming_strings = on;")?;
+    out.write_all(SCHEMA.as_bytes())?;
+    let run = run_id(summary);
+    upsert(out, "runs", &["run_id"], &[
+        ("run_id", text(&run)),
+        ("player", text(&summary.username)),
+        ("started_at", timestamp(summary.run.as_ref().map(|r| r.started_at))),
+        ("finished_at", timestamp(summary.run.as_ref().map(|r| r.finished_at))),
+        ("engine", opt_text(summary.engine.as_ref().map(|e| e.describe()).as_deref())),
+        ("games_analyzed", summary.games_analyzed.to_string()),
+        ("average_accuracy", float(summary.average_accuracy)),
+        ("summary", format!("{}::jsonb", text(&serde_json::to_string(summary)?))),
+    ])?;
+    for g in games {
+        let id = game_id(g);
+        upsert(out, "games", &["game_id"], &[
+            ("game_id", text(&id)),
+            ("run_id", text(&run)),
+            ("url", opt_text(g.url.as_deref())),
+            ("white", text(&g.white)),
+            ("black", text(&g.black)),
+            ("white_elo", opt(g.white_elo)),
+            ("black_elo", opt(g.black_elo)),
+            ("result", opt_text(g.result.as_deref())),
+            ("time_class", opt_text(g.time_class.as_deref())),
+            ("end_time", timestamp(g.end_time)),
+            ("moves", g.moves.to_string()),
+            ("white_accuracy", float(g.white_accuracy)),
+            ("black_accuracy", float(g.black_accuracy)),
+            ("white_blunders", g.white_blunders.to_string()),
+            ("black_blunders", g.black_blunders.to_string()),
+            ("opening", opt_text(g.opening.as_deref())),
+            ("eval_source", text(g.eval_source.as_str())),
+            ("data", format!("{}::jsonb", text(&serde_json::to_string(g)?))),
+        ])?;
+        // Moves past the end of the new analysis go
+        writeln!(out, "DELETE FROM moves WHERE game_id = {} AND ply > {};", text(&id), g.eval_graph.last().map_or(0, |p| p.ply))?;
+        for p in &g.eval_graph { upsert(out, "moves", &["game_id", "ply"], &move_row(&id, p))?; }
+    }
+    Ok(())
+}
+
+/// Stores a run in the database at `conn` (a `postgres://` URL or
+/// `key=value` connection string) in one transaction through `psql`.
+pub fn store(conn: &str, summary: &Summary, games: &[GameResult]) -> Result<(), String> {
+    let mut sql = Vec::new();
+    write_sql(&mut sql, summary, games).map_err(|e| e.to_string())?;
+    let mut child = Command::new("psql")
+        .args(["--no-psqlrc", "--quiet", "--set", "ON_ERROR_STOP=1", "--single-transaction", "--file", "-", "--dbname", conn])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run psql: {}", e))?;
+    let written = child.stdin.take().expect("piped stdin").write_all(&sql);
+    let output = child.wait_with_output().map_err(|e| format!("psql failed: {}", e))?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("psql failed: {}", message.trim()));
+    }
+    written.map_err(|e| format!("cannot write to psql: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_double_quotes_and_keep_backslashes() {
+        assert_eq!(text("O'Kelly"), "'O''Kelly'");
+        assert_eq!(text("''"), "''''''");
+        // Literal under standard_conforming_strings, which write_sql sets
+        assert_eq!(text(r"C:\games\n"), r"'C:\games\n'");
+        assert_eq!(text(r"\'; DROP TABLE runs; --"), r"'\''; DROP TABLE runs; --'");
+    }
+
+    #[test]
+    fn literals_drop_nul() {
+        // Postgres text cannot hold NUL
+        assert_eq!(text("a\0b\0"), "'ab'");
+        assert_eq!(opt_text(None), "NULL");
+    }
+
+    #[test]
+    fn non_finite_floats_are_null() {
+        assert_eq!(float(f64::NAN), "NULL");
+        assert_eq!(float(f64::INFINITY), "NULL");
+        assert_eq!(float(81.5), "81.5");
+    }
+
+    #[test]
+    fn upserts_update_every_column_but_the_key() {
+        let mut out = Vec::new();
+        upsert(&mut out, "moves", &["game_id", "ply"], &[("game_id", text("g")), ("ply", "3".into()), ("san", text("e4"))]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+            "INSERT INTO moves (game_id, ply, san) VALUES ('g', 3, 'e4') ON CONFLICT (game_id, ply) DO UPDATE SET san = EXCLUDED.san;\n");
+    }
+}
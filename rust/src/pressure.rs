@@ -0,0 +1,67 @@
+//! Accuracy split by time pressure, relative to the time control.
+//!
+//! A move's time budget is the remaining clock spread over the moves still to
+//! play (40 minus the move number, at least 10) plus the increment. Pressure
+//! compares it with the budget at the start of the game, (base + 40 × inc) / 40,
+//! so three minutes left means little in a rapid game with increment and a lot
+//! in bullet.
+
+use crate::accuracy::{Bucket, BucketSplit};
+use crate::analysis::MoveRecord;
+use serde::Serialize;
+
+/// Budget ratios below which a move counts as under high or medium pressure.
+const HIGH: f64 = 0.25;
+const MEDIUM: f64 = 0.6;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Pressure {
+    High,
+    Medium,
+    None,
+}
+
+/// Pressure on a move of `move_number` with `clock` seconds left after it.
+pub fn pressure(clock: f64, move_number: usize, base: f64, inc: f64) -> Pressure {
+    let normal = (base + 40.0 * inc) / 40.0;
+    if normal <= 0.0 { return Pressure::None; }
+    let moves_left = 40usize.saturating_sub(move_number).max(10) as f64;
+    let ratio = (clock / moves_left + inc) / normal;
+    if ratio < HIGH { Pressure::High } else if ratio < MEDIUM { Pressure::Medium } else { Pressure::None }
+}
+
+/// A player's accuracy under high, medium and no time pressure.
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct PressureSplit {
+    pub high: Bucket,
+    pub medium: Bucket,
+    pub none: Bucket,
+}
+
+impl BucketSplit for PressureSplit {
+    const TITLE: &'static str = "Accuracy by Time Pressure";
+    /// Base time and increment of the time control, in seconds. Moves
+    /// without a clock time are left out.
+    type By = (f64, f64);
+
+    fn keys(r: &MoveRecord, (base, inc): (f64, f64)) -> Vec<usize> {
+        r.clock.map(|clock| match pressure(clock, r.ply.div_ceil(2), base, inc) {
+            Pressure::High => 0,
+            Pressure::Medium => 1,
+            Pressure::None => 2,
+        }).into_iter().collect()
+    }
+
+    fn bucket_mut(&mut self, key: usize) -> &mut Bucket {
+        match key {
+            0 => &mut self.high,
+            1 => &mut self.medium,
+            _ => &mut self.none,
+        }
+    }
+
+    fn rows(&self) -> Vec<(String, Bucket)> {
+        vec![("High".into(), self.high), ("Medium".into(), self.medium), ("None".into(), self.none)]
+    }
+}
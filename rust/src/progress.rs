@@ -0,0 +1,132 @@
+//! Live progress reporting with ETA and per-stage timings.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub struct Progress {
+    verb: &'static str,
+    total: usize,
+    every: usize,
+    completed: AtomicUsize,
+    start: Instant,
+    fetch: Duration,
+    parse_nanos: AtomicU64,
+    parse_allocations: AtomicU64,
+    parse_bytes: AtomicU64,
+    skipped: Mutex<BTreeMap<&'static str, usize>>,
+}
+
+impl Progress {
+    /// Starts the analysis stage clock; `fetch` is the already finished fetch stage.
+    pub fn new(verb: &'static str, total: usize, every: usize, fetch: Duration) -> Self {
+        Self {
+            verb,
+            total,
+            every: every.max(1),
+            completed: AtomicUsize::new(0),
+            start: Instant::now(),
+            fetch,
+            parse_nanos: AtomicU64::new(0),
+            parse_allocations: AtomicU64::new(0),
+            parse_bytes: AtomicU64::new(0),
+            skipped: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Adds time spent parsing PGN (summed across workers).
+    pub fn add_parse(&self, d: Duration) {
+        self.parse_nanos.fetch_add(d.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Adds heap allocations made while parsing.
+    pub fn add_parse_alloc(&self, s: crate::alloc::Snapshot) {
+        self.parse_allocations.fetch_add(s.allocations, Ordering::Relaxed);
+        self.parse_bytes.fetch_add(s.bytes, Ordering::Relaxed);
+    }
+
+    pub fn parse_alloc(&self) -> crate::alloc::Snapshot {
+        crate::alloc::Snapshot {
+            allocations: self.parse_allocations.load(Ordering::Relaxed),
+            bytes: self.parse_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Counts one game left out of the analysis, by reason.
+    pub fn skip(&self, reason: &'static str) {
+        *self.skipped.lock().unwrap().entry(reason).or_insert(0) += 1;
+    }
+
+    pub fn skipped(&self) -> BTreeMap<String, usize> {
+        self.skipped.lock().unwrap().iter().map(|(r, &n)| (r.to_string(), n)).collect()
+    }
+
+    pub fn parse_time(&self) -> Duration {
+        Duration::from_nanos(self.parse_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Marks one item done and logs a progress line every `every` items.
+    pub fn tick(&self) {
+        let c = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+        if c.is_multiple_of(self.every) || c == self.total {
+            let elapsed = self.start.elapsed().as_secs_f64();
+            let rate = c as f64 / elapsed;
+            let eta = (self.total - c) as f64 / rate;
+            crate::progress!("  {} {}/{} games ({:.2} games/sec, ETA {}) [fetch {} | parse {} | analysis {}]",
+                self.verb, c, self.total, rate, fmt_duration(eta),
+                fmt_duration(self.fetch.as_secs_f64()), fmt_duration(self.parse_time().as_secs_f64()), fmt_duration(elapsed));
+        }
+    }
+}
+
+/// Compact human duration: "850ms", "12.3s", "4m05s", "1h02m".
+pub fn fmt_duration(secs: f64) -> String {
+    if !secs.is_finite() { return "?".to_string(); }
+    if secs < 1.0 { return format!("{:.0}ms", secs * 1000.0); }
+    if secs < 60.0 { return format!("{:.1}s", secs); }
+    let s = secs.round() as u64;
+    if s < 3600 { format!("{}m{:02}s", s / 60, s % 60) } else { format!("{}h{:02}m", s / 3600, s % 3600 / 60) }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct WorkerStats {
+    pub games: usize,
+    pub positions: usize,
+    pub busy_s: f64,
+    pub idle_s: f64,
+    pub engine_restarts: usize,
+}
+
+impl WorkerStats {
+    pub fn positions_per_second(&self) -> f64 {
+        if self.busy_s > 0.0 { self.positions as f64 / self.busy_s } else { 0.0 }
+    }
+}
+
+/// Per-worker counters, indexed by the rayon thread running the job.
+pub struct Workers {
+    stats: Vec<Mutex<WorkerStats>>,
+}
+
+impl Workers {
+    pub fn new(n: usize) -> Self {
+        Self { stats: (0..n.max(1)).map(|_| Mutex::new(WorkerStats::default())).collect() }
+    }
+
+    /// Updates the calling worker's counters.
+    pub fn record(&self, f: impl FnOnce(&mut WorkerStats)) {
+        let i = rayon::current_thread_index().unwrap_or(0) % self.stats.len();
+        f(&mut self.stats[i].lock().unwrap());
+    }
+
+    /// Final per-worker stats; idle time is the part of `wall` a worker was not busy.
+    pub fn finish(self, wall: Duration) -> Vec<WorkerStats> {
+        self.stats.into_iter().map(|m| {
+            let mut s = m.into_inner().unwrap();
+            s.idle_s = (wall.as_secs_f64() - s.busy_s).max(0.0);
+            s
+        }).collect()
+    }
+}
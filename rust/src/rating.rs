@@ -0,0 +1,60 @@
+//! The player's rating over time per time class, next to their accuracy.
+
+use crate::accuracy::mean;
+use crate::report::GameResult;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+#[derive(Serialize, Debug)]
+pub struct RatingPoint {
+    pub end_time: i64,
+    pub rating: u32,
+    pub accuracy: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RatingSeries {
+    pub time_class: String,
+    /// Oldest first.
+    pub points: Vec<RatingPoint>,
+}
+
+impl RatingSeries {
+    /// Mean accuracy of the oldest and the newest quarter of the games.
+    pub fn accuracy_trend(&self) -> (f64, f64) {
+        let q = self.points.len().div_ceil(4);
+        let acc = |p: &[RatingPoint]| mean(&p.iter().map(|p| p.accuracy).collect::<Vec<_>>());
+        (acc(&self.points[..q]), acc(&self.points[self.points.len() - q..]))
+    }
+}
+
+/// One series per time class, over the games with a rating and an end time.
+pub fn rating_series(games: &[GameResult], target: &str) -> Vec<RatingSeries> {
+    let mut by_class: BTreeMap<&str, Vec<RatingPoint>> = BTreeMap::new();
+    for g in games {
+        let (Some(end_time), Some((rating, _))) = (g.end_time, g.elos_for(target)) else { continue };
+        by_class.entry(g.time_class.as_deref().unwrap_or("unknown")).or_default()
+            .push(RatingPoint { end_time, rating, accuracy: g.accuracy_for(target) });
+    }
+    by_class.into_iter().map(|(class, mut points)| {
+        points.sort_by_key(|p| p.end_time);
+        RatingSeries { time_class: class.to_string(), points }
+    }).collect()
+}
+
+pub fn write_text(out: &mut dyn Write, series: &[RatingSeries]) -> io::Result<()> {
+    writeln!(out, "\nRating Progression")?;
+    writeln!(out, "{}", "=".repeat(50))?;
+    writeln!(out, "{:<10} {:>6} {:>13} {:>11} {:>17}", "Class", "Games", "Rating", "Range", "Accuracy")?;
+    for s in series {
+        let (first, last) = (&s.points[0], &s.points[s.points.len() - 1]);
+        let min = s.points.iter().map(|p| p.rating).min().unwrap_or(0);
+        let max = s.points.iter().map(|p| p.rating).max().unwrap_or(0);
+        let (acc_from, acc_to) = s.accuracy_trend();
+        writeln!(out, "{:<10} {:>6} {:>13} {:>11} {:>17}", s.time_class, s.points.len(),
+            format!("{} → {}", first.rating, last.rating), format!("{}-{}", min, max),
+            format!("{:.1}% → {:.1}%", acc_from, acc_to))?;
+    }
+    Ok(())
+}
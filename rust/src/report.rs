@@ -0,0 +1,639 @@
+use crate::accuracy::{mean, BucketSplit};
+use crate::alloc::AllocStats;
+use crate::analysis::{EvalSource, Scramble};
+use crate::blunders::{Blunder, BlunderCauses, Listings};
+use crate::calibration::{Calibration, Forecast};
+use crate::cploss::{CpLossReport, GameCpLoss};
+use crate::criticality::CriticalitySplit;
+use crate::engine::EngineInfo;
+use crate::endgame::{EndgameEntry, EndgameStats};
+use crate::explorer::MatchCount;
+use crate::graph::EvalPoint;
+use crate::material::MaterialSplit;
+use crate::meta::RunMetadata;
+use crate::movenumber::MoveNumberSplit;
+use crate::pieces::PieceSplit;
+use crate::positions::RepeatedPositions;
+use crate::pressure::PressureSplit;
+use crate::pgn::GameHeaders;
+use crate::openings::{Novelty, Repertoire, TheoryStats};
+use crate::opponents::OpponentStats;
+use crate::rating::RatingSeries;
+use crate::sessions::SessionStats;
+use crate::sharpness::SharpAccuracy;
+use crate::stats::bootstrap_mean_ci;
+use crate::termination::{Termination, TerminationStats};
+use crate::timeofday::TimeOfDay;
+use crate::variations::SideLines;
+use crate::progress::{fmt_duration, WorkerStats};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// Data format written to stdout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    White,
+    Black,
+}
+
+#[derive(Serialize, serde::Deserialize, Clone, Debug)]
+pub struct GameResult {
+    /// Position of the game in the run's input, from 1, so that results can
+    /// be matched up however the workers finished.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub url: Option<String>,
+    pub white: String,
+    pub black: String,
+    pub white_accuracy: f64,
+    pub black_accuracy: f64,
+    pub moves: usize,
+    /// Whether the game was scored from the engine or from evals it came with.
+    #[serde(default)]
+    pub eval_source: EvalSource,
+    /// The player's opening moves found in the master reference.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub master_match: Option<MatchCount>,
+    /// ECO code or opening name from the PGN tags.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening: Option<String>,
+    /// Whether the game was checked against a reference for novelties.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub theory_checked: bool,
+    /// First move outside the reference; `None` if checked and all theory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub novelty: Option<Novelty>,
+    /// PGN result tag ("1-0", "0-1", "1/2-1/2").
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Opening plies in SAN, recorded for the repertoire report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+    /// How the player entered the endgame, if the game reached one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endgame: Option<EndgameEntry>,
+    /// The player's accuracy by material balance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialSplit>,
+    /// The player's accuracy by piece moved and kind of move.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pieces: Option<PieceSplit>,
+    /// The player's accuracy by move number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub move_numbers: Option<MoveNumberSplit>,
+    /// White's expected score at the calibration checkpoints.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forecasts: Vec<Forecast>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white_elo: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black_elo: Option<u32>,
+    /// Lowest and highest expected score the player had during the game.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_range: Option<(f64, f64)>,
+    /// Unix time the game ended.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_class: Option<String>,
+    /// Movetext with eval annotations, kept for --publish-study.
+    #[serde(skip)]
+    pub annotated_pgn: Option<String>,
+    pub white_blunders: usize,
+    pub black_blunders: usize,
+    /// The player's sharpness-weighted accuracy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sharp_accuracy: Option<f64>,
+    /// Per-move centipawn losses of the player and the opponent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cp_loss: Option<GameCpLoss>,
+    /// The player's accuracy on critical and easy positions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub criticality: Option<CriticalitySplit>,
+    /// The player's accuracy by time pressure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<PressureSplit>,
+    /// The player's moves below the --exclude-below-clock limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scramble: Option<Scramble>,
+    /// How the game ended, recorded for the termination report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub termination: Option<Termination>,
+    /// White's evaluation after every move, for eval graphs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub eval_graph: Vec<EvalPoint>,
+    /// The player's blunders, recorded for the blunder listing.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blunder_moves: Vec<Blunder>,
+    /// The player's largest expected-score drop in one move.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_swing: Option<f64>,
+    /// Ply whose search timed out; only the moves before it were scored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timed_out_at: Option<usize>,
+    /// The player's moves in the game's side lines (--include-variations).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variations: Option<SideLines>,
+    /// Tag pairs of the game's PGN.
+    #[serde(default, skip_serializing_if = "GameHeaders::is_empty")]
+    pub headers: GameHeaders,
+}
+
+impl GameResult {
+    /// Color played by `target` (lowercase username).
+    pub fn side_of(&self, target: &str) -> Side {
+        if self.white == target { Side::White } else { Side::Black }
+    }
+
+    /// Game score of `target`: 1, 0.5 or 0, `None` when unfinished or unknown.
+    pub fn score_for(&self, target: &str) -> Option<f64> {
+        let white = match self.result.as_deref()? {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            "1/2-1/2" => 0.5,
+            _ => return None,
+        };
+        Some(if self.white == target { white } else { 1.0 - white })
+    }
+
+    /// Ratings of `target` and their opponent, when both are known.
+    pub fn elos_for(&self, target: &str) -> Option<(u32, u32)> {
+        let (w, b) = (self.white_elo?, self.black_elo?);
+        Some(if self.white == target { (w, b) } else { (b, w) })
+    }
+
+    /// Moves played by `target`.
+    pub fn moves_for(&self, target: &str) -> usize {
+        if self.white == target { self.moves.div_ceil(2) } else { self.moves / 2 }
+    }
+
+    /// Blunders by `target`.
+    pub fn blunders_for(&self, target: &str) -> usize {
+        if self.white == target { self.white_blunders } else { self.black_blunders }
+    }
+
+    /// "2024-01-31", from the Date tag or the end time.
+    pub fn date(&self) -> Option<String> {
+        self.headers.date.as_ref().map(|d| d.replace('.', "-"))
+            .or_else(|| self.end_time.map(|t| crate::date::fmt_unix(t)[..10].to_string()))
+    }
+
+    /// Accuracy of `target` (lowercase username) in this game.
+    pub fn accuracy_for(&self, target: &str) -> f64 {
+        if self.white == target { self.white_accuracy } else { self.black_accuracy }
+    }
+
+    /// Accuracy of `target`'s opponent in this game.
+    pub fn accuracy_for_opponent(&self, target: &str) -> f64 {
+        if self.white == target { self.black_accuracy } else { self.white_accuracy }
+    }
+}
+
+/// Accuracy and score over a group of games.
+#[derive(Serialize, Debug, Default)]
+pub struct GameGroup {
+    pub games: usize,
+    pub avg_accuracy: f64,
+    /// Mean game score, over the games with a known result.
+    pub avg_score: Option<f64>,
+}
+
+impl GameGroup {
+    pub fn of(games: &[&GameResult], target: &str) -> Self {
+        let acc: Vec<f64> = games.iter().map(|g| g.accuracy_for(target)).collect();
+        let scores: Vec<f64> = games.iter().filter_map(|g| g.score_for(target)).collect();
+        Self { games: games.len(), avg_accuracy: mean(&acc), avg_score: (!scores.is_empty()).then(|| mean(&scores)) }
+    }
+}
+
+/// Elo-expected against actual score over the rated, finished games.
+#[derive(Serialize, Debug)]
+pub struct ScorePerformance {
+    pub games: usize,
+    pub score: f64,
+    pub expected_score: f64,
+    pub average_opponent_elo: f64,
+}
+
+impl ScorePerformance {
+    pub fn from_games(games: &[GameResult], target: &str) -> Option<Self> {
+        let rated: Vec<(f64, u32, u32)> = games.iter()
+            .filter_map(|g| Some((g.score_for(target)?, g.elos_for(target)?)))
+            .map(|(s, (me, opp))| (s, me, opp))
+            .collect();
+        if rated.is_empty() { return None; }
+        Some(Self {
+            games: rated.len(),
+            score: rated.iter().map(|r| r.0).sum(),
+            expected_score: rated.iter().map(|&(_, me, opp)| elo_expected(me, opp)).sum(),
+            average_opponent_elo: rated.iter().map(|r| r.2 as f64).sum::<f64>() / rated.len() as f64,
+        })
+    }
+}
+
+/// Expected score of a player rated `me` against `opp`.
+pub fn elo_expected(me: u32, opp: u32) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opp as f64 - me as f64) / 400.0))
+}
+
+/// How often lost positions were saved and won positions were won.
+#[derive(Serialize, Debug)]
+pub struct Resourcefulness {
+    /// Expected score at or below which a position counts as lost.
+    pub threshold: f64,
+    pub lost_games: usize,
+    /// Games that were lost at some point but drawn or won.
+    pub swindles: usize,
+    pub winning_games: usize,
+    /// Games that were winning at some point and won.
+    pub conversions: usize,
+}
+
+impl Resourcefulness {
+    pub fn from_games(games: &[GameResult], target: &str, threshold: f64) -> Option<Self> {
+        let ranged: Vec<(f64, f64, f64)> = games.iter()
+            .filter_map(|g| { let (lo, hi) = g.expected_range?; Some((lo, hi, g.score_for(target)?)) })
+            .collect();
+        if ranged.is_empty() { return None; }
+        let lost: Vec<_> = ranged.iter().filter(|r| r.0 <= threshold).collect();
+        let winning: Vec<_> = ranged.iter().filter(|r| r.1 >= 1.0 - threshold).collect();
+        Some(Self {
+            threshold,
+            lost_games: lost.len(),
+            swindles: lost.iter().filter(|r| r.2 >= 0.5).count(),
+            winning_games: winning.len(),
+            conversions: winning.iter().filter(|r| r.2 == 1.0).count(),
+        })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let pct = |n: usize, d: usize| if d == 0 { "-".to_string() } else { format!("{:.1}%", 100.0 * n as f64 / d as f64) };
+        writeln!(out, "\nResourcefulness")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "Swindle rate: {} ({}/{} lost positions saved)", pct(self.swindles, self.lost_games), self.swindles, self.lost_games)?;
+        writeln!(out, "Conversion rate: {} ({}/{} winning positions won)", pct(self.conversions, self.winning_games), self.conversions, self.winning_games)
+    }
+}
+
+/// Games and average accuracy of one eval source.
+#[derive(Serialize, Debug)]
+pub struct SourceAccuracy {
+    pub games: usize,
+    pub accuracy: f64,
+}
+
+/// The average of engine-scored and server-scored games, kept apart in runs
+/// with both: server evals carry no WDL, so their win chances come from
+/// [`Wdl::from_score`](crate::engine::Wdl::from_score) without a draw
+/// band and their accuracies are not comparable with the engine's.
+#[derive(Serialize, Debug)]
+pub struct EvalSourceSplit {
+    /// Games scored by the run's engine or from saved engine evals.
+    pub engine: SourceAccuracy,
+    pub server: SourceAccuracy,
+}
+
+impl EvalSourceSplit {
+    fn from_games(games: &[GameResult], acc: &[f64]) -> Option<Self> {
+        let (server, engine): (Vec<(&GameResult, &f64)>, Vec<_>) = games.iter().zip(acc)
+            .partition(|(g, _)| g.eval_source == EvalSource::Server);
+        if server.is_empty() || engine.is_empty() { return None; }
+        let of = |group: Vec<(&GameResult, &f64)>| {
+            let acc: Vec<f64> = group.into_iter().map(|(_, &a)| a).collect();
+            SourceAccuracy { games: acc.len(), accuracy: mean(&acc) }
+        };
+        Some(Self { engine: of(engine), server: of(server) })
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<RunMetadata>,
+    pub username: String,
+    pub games_analyzed: usize,
+    /// Games left out, by reason.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub skipped: BTreeMap<String, usize>,
+    /// Games scored only up to a search that timed out.
+    pub partial_games: usize,
+    /// Games scored from their server analysis, without the engine.
+    pub server_analyzed: usize,
+    /// Averages per eval source, when the run mixes server and engine games.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_eval_source: Option<EvalSourceSplit>,
+    /// The engine that analyzed the games, unless none was started.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<EngineInfo>,
+    pub total_moves: usize,
+    pub average_accuracy: f64,
+    /// 95% bootstrap confidence interval of the average accuracy, over games.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accuracy_ci: Option<(f64, f64)>,
+    /// The player's games, accuracy and score with each color.
+    pub as_white: GameGroup,
+    pub as_black: GameGroup,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sharp_accuracy: Option<SharpAccuracy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub master_match_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub performance: Option<ScorePerformance>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theory: Option<TheoryStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repertoire: Option<Repertoire>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endgames: Option<EndgameStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub material: Option<MaterialSplit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pieces: Option<PieceSplit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub move_numbers: Option<MoveNumberSplit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<PressureSplit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub criticality: Option<CriticalitySplit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cp_loss: Option<CpLossReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<Calibration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminations: Option<TerminationStats>,
+    /// Moves with less than --exclude-below-clock left, excluded from the average.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scramble: Option<Scramble>,
+    /// The player's moves in side lines, not part of the average.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variations: Option<SideLines>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resourcefulness: Option<Resourcefulness>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<SessionStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opponents: Option<OpponentStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_of_day: Option<TimeOfDay>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ratings: Vec<RatingSeries>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listings: Option<Listings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blunder_causes: Option<BlunderCauses>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeated_positions: Option<RepeatedPositions>,
+    pub fetch_time_s: f64,
+    /// PGN parsing time summed over all workers.
+    pub parse_time_s: f64,
+    pub analysis_time_s: f64,
+    pub games_per_second: f64,
+    pub moves_per_second: f64,
+    pub workers: Vec<WorkerStats>,
+    /// Heap usage per stage, with the `alloc-stats` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocations: Option<AllocStats>,
+}
+
+impl Summary {
+    pub fn new(username: &str, games: &[GameResult], fetch_time_s: f64, parse_time_s: f64, analysis_time_s: f64) -> Self {
+        let target = username.to_lowercase();
+        let acc: Vec<f64> = games.iter().map(|g| g.accuracy_for(&target)).collect();
+        let total_moves = games.iter().map(|g| g.moves).sum();
+        let (white, black): (Vec<&GameResult>, Vec<&GameResult>) = games.iter().partition(|g| g.side_of(&target) == Side::White);
+        let (matched, positions) = games.iter().filter_map(|g| g.master_match)
+            .fold((0, 0), |(m, p), c| (m + c.matched, p + c.positions));
+        Self {
+            username: username.to_string(),
+            games_analyzed: games.len(),
+            run: None,
+            skipped: BTreeMap::new(),
+            partial_games: games.iter().filter(|g| g.timed_out_at.is_some()).count(),
+            server_analyzed: games.iter().filter(|g| g.eval_source == EvalSource::Server).count(),
+            by_eval_source: EvalSourceSplit::from_games(games, &acc),
+            engine: None,
+            total_moves,
+            average_accuracy: mean(&acc),
+            accuracy_ci: bootstrap_mean_ci(&acc, 0.95),
+            as_white: GameGroup::of(&white, &target),
+            as_black: GameGroup::of(&black, &target),
+            sharp_accuracy: None,
+            master_match_rate: (positions > 0).then(|| 100.0 * matched as f64 / positions as f64),
+            performance: ScorePerformance::from_games(games, &target),
+            theory: TheoryStats::from_games(games, &target),
+            repertoire: Repertoire::from_games(games, &target),
+            endgames: EndgameStats::from_games(games, &target),
+            material: MaterialSplit::merge(games.iter().filter_map(|g| g.material.as_ref())),
+            pieces: PieceSplit::merge(games.iter().filter_map(|g| g.pieces.as_ref())),
+            move_numbers: MoveNumberSplit::merge(games.iter().filter_map(|g| g.move_numbers.as_ref())),
+            pressure: PressureSplit::merge(games.iter().filter_map(|g| g.pressure.as_ref())),
+            criticality: CriticalitySplit::merge(games.iter().filter_map(|g| g.criticality.as_ref())),
+            cp_loss: CpLossReport::from_games(games.iter().filter_map(|g| g.cp_loss.as_ref())),
+            calibration: Calibration::from_games(games),
+            terminations: TerminationStats::from_games(games, &target),
+            scramble: Scramble::merge(games.iter().filter_map(|g| g.scramble.as_ref())),
+            variations: SideLines::merge(games.iter().filter_map(|g| g.variations.as_ref())),
+            resourcefulness: None,
+            sessions: None,
+            opponents: None,
+            time_of_day: None,
+            ratings: Vec::new(),
+            listings: None,
+            blunder_causes: None,
+            repeated_positions: None,
+            fetch_time_s,
+            parse_time_s,
+            analysis_time_s,
+            games_per_second: games.len() as f64 / analysis_time_s,
+            moves_per_second: total_moves as f64 / analysis_time_s,
+            workers: Vec::new(),
+            allocations: None,
+        }
+    }
+}
+
+pub fn write_text(out: &mut dyn Write, s: &Summary) -> io::Result<()> {
+    writeln!(out, "\nResults")?;
+    writeln!(out, "{}", "=".repeat(50))?;
+    if let Some(run) = &s.run { run.write_text(out)?; }
+    if let Some(engine) = &s.engine { writeln!(out, "Engine: {}", engine.describe())?; }
+    writeln!(out, "Games analyzed: {}", s.games_analyzed)?;
+    if !s.skipped.is_empty() {
+        let reasons: Vec<String> = s.skipped.iter().map(|(r, n)| format!("{} {}", r, n)).collect();
+        writeln!(out, "Games skipped: {} ({})", s.skipped.values().sum::<usize>(), reasons.join(", "))?;
+    }
+    if s.partial_games > 0 {
+        writeln!(out, "Games partially analyzed: {} (search timed out)", s.partial_games)?;
+    }
+    if s.server_analyzed > 0 {
+        writeln!(out, "Games scored from server analysis: {} (engine not run)", s.server_analyzed)?;
+    }
+    writeln!(out, "Total moves: {}", s.total_moves)?;
+    match s.accuracy_ci {
+        Some((lo, hi)) => writeln!(out, "Average accuracy for {}: {:.2}% (95% CI {:.2}-{:.2}%)", s.username, s.average_accuracy, lo, hi)?,
+        None => writeln!(out, "Average accuracy for {}: {:.2}%", s.username, s.average_accuracy)?,
+    }
+    if let Some(split) = &s.by_eval_source {
+        writeln!(out, "  Engine-scored: {:.2}% over {} games; server-scored: {:.2}% over {} games (no draw band, not comparable)",
+            split.engine.accuracy, split.engine.games, split.server.accuracy, split.server.games)?;
+    }
+    for (color, g) in [("White", &s.as_white), ("Black", &s.as_black)] {
+        if g.games == 0 { continue; }
+        let score = g.avg_score.map_or(String::new(), |sc| format!(", score {:.1}%", 100.0 * sc));
+        writeln!(out, "  As {}: {:.2}% over {} games{}", color, g.avg_accuracy, g.games, score)?;
+    }
+    if let Some(sa) = &s.sharp_accuracy {
+        writeln!(out, "Sharpness-weighted accuracy ({}, strength {}): {:.2}%", sa.scheme.as_str(), sa.strength, sa.accuracy)?;
+    }
+    if let Some(sc) = &s.scramble {
+        writeln!(out, "Time-scramble moves (excluded above): {} at {:.2}% accuracy", sc.moves, sc.accuracy)?;
+    }
+    if let Some(v) = &s.variations {
+        writeln!(out, "Side-line moves (not in the average): {} in {} lines at {:.2}% accuracy", v.moves, v.lines, v.accuracy)?;
+    }
+    if let Some(rate) = s.master_match_rate {
+        writeln!(out, "Master-move match rate: {:.1}%", rate)?;
+    }
+    if let Some(p) = &s.performance {
+        writeln!(out, "Score: {}/{} (Elo-expected {:.1}, {:+.1}) vs average opponent {:.0}",
+            p.score, p.games, p.expected_score, p.score - p.expected_score, p.average_opponent_elo)?;
+    }
+    if let Some(t) = &s.theory { t.write_text(out)?; }
+    if let Some(r) = &s.repertoire { r.write_text(out)?; }
+    if let Some(e) = &s.endgames { e.write_text(out)?; }
+    if let Some(m) = &s.material { m.write_text(out)?; }
+    if let Some(p) = &s.pieces { p.write_text(out)?; }
+    if let Some(m) = &s.move_numbers { m.write_text(out)?; }
+    if let Some(p) = &s.pressure { p.write_text(out)?; }
+    if let Some(c) = &s.criticality { c.write_text(out)?; }
+    if let Some(c) = &s.cp_loss { c.write_text(out)?; }
+    if let Some(c) = &s.calibration { c.write_text(out)?; }
+    if let Some(t) = &s.terminations { t.write_text(out)?; }
+    if let Some(r) = &s.resourcefulness { r.write_text(out)?; }
+    if let Some(t) = &s.sessions { t.write_text(out)?; }
+    if let Some(o) = &s.opponents { o.write_text(out)?; }
+    if let Some(t) = &s.time_of_day { t.write_text(out)?; }
+    if !s.ratings.is_empty() { crate::rating::write_text(out, &s.ratings)?; }
+    if let Some(l) = &s.listings { l.write_text(out)?; }
+    if let Some(c) = &s.blunder_causes { c.write_text(out)?; }
+    if let Some(r) = &s.repeated_positions { r.write_text(out)?; }
+    writeln!(out, "\nPerformance")?;
+    writeln!(out, "{}", "=".repeat(50))?;
+    writeln!(out, "Fetch time: {:.2}s", s.fetch_time_s)?;
+    writeln!(out, "Parse time (all workers): {:.4}s", s.parse_time_s)?;
+    writeln!(out, "Analysis time: {:.2}s", s.analysis_time_s)?;
+    writeln!(out, "Total time: {:.2}s", s.fetch_time_s + s.analysis_time_s)?;
+    writeln!(out, "Games per second: {:.4}", s.games_per_second)?;
+    writeln!(out, "Moves per second: {:.2}", s.moves_per_second)?;
+    if let Some(a) = &s.allocations {
+        writeln!(out, "{:<10} {:>12} {:>12} {:>12}", "Heap", "Allocs", "Allocated", "Peak")?;
+        for (name, st) in [("fetch", a.fetch), ("parse", a.parse), ("analysis", a.analysis)] {
+            writeln!(out, "{:<10} {:>12} {:>12} {:>12}", name, st.allocations, fmt_bytes(st.bytes),
+                st.peak_bytes.map_or("-".to_string(), fmt_bytes))?;
+        }
+    }
+    if !s.workers.is_empty() {
+        writeln!(out, "\nWorkers")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<8} {:>6} {:>10} {:>9} {:>8} {:>9}", "Worker", "Games", "Positions", "Pos/sec", "Idle", "Restarts")?;
+        for (i, w) in s.workers.iter().enumerate() {
+            writeln!(out, "{:<8} {:>6} {:>10} {:>9.1} {:>8} {:>9}",
+                i, w.games, w.positions, w.positions_per_second(), fmt_duration(w.idle_s), w.engine_restarts)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn write_json(out: &mut dyn Write, s: &Summary, games: &[GameResult]) -> io::Result<()> {
+    #[derive(Serialize)]
+    struct Output<'a> { summary: &'a Summary, games: &'a [GameResult] }
+    serde_json::to_writer_pretty(&mut *out, &Output { summary: s, games })?;
+    writeln!(out)
+}
+
+/// A standalone HTML page: the text summary followed by every game with its
+/// eval graph.
+pub fn write_html(out: &mut dyn Write, s: &Summary, games: &[GameResult]) -> io::Result<()> {
+    let mut text = Vec::new();
+    write_text(&mut text, s)?;
+    writeln!(out, "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>chess-bench: {}</title>", html_escape(&s.username))?;
+    writeln!(out, "<style>body{{font:14px system-ui,sans-serif;margin:2em}}h3{{margin-bottom:4px}}svg{{display:block;border:1px solid #999}}</style>")?;
+    writeln!(out, "</head><body>\n<h2>chess-bench: {}</h2>", html_escape(&s.username))?;
+    writeln!(out, "<pre>{}</pre>", html_escape(String::from_utf8_lossy(&text).trim()))?;
+    for (i, g) in games.iter().enumerate() {
+        let title = format!("{}. {} vs {} ({})", i + 1, g.white, g.black, g.result.as_deref().unwrap_or("*"));
+        match &g.url {
+            Some(url) => writeln!(out, "<h3><a href=\"{}\">{}</a></h3>", html_escape(url), html_escape(&title))?,
+            None => writeln!(out, "<h3>{}</h3>", html_escape(&title))?,
+        }
+        writeln!(out, "<p>Accuracy {:.2}% / {:.2}% over {} moves</p>", g.white_accuracy, g.black_accuracy, g.moves)?;
+        if !g.eval_graph.is_empty() { writeln!(out, "{}", crate::graph::svg(&g.eval_graph))?; }
+    }
+    writeln!(out, "</body></html>")
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub fn write_csv(out: &mut dyn Write, games: &[GameResult]) -> io::Result<()> {
+    writeln!(out, "url,white,black,white_accuracy,black_accuracy,moves,event,date,result,termination,time_control,eco,eval_source")?;
+    for g in games {
+        let h = &g.headers;
+        let tag = |t: &Option<String>| csv_field(t.as_deref().unwrap_or(""));
+        writeln!(out, "{},{},{},{:.2},{:.2},{},{},{},{},{},{},{},{}",
+            csv_field(g.url.as_deref().unwrap_or("")), csv_field(&g.white), csv_field(&g.black),
+            g.white_accuracy, g.black_accuracy, g.moves,
+            tag(&h.event), tag(&h.date), tag(&h.result), tag(&h.termination), tag(&h.time_control), tag(&h.eco), g.eval_source.as_str())?;
+    }
+    Ok(())
+}
+
+/// "512 B", "1.5 KiB", "12.3 MiB".
+pub fn fmt_bytes(b: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB"];
+    let mut v = b as f64;
+    let mut u = 0;
+    while v >= 1024.0 && u < units.len() - 1 { v /= 1024.0; u += 1; }
+    if u == 0 { format!("{} B", b) } else { format!("{:.1} {}", v, units[u]) }
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or newline.
+/// One row per game, oldest first: date, color, opponent and their rating,
+/// result, accuracy and blunders of `target` (lowercase username).
+pub fn write_table(out: &mut dyn Write, games: &[GameResult], target: &str) -> io::Result<()> {
+    let mut rows: Vec<&GameResult> = games.iter().collect();
+    rows.sort_by(|a, b| a.end_time.cmp(&b.end_time).then_with(|| a.headers.date.cmp(&b.headers.date)));
+    let opponent_width = rows.iter().map(|g| if g.white == target { g.black.len() } else { g.white.len() }).max().unwrap_or(0).clamp(8, 24);
+    writeln!(out, "\nGames")?;
+    writeln!(out, "{}", "=".repeat(50))?;
+    writeln!(out, "{:<10}  {:<5}  {:<w$}  {:>6}  {:<6}  {:>8}  {:>8}", "Date", "Color", "Opponent", "Rating", "Result", "Accuracy", "Blunders", w = opponent_width)?;
+    for g in rows {
+        let date = g.date().unwrap_or_else(|| "-".into());
+        let (color, opponent) = match g.side_of(target) { Side::White => ("White", &g.black), Side::Black => ("Black", &g.white) };
+        let rating = g.elos_for(target).map_or("-".to_string(), |(_, opp)| opp.to_string());
+        let result = match g.score_for(target) {
+            Some(1.0) => "win",
+            Some(0.0) => "loss",
+            Some(_) => "draw",
+            None => "-",
+        };
+        let opponent: String = opponent.chars().take(opponent_width).collect();
+        writeln!(out, "{:<10}  {:<5}  {:<w$}  {:>6}  {:<6}  {:>7.2}%  {:>8}", date, color, opponent, rating, result,
+            g.accuracy_for(target), g.blunders_for(target), w = opponent_width)?;
+    }
+    Ok(())
+}
+
+pub fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
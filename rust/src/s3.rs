@@ -0,0 +1,272 @@
+//! Uploading result files to an S3 bucket or an S3-compatible store (MinIO,
+//! Cloudflare R2, ...), so runs on cloud machines need no upload step.
+//!
+//! Requests are signed with AWS Signature Version 4 from the usual
+//! environment variables: `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`,
+//! `AWS_SESSION_TOKEN` (temporary credentials) and `AWS_REGION` or
+//! `AWS_DEFAULT_REGION` (us-east-1 when neither is set).
+
+use crate::date::civil_from_days;
+use crate::http::{HttpClient, HttpError};
+use reqwest::header::CONTENT_TYPE;
+
+/// Where files go: `s3://bucket/prefix`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S3Location {
+    pub bucket: String,
+    /// Key prefix without leading or trailing slashes; may be empty.
+    pub prefix: String,
+}
+
+impl S3Location {
+    /// Parses `s3://bucket` or `s3://bucket/prefix`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let rest = s.strip_prefix("s3://").ok_or_else(|| format!("expected s3://bucket/prefix, got {:?}", s))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() { return Err(format!("no bucket in {:?}", s)); }
+        Ok(Self { bucket: bucket.to_string(), prefix: prefix.trim_matches('/').to_string() })
+    }
+
+    /// The object key of file `name` under the prefix.
+    pub fn key(&self, name: &str) -> String {
+        if self.prefix.is_empty() { name.to_string() } else { format!("{}/{}", self.prefix, name) }
+    }
+
+    pub fn url(&self, name: &str) -> String { format!("s3://{}/{}", self.bucket, self.key(name)) }
+}
+
+/// Credentials, region and endpoint of the store.
+pub struct S3Config {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: String,
+    /// Endpoint of an S3-compatible store, e.g. `http://localhost:9000`,
+    /// addressed path-style; AWS itself when unset.
+    endpoint: Option<String>,
+}
+
+impl S3Config {
+    /// Reads the credentials and region from the environment.
+    pub fn from_env(endpoint: Option<String>) -> Result<Self, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let access_key = var("AWS_ACCESS_KEY_ID").ok_or("AWS_ACCESS_KEY_ID is not set")?;
+        let secret_key = var("AWS_SECRET_ACCESS_KEY").ok_or("AWS_SECRET_ACCESS_KEY is not set")?;
+        Ok(Self {
+            access_key,
+            secret_key,
+            session_token: var("AWS_SESSION_TOKEN"),
+            region: var("AWS_REGION").or_else(|| var("AWS_DEFAULT_REGION")).unwrap_or_else(|| "us-east-1".into()),
+            endpoint: endpoint.map(|e| e.trim_end_matches('/').to_string()),
+        })
+    }
+
+    /// HTTP URL of `key` in `bucket`: virtual-hosted on AWS, path-style on
+    /// other endpoints.
+    fn object_url(&self, bucket: &str, key: &str) -> String {
+        match &self.endpoint {
+            Some(endpoint) => format!("{}/{}/{}", endpoint, bucket, uri_encode(key)),
+            None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, self.region, uri_encode(key)),
+        }
+    }
+
+    /// Uploads `body` as file `name` under `location`, replacing any object
+    /// of that key.
+    pub fn put(&self, client: &HttpClient, location: &S3Location, name: &str, body: Vec<u8>, content_type: &str) -> Result<(), HttpError> {
+        let url = reqwest::Url::parse(&self.object_url(&location.bucket, &location.key(name)))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or(""), port),
+            None => url.host_str().unwrap_or("").to_string(),
+        };
+        let now = crate::date::now_unix();
+        let (y, m, d) = civil_from_days(now.div_euclid(86_400));
+        let secs = now.rem_euclid(86_400);
+        let timestamp = format!("{}{:02}{:02}T{:02}{:02}{:02}Z", y, m, d, secs / 3600, secs % 3600 / 60, secs % 60);
+        let payload_hash = hex(&sha256(&body));
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+        if let Some(token) = &self.session_token { headers.push(("x-amz-security-token", token.clone())); }
+        let (signed, canonical) = canonical_request("PUT", url.path(), &headers, &payload_hash);
+        let (signature, scope) = sign(&self.secret_key, &self.region, "s3", &timestamp, &canonical);
+        let authorization = format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed, signature);
+
+        let mut request = client.online(url.as_str())?.put(url.clone())
+            .header("Authorization", authorization)
+            .header(CONTENT_TYPE, content_type)
+            .body(body);
+        // reqwest sets Host itself, from the same URL
+        for (name, value) in headers.into_iter().filter(|h| h.0 != "host") { request = request.header(name, value); }
+        let response = request.send()?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().unwrap_or_default();
+            let code = text.split_once("<Code>").and_then(|(_, r)| r.split_once("</Code>")).map_or(String::new(), |c| format!(" ({})", c.0));
+            return Err(format!("{} answered {}{}", url.host_str().unwrap_or(""), status, code).into());
+        }
+        Ok(())
+    }
+}
+
+/// The SigV4 canonical request of a request without a query string, and
+/// its signed header list. `headers` are lowercase and sorted by name.
+fn canonical_request(method: &str, path: &str, headers: &[(&str, String)], payload_hash: &str) -> (String, String) {
+    let signed: Vec<&str> = headers.iter().map(|h| h.0).collect();
+    let signed = signed.join(";");
+    let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v.trim())).collect();
+    let canonical = format!("{}\n{}\n\n{}\n{}\n{}", method, path, canonical_headers, signed, payload_hash);
+    (signed, canonical)
+}
+
+/// Signs `canonical` made at `timestamp` (`YYYYMMDDTHHMMSSZ`): the
+/// signature and the credential scope.
+fn sign(secret_key: &str, region: &str, service: &str, timestamp: &str, canonical: &str) -> (String, String) {
+    let date = &timestamp[..8];
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", timestamp, scope, hex(&sha256(canonical.as_bytes())));
+    let key = signing_key(secret_key, date, region, service);
+    (hex(&hmac_sha256(&key, to_sign.as_bytes())), scope)
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let mut key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    for part in [region, service, "aws4_request"] { key = hmac_sha256(&key, part.as_bytes()); }
+    key
+}
+
+/// Percent-encodes a key as SigV4 wants it, keeping the slashes.
+fn uri_encode(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    for b in key.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() }
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 (FIPS 180-4), enough for signing requests without a crypto crate.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 { message.push(0); }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() { w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]); }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (x, y) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) { *x = x.wrapping_add(y); }
+    }
+    let mut out = [0; 32];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(h) { chunk.copy_from_slice(&word.to_be_bytes()); }
+    out
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 { block[..32].copy_from_slice(&sha256(key)); } else { block[..key.len()].copy_from_slice(key); }
+    let mut inner: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(data);
+    let mut outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> { (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect() }
+
+    #[test]
+    fn sha256_matches_the_fips_examples() {
+        assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        // Two blocks once padded
+        assert_eq!(hex(&sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(hex(&sha256(&vec![b'a'; 1_000_000])), "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        // Test cases 1, 2, 4 and 6 (a key longer than the block)
+        let cases = [
+            ("0b".repeat(20), hex(b"Hi There"), "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            (hex(b"Jefe"), hex(b"what do ya want for nothing?"), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+            ("0102030405060708090a0b0c0d0e0f10111213141516171819".to_string(), "cd".repeat(50), "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b"),
+            ("aa".repeat(131), hex(b"Test Using Larger Than Block-Size Key - Hash Key First"), "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+        ];
+        for (key, data, mac) in cases {
+            assert_eq!(hex(&hmac_sha256(&unhex(&key), &unhex(&data))), mac);
+        }
+    }
+
+    #[test]
+    fn signing_key_matches_the_aws_example() {
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex(&key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+    }
+
+    #[test]
+    fn signs_the_aws_get_object_example() {
+        let empty = hex(&sha256(b""));
+        let headers = [
+            ("host", "examplebucket.s3.amazonaws.com".to_string()),
+            ("range", "bytes=0-9".to_string()),
+            ("x-amz-content-sha256", empty.clone()),
+            ("x-amz-date", "20130524T000000Z".to_string()),
+        ];
+        let (signed, canonical) = canonical_request("GET", "/test.txt", &headers, &empty);
+        assert_eq!(signed, "host;range;x-amz-content-sha256;x-amz-date");
+        let (signature, scope) = sign("wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY", "us-east-1", "s3", "20130524T000000Z", &canonical);
+        assert_eq!(scope, "20130524/us-east-1/s3/aws4_request");
+        assert_eq!(signature, "f0e8bdb87c964420e857bd35b5d6ed310bd44f0170aba48dd91039c6036bdb41");
+    }
+
+    #[test]
+    fn keys_keep_their_slashes() {
+        assert_eq!(uri_encode("runs/hikaru 2024+1.json"), "runs/hikaru%202024%2B1.json");
+    }
+}
@@ -0,0 +1,155 @@
+//! Five-field cron schedules ("minute hour day-of-month month day-of-week"), in UTC.
+
+use crate::date::{civil_from_days, hour_and_weekday};
+
+/// Matching values of each field, as bit sets.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day-of-month / day-of-week were restricted (not starting with
+    /// `*`, as Vixie cron has it); cron matches either one when both are.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// Minutes searched for the next run: a little over four years, enough for Feb 29.
+const SEARCH_MINUTES: i64 = 4 * 366 * 24 * 60;
+
+impl Schedule {
+    /// Parses "*", numbers, ranges "a-b", lists "a,b" and steps "*/n" / "a-b/n".
+    /// Day-of-week is 0-7 with both 0 and 7 meaning Sunday.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [min, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 cron fields, got {}", fields.len()));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 { weekdays |= 1; }
+        Ok(Self {
+            minutes: parse_field(min, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            days_restricted: !day.starts_with('*'),
+            weekdays_restricted: !weekday.starts_with('*'),
+        })
+    }
+
+    /// First matching minute strictly after `unix` (seconds), if any.
+    pub fn next_after(&self, unix: i64) -> Option<i64> {
+        let start = unix.div_euclid(60) + 1;
+        (start..start + SEARCH_MINUTES).map(|m| m * 60).find(|&t| self.matches(t))
+    }
+
+    fn matches(&self, t: i64) -> bool {
+        let bit = |set: u64, v: u32| set & (1 << v) != 0;
+        let (hour, weekday) = hour_and_weekday(t, 0);
+        let (_, month, day) = civil_from_days(t.div_euclid(86_400));
+        // cron counts weekdays from Sunday
+        let cron_weekday = (weekday + 1) % 7;
+        let day_ok = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => bit(self.days, day) || bit(self.weekdays, cron_weekday),
+            _ => bit(self.days, day) && bit(self.weekdays, cron_weekday),
+        };
+        bit(self.minutes, (t.rem_euclid(3600) / 60) as u32) && bit(self.hours, hour) && bit(self.months, month) && day_ok
+    }
+}
+
+fn parse_field(field: &str, lo: u32, hi: u32) -> Result<u64, String> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((r, s)) => (r, s.parse::<u32>().map_err(|_| format!("bad step in {:?}", part))?),
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (lo, hi),
+            r => match r.split_once('-') {
+                Some((a, b)) => (num(a, part)?, num(b, part)?),
+                None => {
+                    let n = num(r, part)?;
+                    (n, if step > 1 { hi } else { n })
+                }
+            },
+        };
+        if step == 0 || from < lo || to > hi || from > to {
+            return Err(format!("{:?} is out of range {}-{}", part, lo, hi));
+        }
+        for v in (from..=to).step_by(step as usize) { set |= 1 << v; }
+    }
+    Ok(set)
+}
+
+fn num(s: &str, part: &str) -> Result<u32, String> {
+    s.parse().map_err(|_| format!("bad value in {:?}", part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::days_from_civil;
+
+    fn at(y: i32, m: u32, d: u32, hour: i64, min: i64) -> i64 { days_from_civil(y, m, d) * 86_400 + hour * 3600 + min * 60 }
+
+    fn next(spec: &str, after: i64) -> Option<i64> { Schedule::parse(spec).unwrap().next_after(after) }
+
+    #[test]
+    fn steps_and_ranges() {
+        assert_eq!(next("*/15 * * * *", at(2024, 1, 1, 0, 7)), Some(at(2024, 1, 1, 0, 15)));
+        // Strictly after
+        assert_eq!(next("*/15 * * * *", at(2024, 1, 1, 0, 15)), Some(at(2024, 1, 1, 0, 30)));
+        // "a/n" runs from a to the end of the range
+        assert_eq!(next("5/20 * * * *", at(2024, 1, 1, 0, 46)), Some(at(2024, 1, 1, 1, 5)));
+        assert_eq!(next("0 9-17/4 * * *", at(2024, 1, 1, 13, 30)), Some(at(2024, 1, 1, 17, 0)));
+        // Weekdays from Friday 2024-01-05 to Monday
+        assert_eq!(next("0 9 * * 1-5", at(2024, 1, 5, 10, 0)), Some(at(2024, 1, 8, 9, 0)));
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        // 2024-01-01 is a Monday
+        assert_eq!(next("0 0 * * 7", at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 7, 0, 0)));
+        assert_eq!(next("0 0 * * 0", at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 7, 0, 0)));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 13th or a Friday
+        let s = Schedule::parse("0 0 13 * 5").unwrap();
+        assert_eq!(s.next_after(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 5, 0, 0)));
+        assert_eq!(s.next_after(at(2024, 1, 12, 0, 0)), Some(at(2024, 1, 13, 0, 0)));
+    }
+
+    #[test]
+    fn a_starred_step_leaves_the_day_unrestricted() {
+        // Odd days that are Mondays, not odd days or Mondays
+        assert_eq!(next("0 0 */2 * 1", at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 15, 0, 0)));
+    }
+
+    #[test]
+    fn leap_days_are_found() {
+        assert_eq!(next("0 0 29 2 *", at(2024, 3, 1, 0, 0)), Some(at(2028, 2, 29, 0, 0)));
+        assert_eq!(next("0 0 31 2 *", at(2024, 1, 1, 0, 0)), None);
+    }
+
+    #[test]
+    fn rejects_bad_fields() {
+        for (spec, error) in [
+            ("* * * *", "expected 5 cron fields, got 4"),
+            ("60 * * * *", "\"60\" is out of range 0-59"),
+            ("* * 0 * *", "\"0\" is out of range 1-31"),
+            ("* * * * 8", "\"8\" is out of range 0-7"),
+            ("5-1 * * * *", "\"5-1\" is out of range 0-59"),
+            ("*/0 * * * *", "\"*/0\" is out of range 0-59"),
+            ("*/x * * * *", "bad step in \"*/x\""),
+            ("a * * * *", "bad value in \"a\""),
+        ] {
+            assert_eq!(Schedule::parse(spec).unwrap_err(), error, "{}", spec);
+        }
+    }
+}
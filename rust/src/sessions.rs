@@ -0,0 +1,74 @@
+//! Playing sessions from game end times, for fatigue and tilt effects.
+
+use crate::report::{GameGroup, GameResult};
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// Games from this position in a session on are reported together.
+const MAX_INDEX: usize = 10;
+
+#[derive(Serialize, Debug)]
+pub struct SessionStats {
+    /// Longest break, in minutes, between games of one session.
+    pub gap_minutes: u64,
+    pub sessions: usize,
+    /// Games by their 1-based position in the session; the last entry holds
+    /// every game from position 10 on.
+    pub by_position: Vec<GameGroup>,
+    /// Games played right after a loss, or after a win or draw, in the same session.
+    pub after_loss: GameGroup,
+    pub after_non_loss: GameGroup,
+}
+
+impl SessionStats {
+    pub fn from_games(games: &[GameResult], target: &str, gap_minutes: u64) -> Option<Self> {
+        let mut timed: Vec<&GameResult> = games.iter().filter(|g| g.end_time.is_some()).collect();
+        if timed.is_empty() { return None; }
+        timed.sort_by_key(|g| g.end_time);
+
+        let mut sessions: Vec<Vec<&GameResult>> = Vec::new();
+        for g in timed {
+            match sessions.last_mut() {
+                Some(s) if g.end_time? - s.last()?.end_time? <= gap_minutes as i64 * 60 => s.push(g),
+                _ => sessions.push(vec![g]),
+            }
+        }
+
+        let mut by_position: Vec<Vec<&GameResult>> = vec![Vec::new(); MAX_INDEX];
+        let (mut after_loss, mut after_non_loss) = (Vec::new(), Vec::new());
+        for s in &sessions {
+            for (i, &g) in s.iter().enumerate() {
+                by_position[i.min(MAX_INDEX - 1)].push(g);
+                match i.checked_sub(1).and_then(|p| s[p].score_for(target)) {
+                    Some(0.0) => after_loss.push(g),
+                    Some(_) => after_non_loss.push(g),
+                    None => {}
+                }
+            }
+        }
+        while by_position.last().is_some_and(Vec::is_empty) { by_position.pop(); }
+
+        Some(Self {
+            gap_minutes,
+            sessions: sessions.len(),
+            by_position: by_position.iter().map(|g| GameGroup::of(g, target)).collect(),
+            after_loss: GameGroup::of(&after_loss, target),
+            after_non_loss: GameGroup::of(&after_non_loss, target),
+        })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let score = |g: &GameGroup| g.avg_score.map_or("-".to_string(), |s| format!("{:.0}%", 100.0 * s));
+        writeln!(out, "\nSessions ({} sessions, gap > {} min)", self.sessions, self.gap_minutes)?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<12} {:>6} {:>9} {:>7}", "Game #", "Games", "Accuracy", "Score")?;
+        for (i, g) in self.by_position.iter().enumerate() {
+            let label = if i + 1 == MAX_INDEX { format!("{}+", MAX_INDEX) } else { (i + 1).to_string() };
+            writeln!(out, "{:<12} {:>6} {:>8.2}% {:>7}", label, g.games, g.avg_accuracy, score(g))?;
+        }
+        for (label, g) in [("After loss", &self.after_loss), ("After W/D", &self.after_non_loss)] {
+            writeln!(out, "{:<12} {:>6} {:>8.2}% {:>7}", label, g.games, g.avg_accuracy, score(g))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Accuracy weighted by how sharp each position was, so that errors in sharp
+//! positions cost more than slips in quiet ones.
+
+use crate::analysis::MoveRecord;
+use crate::engine::Wdl;
+use serde::Serialize;
+
+/// What makes a position sharp.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SharpnessScheme {
+    /// Entropy of the win/draw/loss forecast: all three results still likely.
+    Entropy,
+    /// Gap between the best and second-best move (needs --multipv 2).
+    Criticality,
+}
+
+impl SharpnessScheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Entropy => "entropy",
+            Self::Criticality => "criticality",
+        }
+    }
+}
+
+/// Entropy of a WDL, divided by its maximum (log 3) to lie in 0..=1.
+pub fn wdl_entropy(wdl: Wdl) -> f64 {
+    let total = (wdl.win + wdl.draw + wdl.loss).max(1) as f64;
+    let h: f64 = [wdl.win, wdl.draw, wdl.loss].iter()
+        .map(|&n| n as f64 / total)
+        .filter(|&p| p > 0.0)
+        .map(|p| -p * p.ln())
+        .sum();
+    h / 3f64.ln()
+}
+
+/// Sharpness of the position before the move, 0 to 1.
+fn sharpness(r: &MoveRecord, scheme: SharpnessScheme) -> f64 {
+    match scheme {
+        SharpnessScheme::Entropy => r.entropy,
+        SharpnessScheme::Criticality => r.criticality.unwrap_or(0.0).min(1.0),
+    }
+}
+
+/// One side's accuracy with each move weighted by 1 + strength × sharpness,
+/// over the moves that count for plain accuracy; `None` without such moves.
+pub fn weighted_accuracy(records: &[MoveRecord], is_white: bool, scheme: SharpnessScheme, strength: f64) -> Option<f64> {
+    let (mut sum, mut weights) = (0.0, 0.0);
+    for r in records.iter().filter(|r| r.is_white == is_white && !r.in_book && !r.scramble) {
+        let w = 1.0 + strength * sharpness(r, scheme);
+        sum += w * r.accuracy;
+        weights += w;
+    }
+    (weights > 0.0).then(|| sum / weights)
+}
+
+/// The player's sharpness-weighted accuracy over all games.
+#[derive(Serialize, Debug)]
+pub struct SharpAccuracy {
+    pub scheme: SharpnessScheme,
+    pub strength: f64,
+    /// Mean over games, like the plain average accuracy.
+    pub accuracy: f64,
+}
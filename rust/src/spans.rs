@@ -0,0 +1,204 @@
+//! Timed spans for `--profile`, written as a Chrome trace or folded stacks,
+//! and summed per pipeline stage by the `profile` subcommand.
+//!
+//! Spans cost one relaxed load while profiling is off. When on, each closed
+//! span is appended to a process-wide list together with its stack path and
+//! self time (its duration minus the time of its child spans).
+
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+static EVENTS: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD: Cell<u64> = const { Cell::new(0) };
+    static STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Frame {
+    name: &'static str,
+    child_us: u64,
+}
+
+/// One closed span.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub name: &'static str,
+    /// Enclosing spans and this one, joined with ';'.
+    pub path: String,
+    pub thread: u64,
+    /// Start since profiling was enabled, in microseconds.
+    pub start_us: u64,
+    pub dur_us: u64,
+    pub self_us: u64,
+}
+
+/// Starts recording spans.
+pub fn enable() {
+    EPOCH.get_or_init(Instant::now);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn enabled() -> bool { ENABLED.load(Ordering::Relaxed) }
+
+/// Closes the span when dropped.
+#[must_use]
+pub struct Span(Option<Instant>);
+
+/// Opens a span named `name` on the current thread.
+#[inline]
+pub fn span(name: &'static str) -> Span {
+    if !enabled() { return Span(None); }
+    STACK.with(|s| s.borrow_mut().push(Frame { name, child_us: 0 }));
+    Span(Some(Instant::now()))
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(start) = self.0 else { return };
+        let dur_us = start.elapsed().as_micros() as u64;
+        let start_us = start.saturating_duration_since(*EPOCH.get_or_init(Instant::now)).as_micros() as u64;
+        let (frame, path) = STACK.with(|s| {
+            let mut s = s.borrow_mut();
+            let frame = s.pop().expect("span stack underflow");
+            if let Some(parent) = s.last_mut() { parent.child_us += dur_us; }
+            let mut path: String = s.iter().map(|f| f.name).collect::<Vec<_>>().join(";");
+            if !path.is_empty() { path.push(';'); }
+            path.push_str(frame.name);
+            (frame, path)
+        });
+        let thread = THREAD.with(|t| {
+            if t.get() == 0 { t.set(NEXT_THREAD.fetch_add(1, Ordering::Relaxed)); }
+            t.get()
+        });
+        let event = Event {
+            name: frame.name,
+            path,
+            thread,
+            start_us,
+            dur_us,
+            self_us: dur_us.saturating_sub(frame.child_us),
+        };
+        EVENTS.lock().unwrap().push(event);
+    }
+}
+
+/// Takes all spans closed so far.
+pub fn take() -> Vec<Event> {
+    std::mem::take(&mut *EVENTS.lock().unwrap())
+}
+
+/// Chrome trace event format, for chrome://tracing or Perfetto.
+pub fn write_chrome(out: &mut impl Write, events: &[Event]) -> io::Result<()> {
+    let trace: Vec<_> = events.iter().map(|e| serde_json::json!({
+        "name": e.name,
+        "ph": "X",
+        "ts": e.start_us,
+        "dur": e.dur_us,
+        "pid": 1,
+        "tid": e.thread,
+    })).collect();
+    serde_json::to_writer(&mut *out, &serde_json::json!({ "traceEvents": trace }))?;
+    writeln!(out)
+}
+
+/// Folded stacks ("fetch;parse 1234", self time in microseconds), the input
+/// of flamegraph.pl and inferno.
+pub fn write_folded(out: &mut impl Write, events: &[Event]) -> io::Result<()> {
+    let mut totals = std::collections::BTreeMap::new();
+    for e in events { *totals.entry(e.path.as_str()).or_insert(0u64) += e.self_us; }
+    for (path, us) in totals {
+        writeln!(out, "{} {}", path, us)?;
+    }
+    Ok(())
+}
+
+/// Writes `events` to `path`: a Chrome trace when it ends in `.json`,
+/// folded stacks otherwise.
+pub fn write_file(path: &Path, events: &[Event]) -> io::Result<()> {
+    let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+    if path.extension().is_some_and(|e| e == "json") {
+        write_chrome(&mut out, events)?;
+    } else {
+        write_folded(&mut out, events)?;
+    }
+    out.flush()
+}
+
+/// Spans of the pipeline, in pipeline order, with their report names.
+/// Self time of any other span (the rest of a game's bookkeeping) is
+/// reported as "Other".
+pub const STAGES: [(&str, &str); 10] = [
+    ("http", "HTTP"),
+    ("json", "JSON decode"),
+    ("parse", "PGN headers"),
+    ("tokenize", "PGN tokenize"),
+    ("replay", "SAN replay"),
+    ("fen", "FEN generation"),
+    ("engine_start", "Engine start"),
+    ("engine", "Engine wait"),
+    ("accuracy", "Accuracy math"),
+    ("other", "Other"),
+];
+
+/// Self time of one stage over a run.
+#[derive(Serialize, Debug)]
+pub struct StageRow {
+    pub stage: &'static str,
+    pub spans: usize,
+    pub total_ms: f64,
+    pub per_game_ms: f64,
+    /// Share of all recorded time.
+    pub share: f64,
+}
+
+/// Where a run spent its time, stage by stage, from its spans. Times add up
+/// across worker threads, so with several workers they exceed the wall time.
+#[derive(Serialize, Debug)]
+pub struct StageProfile {
+    pub games: usize,
+    pub wall_ms: f64,
+    pub rows: Vec<StageRow>,
+}
+
+impl StageProfile {
+    pub fn from_events(events: &[Event], games: usize, wall_ms: f64) -> Self {
+        let mut totals = [(0usize, 0u64); STAGES.len()];
+        for e in events {
+            let i = STAGES.iter().position(|s| s.0 == e.name).unwrap_or(STAGES.len() - 1);
+            totals[i].0 += 1;
+            totals[i].1 += e.self_us;
+        }
+        let all = totals.iter().map(|t| t.1).sum::<u64>().max(1) as f64;
+        let rows = STAGES.iter().zip(totals)
+            .filter(|(_, (spans, _))| *spans > 0)
+            .map(|(&(_, stage), (spans, us))| StageRow {
+                stage,
+                spans,
+                total_ms: us as f64 / 1000.0,
+                per_game_ms: us as f64 / 1000.0 / games.max(1) as f64,
+                share: us as f64 / all,
+            })
+            .collect();
+        Self { games, wall_ms, rows }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nStage Profile ({} games, {:.0} ms wall)", self.games, self.wall_ms)?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<16} {:>8} {:>11} {:>10} {:>6}", "Stage", "Spans", "Total ms", "ms/game", "Share")?;
+        for r in &self.rows {
+            writeln!(out, "{:<16} {:>8} {:>11.1} {:>10.2} {:>5.1}%", r.stage, r.spans, r.total_ms, r.per_game_ms, 100.0 * r.share)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Splitting a large PGN into one file per player, month or event.
+
+use crate::pgn::{tag_value, GameReader};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SplitBy {
+    /// One file per player; a game goes to both its players' files
+    Player,
+    /// One file per month of the Date tag, e.g. 2024-01.pgn
+    Month,
+    /// One file per Event tag
+    Event,
+}
+
+impl SplitBy {
+    /// The groups `game` belongs in: lowercase player names, months or
+    /// events. [`FileStems`] turns them into file names.
+    pub fn keys(self, game: &str) -> Vec<String> {
+        let tag = |name| tag_value(game, name).filter(|v| !v.is_empty() && !v.starts_with('?'));
+        match self {
+            Self::Player => {
+                let mut names: Vec<String> = ["White", "Black"].iter().filter_map(|&t| tag(t)).map(str::to_lowercase).collect();
+                names.dedup();
+                if names.is_empty() { vec!["unknown".into()] } else { names }
+            }
+            Self::Month => vec![tag("Date")
+                .and_then(|d| { let (y, rest) = d.split_once('.')?; let m = rest.split('.').next()?; Some(format!("{}-{}", y, m)) })
+                .filter(|m| !m.contains('?'))
+                .unwrap_or_else(|| "unknown".into())],
+            Self::Event => vec![tag("Event").map_or_else(|| "unknown".into(), str::to_string)],
+        }
+    }
+}
+
+/// `name` with anything but letters, digits (in any script) and '-'
+/// replaced by '_', so it is safe as a file name.
+fn file_stem(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+/// File names handed out to the keys of a split: a key that reads like
+/// one already given a name ("a/b" after "a_b") gets "_2", "_3", ...
+#[derive(Default)]
+pub struct FileStems {
+    by_key: HashMap<String, String>,
+    taken: HashSet<String>,
+}
+
+impl FileStems {
+    pub fn stem(&mut self, key: &str) -> &str {
+        if !self.by_key.contains_key(key) {
+            let base = file_stem(key);
+            let mut stem = base.clone();
+            for n in 2.. {
+                if !self.taken.contains(&stem) { break; }
+                stem = format!("{}_{}", base, n);
+            }
+            self.taken.insert(stem.clone());
+            self.by_key.insert(key.to_string(), stem);
+        }
+        &self.by_key[key]
+    }
+}
+
+/// Games written to each file.
+pub struct SplitStats {
+    pub games: usize,
+    pub files: BTreeMap<PathBuf, usize>,
+}
+
+/// Output files kept open at once; the rest are reopened to append.
+const MAX_OPEN_FILES: usize = 256;
+
+/// Reads games from `input` one at a time and appends each to its files
+/// in `dir`, which is created if needed. Existing files are overwritten.
+pub fn split_pgn(input: impl BufRead, by: SplitBy, dir: &Path) -> io::Result<SplitStats> {
+    std::fs::create_dir_all(dir)?;
+    let mut writers: BTreeMap<PathBuf, BufWriter<File>> = BTreeMap::new();
+    let mut stats = SplitStats { games: 0, files: BTreeMap::new() };
+    let mut stems = FileStems::default();
+    for game in GameReader::new(input) {
+        let game = game?;
+        for key in by.keys(&game) {
+            let path = dir.join(format!("{}.pgn", stems.stem(&key)));
+            if !writers.contains_key(&path) {
+                if writers.len() >= MAX_OPEN_FILES {
+                    for (_, mut out) in std::mem::take(&mut writers) { out.flush()?; }
+                }
+                let file = if stats.files.contains_key(&path) {
+                    std::fs::OpenOptions::new().append(true).open(&path)?
+                } else {
+                    File::create(&path)?
+                };
+                writers.insert(path.clone(), BufWriter::new(file));
+            }
+            let out = writers.get_mut(&path).expect("writer opened above");
+            out.write_all(game.trim_end().as_bytes())?;
+            out.write_all(b"\n\n")?;
+            *stats.files.entry(path).or_insert(0) += 1;
+        }
+        stats.games += 1;
+    }
+    for (_, mut out) in writers { out.flush()?; }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(tags: &[(&str, &str)]) -> String {
+        let tags: String = tags.iter().map(|(n, v)| format!("[{} \"{}\"]\n", n, v)).collect();
+        format!("{}\n1. e4 e5 *\n", tags)
+    }
+
+    #[test]
+    fn keys_per_player_month_and_event() {
+        let g = game(&[("Event", "Tata Steel"), ("Date", "2024.01.15"), ("White", "Hikaru"), ("Black", "MagnusCarlsen")]);
+        assert_eq!(SplitBy::Player.keys(&g), ["hikaru", "magnuscarlsen"]);
+        assert_eq!(SplitBy::Month.keys(&g), ["2024-01"]);
+        assert_eq!(SplitBy::Event.keys(&g), ["Tata Steel"]);
+
+        let blank = game(&[("Event", "?"), ("Date", "2024.??.??"), ("White", "?"), ("Black", "")]);
+        for by in [SplitBy::Player, SplitBy::Month, SplitBy::Event] { assert_eq!(by.keys(&blank), ["unknown"]); }
+        assert_eq!(SplitBy::Player.keys(&game(&[("White", "Same"), ("Black", "same")])), ["same"]);
+    }
+
+    #[test]
+    fn stems_keep_other_scripts_and_number_clashes() {
+        let mut stems = FileStems::default();
+        assert_eq!(stems.stem("иванов"), "иванов");
+        assert_eq!(stems.stem("петров"), "петров");
+        assert_eq!(stems.stem("Šarić"), "Šarić");
+        assert_eq!(stems.stem("a/b"), "a_b");
+        assert_eq!(stems.stem("a b"), "a_b_2");
+        assert_eq!(stems.stem("a_b_2"), "a_b_2_2");
+        assert_eq!(stems.stem("a b"), "a_b_2");
+    }
+}
@@ -0,0 +1,124 @@
+//! The same positions searched several times with the same settings: how
+//! much the evals and best moves move between searches. Multi-threaded
+//! search is not deterministic, so a run's accuracy carries this noise.
+
+use crate::analysis::capped_cp;
+use crate::engine::EngineInfo;
+use crate::enginecmp::{EngineRun, TestPosition, DIVERGENCE_CP};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+/// Positions listed in [`EvalStability::least_stable`].
+pub const LEAST_STABLE: usize = 5;
+
+/// How one position came out across the searches.
+#[derive(Serialize, Debug)]
+pub struct PositionSpread {
+    pub fen: String,
+    /// White's view, in centipawns capped at ±1000.
+    pub min_cp: i32,
+    pub max_cp: i32,
+    pub std_dev_cp: f64,
+    /// Distinct best moves, most often found first.
+    pub best_moves: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct EvalStability {
+    pub engine: EngineInfo,
+    pub positions: usize,
+    pub repeats: usize,
+    /// Standard deviation of each position's evals, in centipawns.
+    pub mean_std_dev_cp: f64,
+    pub p90_std_dev_cp: f64,
+    /// Largest difference between two searches of one position.
+    pub max_range_cp: i32,
+    /// Share of positions whose evals differ by more than [`DIVERGENCE_CP`]
+    /// between some two searches.
+    pub divergent: f64,
+    /// Share of positions where the searches did not all find the same best
+    /// move.
+    pub bestmove_flips: f64,
+    /// Share of searches finding a position's most common best move.
+    pub bestmove_agreement: f64,
+    /// Seconds of each search of the whole set.
+    pub seconds: Vec<f64>,
+    /// Positions with the widest eval range, at most [`LEAST_STABLE`].
+    pub least_stable: Vec<PositionSpread>,
+}
+
+impl EvalStability {
+    /// Compares `runs`, each the evals of `positions` in order.
+    pub fn new(positions: &[TestPosition], runs: Vec<EngineRun>) -> Self {
+        let n = runs.iter().map(|r| r.evals.len()).min().unwrap_or(0).min(positions.len());
+        let repeats = runs.len();
+        let mut spreads = Vec::with_capacity(n);
+        let (mut flips, mut agreeing) = (0, 0);
+        for (i, p) in positions.iter().take(n).enumerate() {
+            let cps: Vec<f64> = runs.iter().map(|r| capped_cp(r.evals[i].score, true) as f64).collect();
+            let mean = cps.iter().sum::<f64>() / repeats as f64;
+            let variance = if repeats > 1 { cps.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (repeats - 1) as f64 } else { 0.0 };
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for r in &runs {
+                let best = r.evals[i].best_move.as_ref().map_or("-".to_string(), |m| m.to_string());
+                *counts.entry(best).or_default() += 1;
+            }
+            let mut best_moves: Vec<(String, usize)> = counts.into_iter().collect();
+            best_moves.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            if best_moves.len() > 1 { flips += 1; }
+            agreeing += best_moves[0].1;
+            spreads.push(PositionSpread {
+                fen: p.fen.clone(),
+                min_cp: cps.iter().fold(f64::INFINITY, |a, &b| a.min(b)) as i32,
+                max_cp: cps.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b)) as i32,
+                std_dev_cp: variance.sqrt(),
+                best_moves: best_moves.into_iter().map(|m| m.0).collect(),
+            });
+        }
+        let share = |count: usize, of: usize| if of == 0 { 0.0 } else { count as f64 / of as f64 };
+        let mut std_devs: Vec<f64> = spreads.iter().map(|s| s.std_dev_cp).collect();
+        std_devs.sort_by(f64::total_cmp);
+        let range = |s: &PositionSpread| s.max_cp - s.min_cp;
+        let divergent = spreads.iter().filter(|s| range(s) > DIVERGENCE_CP).count();
+        let max_range_cp = spreads.iter().map(range).max().unwrap_or(0);
+        // Widest first, earlier positions first among equals
+        spreads.sort_by_key(|s| std::cmp::Reverse(range(s)));
+        spreads.retain(|s| range(s) > 0 || s.best_moves.len() > 1);
+        spreads.truncate(LEAST_STABLE);
+        Self {
+            engine: runs.first().map(|r| r.info.clone()).unwrap_or_default(),
+            positions: n,
+            repeats,
+            mean_std_dev_cp: if n == 0 { 0.0 } else { std_devs.iter().sum::<f64>() / n as f64 },
+            p90_std_dev_cp: std_devs.get((n * 9 / 10).min(n.saturating_sub(1))).copied().unwrap_or(0.0),
+            max_range_cp,
+            divergent: share(divergent, n),
+            bestmove_flips: share(flips, n),
+            bestmove_agreement: share(agreeing, n * repeats),
+            seconds: runs.iter().map(|r| r.seconds).collect(),
+            least_stable: spreads,
+        }
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nEval Stability")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "Engine: {}", self.engine.describe())?;
+        writeln!(out, "Positions: {}, each searched {} times", self.positions, self.repeats)?;
+        writeln!(out, "Eval std dev: mean {:.1}cp, p90 {:.1}cp; max range {}cp", self.mean_std_dev_cp, self.p90_std_dev_cp, self.max_range_cp)?;
+        writeln!(out, "Divergent (>{}cp apart): {:.1}%", DIVERGENCE_CP, 100.0 * self.divergent)?;
+        writeln!(out, "Best move flips: {:.1}% of positions ({:.1}% of searches agree with the most common)",
+            100.0 * self.bestmove_flips, 100.0 * self.bestmove_agreement)?;
+        let times: Vec<String> = self.seconds.iter().map(|s| format!("{:.2}", s)).collect();
+        writeln!(out, "Time per pass (s): {}", times.join(" "))?;
+        if !self.least_stable.is_empty() {
+            writeln!(out, "\nLeast stable positions")?;
+            writeln!(out, "{:>6} {:>6} {:>7}  {:<20} FEN", "Min", "Max", "Std dev", "Best moves")?;
+            for s in &self.least_stable {
+                writeln!(out, "{:>6} {:>6} {:>7.1}  {:<20} {}", s.min_cp, s.max_cp, s.std_dev_cp, s.best_moves.join(" "), s.fen)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+//! Student t tests and bootstrap intervals for telling a real difference
+//! between runs from noise.
+
+use crate::accuracy::mean;
+use serde::Serialize;
+
+/// Significance level used when reporting a difference as significant.
+pub const ALPHA: f64 = 0.05;
+
+/// A two-sided t test of mean(a) against mean(b).
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct TTest {
+    /// Paired over matched values, or Welch's test for independent samples.
+    pub paired: bool,
+    /// Pairs, or the smaller of the two sample sizes.
+    pub n: usize,
+    pub mean_a: f64,
+    pub mean_b: f64,
+    pub t: f64,
+    pub df: f64,
+    pub p: f64,
+}
+
+impl TTest {
+    pub fn diff(&self) -> f64 { self.mean_a - self.mean_b }
+
+    pub fn significant(&self) -> bool { self.p < ALPHA }
+
+    /// "+1.23 (paired t = 2.10, p = 0.041, significant)".
+    pub fn describe(&self, precision: usize) -> String {
+        let p = if self.p < 0.001 { "p < 0.001".to_string() } else { format!("p = {:.3}", self.p) };
+        format!("{:+.prec$} ({} t = {:.2}, {}{})", self.diff(), if self.paired { "paired" } else { "Welch" },
+            self.t, p, if self.significant() { ", significant" } else { "" }, prec = precision)
+    }
+}
+
+fn variance(values: &[f64]) -> f64 {
+    let m = mean(values);
+    values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / (values.len() as f64 - 1.0)
+}
+
+/// Two-sided p-value of `t`, or 1/0 when there is no spread to test against.
+fn p_value(t: f64, df: f64) -> f64 {
+    if t.is_nan() { 1.0 } else if t.is_infinite() { 0.0 } else { incomplete_beta(df / 2.0, 0.5, df / (df + t * t)) }
+}
+
+/// Paired test over `a[i] - b[i]`; `None` below two pairs.
+pub fn paired(a: &[f64], b: &[f64]) -> Option<TTest> {
+    let n = a.len().min(b.len());
+    if n < 2 { return None; }
+    let d: Vec<f64> = (0..n).map(|i| a[i] - b[i]).collect();
+    let (m, se) = (mean(&d), (variance(&d) / n as f64).sqrt());
+    let t = if se == 0.0 { if m == 0.0 { f64::NAN } else { m.signum() * f64::INFINITY } } else { m / se };
+    let df = n as f64 - 1.0;
+    Some(TTest { paired: true, n, mean_a: mean(&a[..n]), mean_b: mean(&b[..n]), t, df, p: p_value(t, df) })
+}
+
+/// Welch's unequal-variance test; `None` unless both samples have two values.
+pub fn welch(a: &[f64], b: &[f64]) -> Option<TTest> {
+    if a.len() < 2 || b.len() < 2 { return None; }
+    let (na, nb) = (a.len() as f64, b.len() as f64);
+    let (va, vb) = (variance(a) / na, variance(b) / nb);
+    let diff = mean(a) - mean(b);
+    let se = (va + vb).sqrt();
+    let (t, df) = if se == 0.0 {
+        (if diff == 0.0 { f64::NAN } else { diff.signum() * f64::INFINITY }, na + nb - 2.0)
+    } else {
+        (diff / se, (va + vb).powi(2) / (va * va / (na - 1.0) + vb * vb / (nb - 1.0)))
+    };
+    Some(TTest { paired: false, n: a.len().min(b.len()), mean_a: mean(a), mean_b: mean(b), t, df, p: p_value(t, df) })
+}
+
+/// Resamples drawn for a bootstrap interval.
+pub const BOOTSTRAP_RESAMPLES: usize = 2000;
+
+/// Percentile bootstrap interval of the mean at `level` (e.g. 0.95), from
+/// [`BOOTSTRAP_RESAMPLES`] resamples with a fixed seed so reruns agree;
+/// `None` below two values.
+pub fn bootstrap_mean_ci(values: &[f64], level: f64) -> Option<(f64, f64)> {
+    if values.len() < 2 { return None; }
+    let mut rng = SplitMix64(0x5eed_c0ff_ee00_0001);
+    let n = values.len();
+    let mut means: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| (0..n).map(|_| values[rng.below(n)]).sum::<f64>() / n as f64)
+        .collect();
+    means.sort_unstable_by(f64::total_cmp);
+    let tail = (1.0 - level) / 2.0;
+    let at = |q: f64| means[((q * BOOTSTRAP_RESAMPLES as f64) as usize).min(BOOTSTRAP_RESAMPLES - 1)];
+    Some((at(tail), at(1.0 - tail)))
+}
+
+/// Small deterministic generator for resampling.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        ((self.next() as u128 * n as u128) >> 64) as usize
+    }
+}
+
+/// ln Γ(x) by the Lanczos approximation (g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const C: [f64; 9] = [
+        0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8,
+        771.323_428_777_653_1, -176.615_029_162_140_6, 12.507_343_278_686_905,
+        -0.138_571_095_265_720_12, 9.984_369_578_019_572e-6, 1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = C[0] + C[1..].iter().enumerate().map(|(i, c)| c / (x + i as f64 + 1.0)).sum::<f64>();
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Regularized incomplete beta function I_x(a, b).
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 { return 0.0; }
+    if x >= 1.0 { return 1.0; }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges fast only on this side of the mean
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Continued fraction of the incomplete beta function (modified Lentz).
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY { d = TINY; }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for num in [m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+                    -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0))] {
+            d = 1.0 + num * d;
+            if d.abs() < TINY { d = TINY; }
+            c = 1.0 + num / c;
+            if c.abs() < TINY { c = TINY; }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-12 { break; }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn p_values_match_t_tables() {
+        // Two-sided 5% critical values
+        close(p_value(2.228, 10.0), 0.050012, 1e-5);
+        close(p_value(-2.228, 10.0), 0.050012, 1e-5);
+        close(p_value(12.706, 1.0), 0.050001, 1e-5);
+        close(p_value(2.0, 30.0), 0.054625, 1e-5);
+        assert_eq!(p_value(0.0, 5.0), 1.0);
+        assert_eq!(p_value(f64::NAN, 5.0), 1.0);
+        assert_eq!(p_value(f64::INFINITY, 5.0), 0.0);
+    }
+
+    #[test]
+    fn ln_gamma_matches_known_values() {
+        close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-10);
+        close(ln_gamma(1.0), 0.0, 1e-10);
+        close(ln_gamma(10.0), 362880f64.ln(), 1e-10);
+        close(ln_gamma(100.5), 361.435540467778, 1e-8);
+    }
+
+    #[test]
+    fn incomplete_beta_matches_closed_forms() {
+        close(incomplete_beta(1.0, 1.0, 0.3), 0.3, 1e-10);
+        close(incomplete_beta(2.0, 1.0, 0.3), 0.09, 1e-10);
+        close(incomplete_beta(1.0, 3.0, 0.25), 1.0 - 0.75f64.powi(3), 1e-10);
+        close(incomplete_beta(2.0, 2.0, 0.5), 0.5, 1e-10);
+        close(incomplete_beta(2.0, 3.0, 0.4), 0.5248, 1e-10);
+        assert_eq!(incomplete_beta(2.0, 3.0, 0.0), 0.0);
+        assert_eq!(incomplete_beta(2.0, 3.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn paired_test_of_differences() {
+        let t = paired(&[1.0, 2.0, 3.0, 4.0], &[0.0; 4]).unwrap();
+        close(t.t, 15f64.sqrt(), 1e-10);
+        assert_eq!(t.df, 3.0);
+        close(t.p, 0.030466, 1e-5);
+        assert!(t.significant());
+        assert!(paired(&[1.0], &[2.0]).is_none());
+    }
+
+    #[test]
+    fn welch_test_of_unequal_variances() {
+        let t = welch(&[1.0, 2.0, 3.0, 4.0, 5.0], &[2.0, 4.0, 6.0, 8.0, 10.0, 12.0]).unwrap();
+        close(t.t, -2.376354, 1e-5);
+        close(t.df, 6.972256, 1e-5);
+        close(t.p, 0.049284, 1e-5);
+        assert_eq!(t.n, 5);
+    }
+}
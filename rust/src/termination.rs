@@ -0,0 +1,114 @@
+//! How games ended, from the Termination header, with accuracy per kind.
+
+use crate::report::GameResult;
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    Checkmate,
+    Resignation,
+    Timeout,
+    Abandonment,
+    Agreement,
+    Repetition,
+    /// Stalemate, insufficient material, the 50-move rule, or a draw of
+    /// unknown cause.
+    OtherDraw,
+    Other,
+}
+
+impl Termination {
+    pub const ALL: [Termination; 8] = [
+        Termination::Checkmate,
+        Termination::Resignation,
+        Termination::Timeout,
+        Termination::Abandonment,
+        Termination::Agreement,
+        Termination::Repetition,
+        Termination::OtherDraw,
+        Termination::Other,
+    ];
+
+    /// Reads chess.com ("hikaru won on time", "Game drawn by repetition") and
+    /// Lichess ("Normal", "Time forfeit") Termination tags. Lichess "Normal"
+    /// covers both mate and resignation, told apart by a final `#`.
+    pub fn classify(termination: &str, result: Option<&str>, last_san: Option<&str>) -> Self {
+        let t = termination.to_lowercase();
+        let drawn = t.contains("drawn") || result == Some("1/2-1/2");
+        if t.contains("abandon") { Self::Abandonment }
+        else if drawn && t.contains("agreement") { Self::Agreement }
+        else if drawn && t.contains("repetition") { Self::Repetition }
+        else if drawn { Self::OtherDraw }
+        else if t.contains("checkmate") { Self::Checkmate }
+        else if t.contains("resignation") { Self::Resignation }
+        else if t.contains("on time") || t.contains("time forfeit") { Self::Timeout }
+        else if t == "normal" {
+            if last_san.is_some_and(|s| s.ends_with('#')) { Self::Checkmate } else { Self::Resignation }
+        }
+        else { Self::Other }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Checkmate => "checkmate",
+            Self::Resignation => "resignation",
+            Self::Timeout => "timeout",
+            Self::Abandonment => "abandonment",
+            Self::Agreement => "agreement",
+            Self::Repetition => "repetition",
+            Self::OtherDraw => "other draw",
+            Self::Other => "other",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TerminationRow {
+    pub kind: Termination,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub accuracy: f64,
+    pub opponent_accuracy: f64,
+}
+
+/// The player's results and accuracy per way the game ended.
+#[derive(Serialize, Debug)]
+pub struct TerminationStats {
+    pub rows: Vec<TerminationRow>,
+}
+
+impl TerminationStats {
+    pub fn from_games(games: &[GameResult], target: &str) -> Option<Self> {
+        let rows: Vec<TerminationRow> = Termination::ALL.iter().filter_map(|&kind| {
+            let group: Vec<&GameResult> = games.iter().filter(|g| g.termination == Some(kind)).collect();
+            if group.is_empty() { return None; }
+            let count = |s: f64| group.iter().filter(|g| g.score_for(target) == Some(s)).count();
+            let n = group.len() as f64;
+            Some(TerminationRow {
+                kind,
+                games: group.len(),
+                wins: count(1.0),
+                draws: count(0.5),
+                losses: count(0.0),
+                accuracy: group.iter().map(|g| g.accuracy_for(target)).sum::<f64>() / n,
+                opponent_accuracy: group.iter().map(|g| g.accuracy_for_opponent(target)).sum::<f64>() / n,
+            })
+        }).collect();
+        (!rows.is_empty()).then_some(Self { rows })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "\nTermination")?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<12} {:>6} {:>11} {:>9} {:>9}", "Ended by", "Games", "W/D/L", "Accuracy", "Opponent")?;
+        for r in &self.rows {
+            writeln!(out, "{:<12} {:>6} {:>11} {:>8.2}% {:>8.2}%", r.kind.as_str(), r.games,
+                format!("{}/{}/{}", r.wins, r.draws, r.losses), r.accuracy, r.opponent_accuracy)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Accuracy and results by local hour of day and weekday.
+
+use crate::date::hour_and_weekday;
+use crate::report::{GameGroup, GameResult};
+use serde::Serialize;
+use std::io::{self, Write};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+#[derive(Serialize, Debug)]
+pub struct HourGroup {
+    pub hour: u32,
+    #[serde(flatten)]
+    pub group: GameGroup,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WeekdayGroup {
+    pub weekday: &'static str,
+    #[serde(flatten)]
+    pub group: GameGroup,
+}
+
+/// Games grouped by when they ended, in the chosen time zone.
+#[derive(Serialize, Debug)]
+pub struct TimeOfDay {
+    /// Offset from UTC in seconds.
+    pub utc_offset_s: i64,
+    pub by_hour: Vec<HourGroup>,
+    pub by_weekday: Vec<WeekdayGroup>,
+}
+
+impl TimeOfDay {
+    pub fn from_games(games: &[GameResult], target: &str, utc_offset_s: i64) -> Option<Self> {
+        let mut hours: Vec<Vec<&GameResult>> = vec![Vec::new(); 24];
+        let mut days: Vec<Vec<&GameResult>> = vec![Vec::new(); 7];
+        for g in games {
+            let Some(t) = g.end_time else { continue };
+            let (h, d) = hour_and_weekday(t, utc_offset_s);
+            hours[h as usize].push(g);
+            days[d as usize].push(g);
+        }
+        if hours.iter().all(Vec::is_empty) { return None; }
+        Some(Self {
+            utc_offset_s,
+            by_hour: hours.iter().enumerate().filter(|(_, g)| !g.is_empty())
+                .map(|(h, g)| HourGroup { hour: h as u32, group: GameGroup::of(g, target) }).collect(),
+            by_weekday: days.iter().enumerate().filter(|(_, g)| !g.is_empty())
+                .map(|(d, g)| WeekdayGroup { weekday: WEEKDAYS[d], group: GameGroup::of(g, target) }).collect(),
+        })
+    }
+
+    pub fn write_text(&self, out: &mut dyn Write) -> io::Result<()> {
+        let row = |out: &mut dyn Write, label: &str, g: &GameGroup| writeln!(out, "{:<8} {:>6} {:>8.2}% {:>7}",
+            label, g.games, g.avg_accuracy, g.avg_score.map_or("-".to_string(), |s| format!("{:.0}%", 100.0 * s)));
+        let off = self.utc_offset_s / 60;
+        writeln!(out, "\nTime of Day (UTC{:+03}:{:02})", off / 60, (off % 60).abs())?;
+        writeln!(out, "{}", "=".repeat(50))?;
+        writeln!(out, "{:<8} {:>6} {:>9} {:>7}", "Hour", "Games", "Accuracy", "Score")?;
+        for h in &self.by_hour { row(out, &format!("{:02}:00", h.hour), &h.group)?; }
+        writeln!(out, "{:<8} {:>6} {:>9} {:>7}", "Weekday", "Games", "Accuracy", "Score")?;
+        for d in &self.by_weekday { row(out, d.weekday, &d.group)?; }
+        Ok(())
+    }
+}
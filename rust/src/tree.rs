@@ -0,0 +1,196 @@
+//! The player's games as an opening tree: positions joined by the moves
+//! played from them, with how often, how they scored and how well the
+//! player played afterwards. Written as JSON for explorer front-ends.
+
+use crate::analysis::{side_accuracy, MoveRecord};
+use crate::evalstore::EvalStore;
+use serde::Serialize;
+use shakmaty::fen::Fen;
+use shakmaty::san::San;
+use shakmaty::{CastlingMode, Chess, EnPassantMode, Position};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct EdgeAcc {
+    san: String,
+    uci: String,
+    to: usize,
+    games: usize,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    /// The player's accuracy after the move in each game, by input index.
+    accuracy: Vec<(usize, f64)>,
+}
+
+struct NodeAcc {
+    /// Input index of the first game that reached the position, which gives
+    /// its FEN (the move counters depend on the way there).
+    first: usize,
+    fen: String,
+    games: usize,
+    edges: Vec<EdgeAcc>,
+}
+
+#[derive(Default)]
+struct Nodes {
+    index: HashMap<u64, usize>,
+    nodes: Vec<NodeAcc>,
+}
+
+impl Nodes {
+    fn node(&mut self, pos: &Chess, game: usize) -> usize {
+        let next = self.nodes.len();
+        let id = *self.index.entry(EvalStore::key(pos)).or_insert(next);
+        if id == next {
+            self.nodes.push(NodeAcc { first: usize::MAX, fen: String::new(), games: 0, edges: Vec::new() });
+        }
+        let node = &mut self.nodes[id];
+        if game < node.first {
+            node.first = game;
+            node.fen = Fen::from_position(pos, EnPassantMode::Legal).to_string();
+        }
+        id
+    }
+}
+
+/// Builds the tree from the games of a run. Shared by its workers.
+pub struct TreeBuilder {
+    max_ply: usize,
+    nodes: Mutex<Nodes>,
+}
+
+impl TreeBuilder {
+    /// Takes the first `max_ply` plies of each game.
+    pub fn new(max_ply: usize) -> Self { Self { max_ply, nodes: Mutex::new(Nodes::default()) } }
+
+    /// Adds analyzed game `index` (its place in the input) with the player's
+    /// score (1, 0.5 or 0, if known). Each move's accuracy is the player's
+    /// over the rest of the game from that move on. Transpositions join at
+    /// the same node, and a game counts once per node and move however often
+    /// it repeats them.
+    pub fn add_game(&self, index: usize, records: &[MoveRecord], as_white: bool, score: Option<f64>) {
+        let mut pos = Chess::default();
+        let mut played = Vec::with_capacity(self.max_ply);
+        for (i, r) in records.iter().take(self.max_ply).enumerate() {
+            let Some(mv) = r.san.parse::<San>().ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+            let before = pos.clone();
+            pos.play_unchecked(mv);
+            let uci = mv.to_uci(CastlingMode::Standard).to_string();
+            played.push((before, pos.clone(), r.san.to_string(), uci, side_accuracy(&records[i..], as_white)));
+        }
+        let mut nodes = self.nodes.lock().unwrap();
+        let mut seen_nodes = HashSet::new();
+        let mut seen_edges = HashSet::new();
+        let root = nodes.node(&Chess::default(), index);
+        if seen_nodes.insert(root) { nodes.nodes[root].games += 1; }
+        for (before, after, san, uci, accuracy) in played {
+            let from = nodes.node(&before, index);
+            let to = nodes.node(&after, index);
+            if seen_nodes.insert(to) { nodes.nodes[to].games += 1; }
+            if !seen_edges.insert((from, to)) { continue; }
+            let edges = &mut nodes.nodes[from].edges;
+            let edge = match edges.iter().position(|e| e.to == to) {
+                Some(i) => &mut edges[i],
+                None => {
+                    edges.push(EdgeAcc { san, uci, to, ..Default::default() });
+                    edges.last_mut().expect("just pushed")
+                }
+            };
+            edge.games += 1;
+            match score {
+                Some(1.0) => edge.wins += 1,
+                Some(0.0) => edge.losses += 1,
+                Some(_) => edge.draws += 1,
+                None => {}
+            }
+            edge.accuracy.push((index, accuracy));
+        }
+    }
+
+    /// The tree, nodes numbered breadth first from the start position (0)
+    /// and moves ordered by games, with sums taken in input order, so the
+    /// same games always give the same file. `None` without games.
+    pub fn finish(self, player: &str) -> Option<OpeningTree> {
+        let mut nodes = self.nodes.into_inner().unwrap();
+        let root = *nodes.index.get(&EvalStore::key(&Chess::default()))?;
+        for n in &mut nodes.nodes {
+            n.edges.sort_by(|a, b| b.games.cmp(&a.games).then_with(|| a.san.cmp(&b.san)));
+            for e in &mut n.edges { e.accuracy.sort_by_key(|a| a.0); }
+        }
+        let mut ids = vec![usize::MAX; nodes.nodes.len()];
+        let mut order = Vec::with_capacity(nodes.nodes.len());
+        let mut queue = VecDeque::from([root]);
+        ids[root] = 0;
+        while let Some(n) = queue.pop_front() {
+            order.push(n);
+            for e in &nodes.nodes[n].edges {
+                if ids[e.to] == usize::MAX {
+                    ids[e.to] = order.len() + queue.len();
+                    queue.push_back(e.to);
+                }
+            }
+        }
+        let out = order.iter().enumerate().map(|(id, &n)| {
+            let n = &nodes.nodes[n];
+            TreeNode {
+                id,
+                fen: n.fen.clone(),
+                games: n.games,
+                moves: n.edges.iter().map(|e| {
+                    let decided = e.wins + e.draws + e.losses;
+                    TreeMove {
+                        san: e.san.clone(),
+                        uci: e.uci.clone(),
+                        to: ids[e.to],
+                        games: e.games,
+                        wins: e.wins,
+                        draws: e.draws,
+                        losses: e.losses,
+                        score: (decided > 0).then(|| (e.wins as f64 + 0.5 * e.draws as f64) / decided as f64),
+                        accuracy: e.accuracy.iter().map(|a| a.1).sum::<f64>() / e.games as f64,
+                    }
+                }).collect(),
+            }
+        }).collect();
+        Some(OpeningTree { player: player.to_string(), max_ply: self.max_ply, nodes: out })
+    }
+}
+
+/// A move from a position, with the games that played it.
+#[derive(Serialize, Debug)]
+pub struct TreeMove {
+    pub san: String,
+    pub uci: String,
+    /// Id of the position the move leads to.
+    pub to: usize,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    /// The player's mean score in these games.
+    pub score: Option<f64>,
+    /// The player's mean accuracy from this move to the end of the game.
+    pub accuracy: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TreeNode {
+    pub id: usize,
+    pub fen: String,
+    /// Games that reached the position.
+    pub games: usize,
+    /// Most played first.
+    pub moves: Vec<TreeMove>,
+}
+
+/// Positions of the player's openings, the start position first. Results
+/// are the player's.
+#[derive(Serialize, Debug)]
+pub struct OpeningTree {
+    pub player: String,
+    /// Plies of each game in the tree.
+    pub max_ply: usize,
+    pub nodes: Vec<TreeNode>,
+}
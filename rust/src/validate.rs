@@ -0,0 +1,362 @@
+//! Strict checking of PGN files: every game is replayed and each problem is
+//! reported with its line, where the analysis itself quietly skips or
+//! truncates. A cleaned copy keeps the legal moves and well-formed comments.
+
+use crate::pgn::{split_annotation, GameReader};
+use serde::Serialize;
+use shakmaty::fen::Fen;
+use shakmaty::san::{San, SanError, SanPlus};
+use shakmaty::{CastlingMode, Chess, Position};
+use std::fmt::Write as _;
+use std::io::{self, BufRead};
+
+/// Tags every PGN game should have (the Seven Tag Roster).
+pub const REQUIRED_TAGS: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    MalformedTag,
+    MissingTag,
+    /// Not SAN at all, e.g. "Nf9" or "e4e5".
+    InvalidSan,
+    IllegalMove,
+    AmbiguousMove,
+    UnclosedComment,
+    StrayBrace,
+    UnbalancedVariation,
+    /// The result after the moves differs from the Result tag.
+    ResultMismatch,
+    NoMoves,
+}
+
+impl IssueKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::MalformedTag => "malformed tag",
+            Self::MissingTag => "missing tag",
+            Self::InvalidSan => "invalid SAN",
+            Self::IllegalMove => "illegal move",
+            Self::AmbiguousMove => "ambiguous move",
+            Self::UnclosedComment => "unclosed comment",
+            Self::StrayBrace => "stray brace",
+            Self::UnbalancedVariation => "unbalanced variation",
+            Self::ResultMismatch => "result mismatch",
+            Self::NoMoves => "no moves",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Issue {
+    /// Game number in the file, from 1.
+    pub game: usize,
+    /// Line in the file, from 1.
+    pub line: usize,
+    pub kind: IssueKind,
+    pub detail: String,
+}
+
+/// What the scanner found in the movetext.
+enum Token {
+    /// `nag` is the move's `!`/`?` annotation, kept for the cleaned copy.
+    Move { san: String, nag: Option<u8>, line: usize },
+    Comment(String),
+    Result(String),
+}
+
+/// A game checked, with its repaired form.
+pub struct CheckedGame {
+    pub issues: Vec<Issue>,
+    /// The game rebuilt from its well-formed tags (missing roster tags set
+    /// to "?"), legal moves up to the first bad one with their `!`/`?`
+    /// annotations as NAGs, and comments.
+    pub cleaned: String,
+}
+
+/// Checks one game whose first line is line `first_line` of the file.
+pub fn check_game(game: &str, number: usize, first_line: usize) -> CheckedGame {
+    let mut issues = Vec::new();
+    let mut issue = |line: usize, kind, detail: String| issues.push(Issue { game: number, line, kind, detail });
+
+    // Tag section
+    let mut tags: Vec<(String, String)> = Vec::new();
+    let mut movetext: Vec<(usize, &str)> = Vec::new();
+    for (i, line) in game.lines().enumerate() {
+        let ln = first_line + i;
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && movetext.is_empty() {
+            match parse_tag(trimmed) {
+                Some(tag) => tags.push(tag),
+                None => issue(ln, IssueKind::MalformedTag, trimmed.to_string()),
+            }
+        } else if !trimmed.is_empty() || !movetext.is_empty() {
+            movetext.push((ln, line));
+        }
+    }
+    let tag = |name: &str| tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    for name in REQUIRED_TAGS.iter().filter(|&&n| tag(n).is_none()) {
+        issue(first_line, IssueKind::MissingTag, name.to_string());
+    }
+
+    // Movetext
+    let tokens = scan(&movetext, &mut issue);
+    let mut pos: Chess = match tag("FEN") {
+        Some(fen) => match fen.parse::<Fen>().ok().and_then(|f| f.into_position(CastlingMode::Standard).ok()) {
+            Some(p) => p,
+            None => {
+                issue(first_line, IssueKind::MalformedTag, format!("FEN \"{}\"", fen));
+                Chess::default()
+            }
+        },
+        None => Chess::default(),
+    };
+    let start_ply = if pos.turn().is_white() { 0 } else { 1 };
+    let mut moves: Vec<(String, Option<u8>, Vec<String>)> = Vec::new();
+    let mut leading_comments = Vec::new();
+    let mut broken = false;
+    let mut result_token = None;
+    for token in tokens {
+        match token {
+            Token::Comment(c) => match moves.last_mut() {
+                Some((_, _, comments)) => comments.push(c),
+                None => leading_comments.push(c),
+            },
+            Token::Result(r) => result_token = Some(r),
+            Token::Move { .. } if broken => {}
+            Token::Move { san, nag, line } => {
+                let played = san.parse::<San>().map_err(|_| (IssueKind::InvalidSan, "not SAN")).and_then(|s| {
+                    s.to_move(&pos).map_err(|e| match e {
+                        SanError::IllegalSan => (IssueKind::IllegalMove, "illegal here"),
+                        SanError::AmbiguousSan => (IssueKind::AmbiguousMove, "matches several moves"),
+                    })
+                });
+                match played {
+                    Ok(m) => moves.push((SanPlus::from_move_and_play_unchecked(&mut pos, m).to_string(), nag, Vec::new())),
+                    Err((kind, why)) => {
+                        let ply = start_ply + moves.len() + 1;
+                        issue(line, kind, format!("{}{} {} ({}); later moves not checked",
+                            ply.div_ceil(2), if ply % 2 == 1 { "." } else { "..." }, san, why));
+                        broken = true;
+                    }
+                }
+            }
+        }
+    }
+    if moves.is_empty() && !broken {
+        issue(first_line, IssueKind::NoMoves, String::new());
+    }
+    let tag_result = tag("Result").filter(|r| matches!(*r, "1-0" | "0-1" | "1/2-1/2" | "*"));
+    if let (Some(r), Some(t)) = (&result_token, tag_result) {
+        if r != t { issue(movetext.last().map_or(first_line, |l| l.0), IssueKind::ResultMismatch, format!("movetext {} but Result tag {}", r, t)); }
+    }
+
+    let result = if broken { "*".to_string() } else { tag_result.or(result_token.as_deref()).unwrap_or("*").to_string() };
+    let mut cleaned = String::with_capacity(game.len());
+    for name in REQUIRED_TAGS {
+        let value = if name == "Result" { result.as_str() } else { tag(name).unwrap_or("?") };
+        let _ = writeln!(cleaned, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+    for (name, value) in tags.iter().filter(|(n, _)| !REQUIRED_TAGS.contains(&n.as_str())) {
+        let _ = writeln!(cleaned, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+    cleaned.push('\n');
+    for c in &leading_comments { let _ = write!(cleaned, "{{{}}} ", c); }
+    for (i, (san, nag, comments)) in moves.iter().enumerate() {
+        let ply = start_ply + i + 1;
+        if ply % 2 == 1 { let _ = write!(cleaned, "{}. ", ply.div_ceil(2)); }
+        else if i == 0 { let _ = write!(cleaned, "{}... ", ply / 2); }
+        cleaned.push_str(san);
+        cleaned.push(' ');
+        if let Some(nag) = nag { let _ = write!(cleaned, "${} ", nag); }
+        for c in comments { let _ = write!(cleaned, "{{{}}} ", c); }
+    }
+    cleaned.push_str(&result);
+    cleaned.push('\n');
+    issues.sort_by_key(|i| i.line);
+    CheckedGame { issues, cleaned }
+}
+
+/// Name and unescaped value of a `[Name "Value"]` line.
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, rest) = inner.split_once(char::is_whitespace)?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') { return None; }
+    let quoted = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::with_capacity(quoted.len());
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?),
+            '"' => return None,
+            c => value.push(c),
+        }
+    }
+    Some((name.to_string(), value))
+}
+
+/// Moves, comments and the result of the movetext, skipping move numbers,
+/// NAGs and variations. Unbalanced braces and parentheses are reported.
+fn scan(lines: &[(usize, &str)], issue: &mut impl FnMut(usize, IssueKind, String)) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut comment: Option<(usize, String)> = None;
+    let mut depth = 0usize;
+    let mut variation_line = 0;
+    for &(ln, line) in lines {
+        let mut rest = line;
+        if comment.is_none() && line.starts_with('%') { continue; }
+        while !rest.is_empty() {
+            if let Some((_, text)) = &mut comment {
+                match rest.find('}') {
+                    Some(end) => {
+                        text.push(' ');
+                        text.push_str(rest[..end].trim());
+                        let (_, text) = comment.take().expect("comment is open");
+                        if depth == 0 { tokens.push(Token::Comment(text.trim().to_string())); }
+                        rest = &rest[end + 1..];
+                    }
+                    None => {
+                        text.push(' ');
+                        text.push_str(rest.trim());
+                        rest = "";
+                    }
+                }
+                continue;
+            }
+            rest = rest.trim_start();
+            let Some(c) = rest.chars().next() else { break };
+            match c {
+                '{' => {
+                    comment = Some((ln, String::new()));
+                    rest = &rest[1..];
+                }
+                '}' => {
+                    issue(ln, IssueKind::StrayBrace, "'}' without '{'".into());
+                    rest = &rest[1..];
+                }
+                ';' => {
+                    if depth == 0 { tokens.push(Token::Comment(rest[1..].trim().to_string())); }
+                    rest = "";
+                }
+                '(' => {
+                    if depth == 0 { variation_line = ln; }
+                    depth += 1;
+                    rest = &rest[1..];
+                }
+                ')' => {
+                    match depth.checked_sub(1) {
+                        Some(d) => depth = d,
+                        None => issue(ln, IssueKind::UnbalancedVariation, "')' without '('".into()),
+                    }
+                    rest = &rest[1..];
+                }
+                _ => {
+                    let end = rest.find(|c: char| c.is_whitespace() || "{}();".contains(c)).unwrap_or(rest.len());
+                    let word = &rest[..end];
+                    rest = &rest[end..];
+                    if depth > 0 { continue; }
+                    let word = match word.find('.') {
+                        Some(dot) if word[..dot].bytes().all(|b| b.is_ascii_digit()) => word[dot..].trim_start_matches('.'),
+                        _ => word,
+                    };
+                    if word.is_empty() || word.starts_with('$') { continue; }
+                    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+                        tokens.push(Token::Result(word.to_string()));
+                    } else {
+                        let (san, nag) = split_annotation(word);
+                        tokens.push(Token::Move { san: san.to_string(), nag, line: ln });
+                    }
+                }
+            }
+        }
+    }
+    if let Some((ln, _)) = comment {
+        issue(ln, IssueKind::UnclosedComment, "'{' never closed".into());
+    }
+    if depth > 0 {
+        issue(variation_line, IssueKind::UnbalancedVariation, "'(' never closed".into());
+    }
+    tokens
+}
+
+/// Totals over a file.
+#[derive(Serialize, Debug, Default)]
+pub struct Validation {
+    pub games: usize,
+    /// Games with at least one issue.
+    pub bad_games: usize,
+    pub issues: Vec<Issue>,
+}
+
+/// Checks every game of `input`, passing each cleaned game to `cleaned`.
+pub fn validate(input: impl BufRead, mut cleaned: impl FnMut(&str) -> io::Result<()>) -> io::Result<Validation> {
+    let mut v = Validation::default();
+    let mut line = 1;
+    for game in GameReader::new(input) {
+        let game = game?;
+        // Blank lines before the game belong to it, but not to its first line
+        let lead = game.lines().take_while(|l| l.trim().is_empty()).count();
+        v.games += 1;
+        let checked = check_game(game.trim_start_matches(['\n', '\r', ' ', '\t']), v.games, line + lead);
+        line += game.lines().count();
+        if !checked.issues.is_empty() { v.bad_games += 1; }
+        v.issues.extend(checked.issues);
+        cleaned(&checked.cleaned)?;
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROSTER: &str = "[Event \"Test\"]\n[Site \"?\"]\n[Date \"2024.01.01\"]\n[Round \"1\"]\n[White \"A\"]\n[Black \"B\"]\n[Result \"*\"]\n\n";
+
+    fn check(movetext: &str) -> CheckedGame { check_game(&format!("{}{}\n", ROSTER, movetext), 1, 1) }
+
+    fn kinds(game: &CheckedGame) -> Vec<IssueKind> { game.issues.iter().map(|i| i.kind).collect() }
+
+    #[test]
+    fn annotated_moves_are_legal_and_kept_as_nags() {
+        let game = check("1. e4! e5 2. Nf3 Nc6?! 3. Bb5!! a6?? 4. Ba4!? Nf6? *");
+        assert!(game.issues.is_empty(), "{:?}", game.issues);
+        assert!(game.cleaned.ends_with("\n1. e4 $1 e5 2. Nf3 Nc6 $6 3. Bb5 $3 a6 $4 4. Ba4 $5 Nf6 $2 *\n"), "{}", game.cleaned);
+    }
+
+    #[test]
+    fn invalid_san_stops_the_check() {
+        let game = check("1. e4 Nf9 2. Nf3 *");
+        assert_eq!(kinds(&game), [IssueKind::InvalidSan]);
+        assert!(game.issues[0].detail.starts_with("1... Nf9 (not SAN)"), "{}", game.issues[0].detail);
+        assert!(game.cleaned.ends_with("\n1. e4 *\n"), "{}", game.cleaned);
+    }
+
+    #[test]
+    fn illegal_move() {
+        let game = check("1. e4 e5 2. Ke3 *");
+        assert_eq!(kinds(&game), [IssueKind::IllegalMove]);
+        assert_eq!(game.issues[0].line, 9);
+    }
+
+    #[test]
+    fn ambiguous_move() {
+        // Both knights reach g5
+        let game = check("1. Nc3 a6 2. Ne4 a5 3. Nf3 h6 4. Ng5 *");
+        assert_eq!(kinds(&game), [IssueKind::AmbiguousMove]);
+    }
+
+    #[test]
+    fn missing_tag_is_filled_in() {
+        let game = check_game("[Event \"Test\"]\n[Site \"?\"]\n[Date \"?\"]\n[White \"A\"]\n[Black \"B\"]\n[Result \"*\"]\n\n1. e4 *\n", 1, 1);
+        assert_eq!(kinds(&game), [IssueKind::MissingTag]);
+        assert_eq!(game.issues[0].detail, "Round");
+        assert!(game.cleaned.contains("[Round \"?\"]\n"));
+    }
+
+    #[test]
+    fn unclosed_comment() {
+        let game = check("1. e4 {never closed\ne5 *");
+        assert_eq!(kinds(&game), [IssueKind::UnclosedComment]);
+        assert_eq!(game.issues[0].line, 9);
+    }
+}
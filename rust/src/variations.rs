@@ -0,0 +1,74 @@
+//! Side lines of annotated and study PGNs, scored apart from the mainline.
+
+use crate::accuracy::{mean, Scoring};
+use crate::analysis::{score_moves, AnalysisError, MoveRecord};
+use crate::engine::Eval;
+use crate::evalstore::EvalStore;
+use crate::pgn::Variation;
+use serde::{Deserialize, Serialize};
+use shakmaty::san::San;
+use shakmaty::{Chess, Position};
+use std::collections::HashMap;
+
+/// Evals of the positions a game's lines reached, by position key, so that
+/// each side line searches only the positions new to it.
+pub struct KnownEvals(HashMap<u64, Eval>);
+
+impl KnownEvals {
+    /// The evals after each scored move of the mainline `moves`.
+    pub fn from_line(moves: &[&str], records: &[MoveRecord]) -> Self {
+        let mut known = HashMap::with_capacity(records.len());
+        let mut pos = Chess::default();
+        for (san, r) in moves.iter().zip(records) {
+            let Some(mv) = san.parse::<San>().ok().and_then(|s| s.to_move(&pos).ok()) else { break };
+            pos.play_unchecked(mv);
+            known.insert(EvalStore::key(&pos), r.eval);
+        }
+        Self(known)
+    }
+}
+
+/// Scores the moves of `variation` past its branch point. Positions not in
+/// `known` are evaluated with `evaluate` (position and ply) and added to it.
+pub fn score_variation<'a, F>(variation: &Variation<'a>, known: &mut KnownEvals, scoring: &Scoring, mut evaluate: F) -> Result<Vec<MoveRecord<'a>>, AnalysisError>
+where F: FnMut(&Chess, usize) -> Result<Eval, AnalysisError> {
+    let records = score_moves(&variation.moves, scoring, |pos, ply| {
+        let key = EvalStore::key(pos);
+        if let Some(&eval) = known.0.get(&key) { return Ok(eval); }
+        let eval = evaluate(pos, ply)?;
+        known.0.insert(key, eval);
+        Ok(eval)
+    })?;
+    Ok(records.into_iter().filter(|r| r.ply > variation.branch).collect())
+}
+
+/// One side's moves in the side lines of a game, kept out of its accuracy.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct SideLines {
+    /// Side lines scored.
+    pub lines: usize,
+    pub moves: usize,
+    pub accuracy: f64,
+}
+
+impl SideLines {
+    /// The moves of one side in the scored `lines`; `None` without lines.
+    pub fn from_lines(lines: &[Vec<MoveRecord>], is_white: bool) -> Option<Self> {
+        if lines.is_empty() { return None; }
+        let acc: Vec<f64> = lines.iter().flatten().filter(|r| r.is_white == is_white).map(|r| r.accuracy).collect();
+        Some(Self { lines: lines.len(), moves: acc.len(), accuracy: mean(&acc) })
+    }
+
+    /// Move-weighted combination of per-game side lines.
+    pub fn merge<'a>(items: impl Iterator<Item = &'a SideLines>) -> Option<Self> {
+        let mut seen = false;
+        let (mut lines, mut moves, mut sum) = (0, 0, 0.0);
+        for s in items {
+            seen = true;
+            lines += s.lines;
+            moves += s.moves;
+            sum += s.accuracy * s.moves as f64;
+        }
+        seen.then(|| SideLines { lines, moves, accuracy: if moves == 0 { 0.0 } else { sum / moves as f64 } })
+    }
+}
@@ -0,0 +1,131 @@
+//! A small local web page showing a run as it progresses.
+
+use crate::accuracy::mean;
+use crate::report::GameResult;
+use serde::Serialize;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Games in the rolling accuracy.
+const ROLLING: usize = 20;
+/// Most recent games listed on the page.
+const SHOWN: usize = 100;
+
+const PAGE: &str = r#"<!doctype html>
+<html><head><meta charset="utf-8"><title>chess-bench</title>
+<style>body{font:14px system-ui,sans-serif;margin:2em}table{border-collapse:collapse}td,th{padding:2px 10px;text-align:right}th{border-bottom:1px solid #999}progress{width:30em}</style>
+</head><body>
+<h2 id="title">chess-bench</h2>
+<p><progress id="bar" value="0" max="1"></progress> <span id="count"></span></p>
+<p>Rolling accuracy (last 20 games): <b id="rolling">-</b> &middot; Average: <b id="average">-</b></p>
+<p>Latest games:</p>
+<table><thead><tr><th>#</th><th>White</th><th>Black</th><th>White acc</th><th>Black acc</th><th>Moves</th></tr></thead><tbody id="games"></tbody></table>
+<script>
+async function refresh() {
+  const s = await (await fetch("/status.json")).json();
+  document.getElementById("title").textContent = "chess-bench: " + s.username;
+  document.getElementById("bar").max = s.total || 1;
+  document.getElementById("bar").value = s.done;
+  document.getElementById("count").textContent = s.done + " / " + s.total + " games, " + s.elapsed_s.toFixed(0) + "s";
+  const pct = v => v == null ? "-" : v.toFixed(2) + "%";
+  document.getElementById("rolling").textContent = pct(s.rolling_accuracy);
+  document.getElementById("average").textContent = pct(s.average_accuracy);
+  // Names and the like come from the game files: set as text, never as markup
+  const rows = s.games.slice().reverse().map((g, i) => {
+    const tr = document.createElement("tr");
+    for (const v of [s.completed - i, g.white, g.black, pct(g.white_accuracy), pct(g.black_accuracy), g.moves]) {
+      tr.insertCell().textContent = v;
+    }
+    return tr;
+  });
+  document.getElementById("games").replaceChildren(...rows);
+}
+refresh(); setInterval(refresh, 1000);
+</script></body></html>
+"#;
+
+struct State {
+    done: usize,
+    games: Vec<GameResult>,
+}
+
+/// Run state shared between the workers and the HTTP server thread.
+pub struct Dashboard {
+    username: String,
+    target: String,
+    total: usize,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    username: &'a str,
+    total: usize,
+    done: usize,
+    elapsed_s: f64,
+    rolling_accuracy: Option<f64>,
+    average_accuracy: Option<f64>,
+    /// Games with a result so far; `games` holds the last few of them.
+    completed: usize,
+    games: &'a [GameResult],
+}
+
+impl Dashboard {
+    /// Binds `addr` and serves the page from a background thread.
+    pub fn serve(addr: &str, username: &str, total: usize) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(addr)?;
+        let dash = Arc::new(Self {
+            username: username.to_string(),
+            target: username.to_lowercase(),
+            total,
+            start: Instant::now(),
+            state: Mutex::new(State { done: 0, games: Vec::new() }),
+        });
+        let shared = Arc::clone(&dash);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(e) = shared.respond(stream) { crate::verbose!("  Dashboard request failed: {}", e); }
+            }
+        });
+        Ok(dash)
+    }
+
+    /// Records one finished game; `None` for skipped games.
+    pub fn push(&self, result: Option<&GameResult>) {
+        let mut s = self.state.lock().unwrap();
+        s.done += 1;
+        if let Some(r) = result { s.games.push(r.clone()); }
+    }
+
+    fn status_json(&self) -> serde_json::Result<String> {
+        let s = self.state.lock().unwrap();
+        let acc: Vec<f64> = s.games.iter().map(|g| g.accuracy_for(&self.target)).collect();
+        let recent = &acc[acc.len().saturating_sub(ROLLING)..];
+        serde_json::to_string(&Status {
+            username: &self.username,
+            total: self.total,
+            done: s.done,
+            elapsed_s: self.start.elapsed().as_secs_f64(),
+            rolling_accuracy: (!recent.is_empty()).then(|| mean(recent)),
+            average_accuracy: (!acc.is_empty()).then(|| mean(&acc)),
+            completed: s.games.len(),
+            games: &s.games[s.games.len().saturating_sub(SHOWN)..],
+        })
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        let mut request_line = String::new();
+        BufReader::new(&stream).read_line(&mut request_line)?;
+        let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+        let (status, kind, body) = match path {
+            "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+            "/status.json" => ("200 OK", "application/json", self.status_json()?),
+            _ => ("404 Not Found", "text/plain", "not found\n".to_string()),
+        };
+        write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            status, kind, body.len(), body)
+    }
+}
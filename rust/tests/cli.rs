@@ -0,0 +1,31 @@
+//! Argument parsing and help output of the `benchmark` binary.
+
+use std::process::{Command, Output};
+
+fn benchmark(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_benchmark")).args(args).output().unwrap()
+}
+
+#[test]
+fn top_level_help_describes_the_benchmark() {
+    let out = benchmark(&["--help"]);
+    assert!(out.status.success());
+    let help = String::from_utf8_lossy(&out.stdout);
+    assert!(help.starts_with("Benchmark a chess player's accuracy"), "{}", help);
+}
+
+#[test]
+fn vs_titled_rejects_unknown_titles() {
+    let out = benchmark(&["--vs-titled=GM,XX", "hikaru"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("expected one of GM, WGM"));
+}
+
+#[test]
+fn swindle_threshold_is_an_expected_score() {
+    for bad in ["0", "1", "1.5", "-0.1", "NaN"] {
+        let out = benchmark(&["--swindles", &format!("--swindle-threshold={}", bad), "hikaru"]);
+        assert_eq!(out.status.code(), Some(2), "{}", bad);
+        assert!(String::from_utf8_lossy(&out.stderr).contains("between 0 and 1"), "{}", bad);
+    }
+}
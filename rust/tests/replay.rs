@@ -0,0 +1,112 @@
+//! Full runs offline: the chess.com responses come from the recorded
+//! fixtures in `fixtures/replay` and the engine is a stub that gives every
+//! position the same score, so the numbers do not depend on a Stockfish.
+//!
+//! The stub is a shell script, so these run on Unix only.
+#![cfg(unix)]
+
+use serde_json::Value;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const ENGINE: &str = "#!/bin/sh
+while read -r cmd rest; do
+  case \"$cmd\" in
+    uci) echo \"id name ReplayFish\"; echo \"uciok\" ;;
+    isready) echo \"readyok\" ;;
+    go) echo \"info depth 1 score cp 20 wdl 400 300 300 pv 0000\"; echo \"bestmove 0000\" ;;
+    quit) exit 0 ;;
+  esac
+done
+";
+
+/// A scratch directory holding the stub engine, also used as the config
+/// and cache home so that the run does not touch the user's files.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chess-bench-replay-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let engine = dir.join("engine");
+    std::fs::write(&engine, ENGINE).unwrap();
+    std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755)).unwrap();
+    dir
+}
+
+fn benchmark(name: &str, args: &[&str]) -> Output {
+    let dir = scratch(name);
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/replay");
+    Command::new(env!("CARGO_BIN_EXE_benchmark"))
+        .args(args)
+        .arg("--replay").arg(fixtures)
+        .args(["--skip-engine-check", "-q"])
+        .env("STOCKFISH_PATH", dir.join("engine"))
+        .env("XDG_CONFIG_HOME", &dir)
+        .env("XDG_CACHE_HOME", &dir)
+        .env("XDG_DATA_HOME", &dir)
+        .output()
+        .unwrap()
+}
+
+fn json_run(name: &str, args: &[&str]) -> Value {
+    let out = benchmark(name, &[args, &["--format", "json"]].concat());
+    assert!(out.status.success(), "run failed: {}", String::from_utf8_lossy(&out.stderr));
+    serde_json::from_slice(&out.stdout).unwrap()
+}
+
+#[test]
+fn fetches_and_analyzes_every_recorded_game() {
+    let run = json_run("all", &["hikaru", "20", "--workers", "2"]);
+    let summary = &run["summary"];
+    assert_eq!(summary["username"], "hikaru");
+    assert_eq!(summary["games_analyzed"], 20);
+    assert_eq!(summary["total_moves"], 2202);
+    assert_eq!(summary["engine"]["name"], "ReplayFish");
+    assert_eq!(summary["as_white"]["games"], 10);
+    assert_eq!(summary["as_black"]["games"], 10);
+    assert_eq!(summary["average_accuracy"], 80.0);
+
+    let games = run["games"].as_array().unwrap();
+    assert_eq!(games.len(), 20);
+    let game = games.iter().find(|g| g["url"] == "https://www.chess.com/game/live/200010").unwrap();
+    assert_eq!(game["black"], "opp0");
+    assert_eq!(game["moves"], 126);
+    assert_eq!(game["result"], "0-1");
+    assert_eq!(game["opening"], "A45");
+    assert_eq!(game["time_class"], "blitz");
+    assert_eq!(game["eval_source"], "engine");
+}
+
+#[test]
+fn counts_filtered_games_as_skipped() {
+    let run = json_run("filters", &["hikaru", "20", "--result", "win", "--min-moves", "30"]);
+    let summary = &run["summary"];
+    assert_eq!(summary["games_analyzed"], 7);
+    assert_eq!(summary["skipped"]["result filter"], 12);
+    assert_eq!(summary["skipped"]["too short"], 1);
+    assert_eq!(run["games"].as_array().unwrap().len(), 7);
+}
+
+#[test]
+fn prints_the_text_report() {
+    let out = benchmark("text", &["hikaru", "5", "--workers", "1"]);
+    assert!(out.status.success(), "run failed: {}", String::from_utf8_lossy(&out.stderr));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains("Games analyzed: 5"), "{}", text);
+    assert!(text.contains("Average accuracy for hikaru: 80.00%"), "{}", text);
+}
+
+#[test]
+fn a_player_without_recordings_fails_the_fetch() {
+    let out = benchmark("missing", &["nobody", "20"]);
+    assert_eq!(out.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("was not recorded"));
+}
+
+#[test]
+fn vs_titled_leaves_the_username_positional() {
+    // No titled-player lists are recorded, so every opponent is untitled
+    let out = benchmark("titled", &["--vs-titled", "hikaru", "20"]);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(6), "{}", stderr);
+    assert!(stderr.contains("https://api.chess.com/pub/titled/GM was not recorded"), "{}", stderr);
+}